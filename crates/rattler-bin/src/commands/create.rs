@@ -1,7 +1,7 @@
 use crate::global_multi_progress;
 use anyhow::Context;
 use clap::ValueEnum;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use rattler::install::{IndicatifReporter, Installer};
 use rattler::package_cache::PackageCache;
@@ -13,15 +13,13 @@ use rattler_conda_types::{
     Channel, ChannelConfig, GenericVirtualPackage, MatchSpec, ParseStrictness, Platform,
     PrefixRecord, RepoDataRecord, Version,
 };
-use rattler_networking::{AuthenticationMiddleware, AuthenticationStorage};
 use rattler_repodata_gateway::{Gateway, RepoData};
 use rattler_solve::{
     libsolv_c::{self},
     resolvo, SolverImpl, SolverTask,
 };
-use reqwest::Client;
+use serde::Serialize;
 use std::future::IntoFuture;
-use std::sync::Arc;
 use std::time::Instant;
 use std::{borrow::Cow, env, path::PathBuf, str::FromStr, time::Duration};
 
@@ -33,9 +31,15 @@ pub struct Opt {
     #[clap(required = true)]
     specs: Vec<String>,
 
+    /// Only report the operations that would be performed, without changing
+    /// the environment.
     #[clap(long)]
     dry_run: bool,
 
+    /// The format of the report printed by `--dry-run`.
+    #[clap(long, value_enum, default_value_t, requires = "dry_run")]
+    report_format: ReportFormat,
+
     #[clap(long)]
     platform: Option<String>,
 
@@ -68,6 +72,16 @@ pub enum SolveStrategy {
     LowestDirect,
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A human readable list of operations.
+    #[default]
+    Text,
+
+    /// A JSON document that can be processed by other tools.
+    Json,
+}
+
 #[derive(Default, Debug, Clone, Copy, ValueEnum)]
 pub enum Solver {
     #[default]
@@ -86,6 +100,9 @@ impl From<SolveStrategy> for rattler_solve::SolveStrategy {
 }
 
 pub async fn create(opt: Opt) -> anyhow::Result<()> {
+    // When a JSON report is requested, stdout must only contain the report.
+    let print_progress = !(opt.dry_run && opt.report_format == ReportFormat::Json);
+
     let channel_config = ChannelConfig::default_with_root_dir(env::current_dir()?);
     let current_dir = env::current_dir()?;
     let target_prefix = opt
@@ -94,7 +111,9 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 
     // Make the target prefix absolute
     let target_prefix = std::path::absolute(target_prefix)?;
-    println!("Target prefix: {}", target_prefix.display());
+    if print_progress {
+        println!("Target prefix: {}", target_prefix.display());
+    }
 
     // Determine the platform we're going to install for
     let install_platform = if let Some(platform) = opt.platform {
@@ -103,7 +122,9 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
         Platform::current()
     };
 
-    println!("Installing for platform: {install_platform:?}");
+    if print_progress {
+        println!("Installing for platform: {install_platform:?}");
+    }
 
     // Parse the specs from the command line. We do this explicitly instead of allow clap to deal
     // with this because we need to parse the `channel_config` when parsing matchspecs.
@@ -133,19 +154,7 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
     // For each channel/subdirectory combination, download and cache the `repodata.json` that should
     // be available from the corresponding Url. The code below also displays a nice CLI progress-bar
     // to give users some more information about what is going on.
-    let download_client = Client::builder()
-        .no_gzip()
-        .build()
-        .expect("failed to create client");
-
-    let authentication_storage = AuthenticationStorage::default();
-    let download_client = reqwest_middleware::ClientBuilder::new(download_client)
-        .with_arc(Arc::new(AuthenticationMiddleware::new(
-            authentication_storage,
-        )))
-        .with(rattler_networking::OciMiddleware)
-        .with(rattler_networking::GCSMiddleware)
-        .build();
+    let download_client = super::download_client();
 
     // Get the package names from the matchspecs so we can only load the package records that we need.
     let gateway = Gateway::builder()
@@ -172,11 +181,13 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 
     // Determine the number of recors
     let total_records: usize = repo_data.iter().map(RepoData::len).sum();
-    println!(
-        "Loaded {} records in {:?}",
-        total_records,
-        start_load_repo_data.elapsed()
-    );
+    if print_progress {
+        println!(
+            "Loaded {} records in {:?}",
+            total_records,
+            start_load_repo_data.elapsed()
+        );
+    }
 
     // Determine virtual packages of the system. These packages define the capabilities of the
     // system. Some packages depend on these virtual packages to indicate compatibility with the
@@ -211,12 +222,14 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
         }
     })?;
 
-    println!(
-        "Virtual packages:\n{}\n",
-        virtual_packages
-            .iter()
-            .format_with("\n", |i, f| f(&format_args!("  - {i}",)))
-    );
+    if print_progress {
+        println!(
+            "Virtual packages:\n{}\n",
+            virtual_packages
+                .iter()
+                .format_with("\n", |i, f| f(&format_args!("  - {i}",)))
+        );
+    }
 
     // Now that we parsed and downloaded all information, construct the packaging problem that we
     // need to solve. We do this by constructing a `SolverProblem`. This encapsulates all the
@@ -251,10 +264,13 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
             install_platform,
         )?;
 
-        if transaction.operations.is_empty() {
-            println!("No operations necessary");
-        } else {
-            print_transaction(&transaction);
+        // Determine which packages are already available in the cache. This does not require any
+        // network access.
+        let package_cache = PackageCache::new(cache_dir.join(rattler_cache::PACKAGE_CACHE_DIR));
+        let report = TransactionReport::new(&transaction, &package_cache).await?;
+        match opt.report_format {
+            ReportFormat::Text => report.print(),
+            ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
         }
 
         return Ok(());
@@ -292,7 +308,7 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 }
 
 /// Prints the operations of the transaction to the console.
-fn print_transaction(transaction: &Transaction<PrefixRecord, RepoDataRecord>) {
+pub fn print_transaction(transaction: &Transaction<PrefixRecord, RepoDataRecord>) {
     let format_record = |r: &RepoDataRecord| {
         let direct_url_print = if r.clone().channel.is_empty() {
             r.url.as_str()
@@ -340,6 +356,186 @@ fn print_transaction(transaction: &Transaction<PrefixRecord, RepoDataRecord>) {
     }
 }
 
+/// A report of the operations of a transaction, printed by `--dry-run`.
+#[derive(Debug, Serialize)]
+struct TransactionReport {
+    platform: Platform,
+    operations: Vec<OperationReport>,
+
+    /// The total size of all packages that are installed by the transaction.
+    total_size: u64,
+
+    /// The size of the packages that are not in the package cache and have to
+    /// be downloaded.
+    download_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct OperationReport {
+    operation: OperationKind,
+    name: String,
+
+    /// The package that is currently installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<PackageReport>,
+
+    /// The package that is installed by the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<PackageReport>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum OperationKind {
+    Install,
+    Change,
+    Reinstall,
+    Remove,
+}
+
+#[derive(Debug, Serialize)]
+struct PackageReport {
+    version: String,
+    build: String,
+    channel: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    /// Whether the package is already present in the package cache. Only set
+    /// for packages that are installed by the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cached: Option<bool>,
+}
+
+impl PackageReport {
+    fn new(record: &RepoDataRecord, cached: Option<bool>) -> Self {
+        Self {
+            version: record.package_record.version.to_string(),
+            build: record.package_record.build.clone(),
+            channel: record.channel.clone(),
+            url: record.url.to_string(),
+            size: record.package_record.size,
+            cached,
+        }
+    }
+}
+
+impl TransactionReport {
+    async fn new(
+        transaction: &Transaction<PrefixRecord, RepoDataRecord>,
+        package_cache: &PackageCache,
+    ) -> anyhow::Result<Self> {
+        let mut operations = Vec::with_capacity(transaction.operations.len());
+        let mut total_size = 0;
+        let mut download_size = 0;
+        for operation in &transaction.operations {
+            let (kind, old, new) = match operation {
+                TransactionOperation::Install(new) => (OperationKind::Install, None, Some(new)),
+                TransactionOperation::Change { old, new } => {
+                    (OperationKind::Change, Some(&old.repodata_record), Some(new))
+                }
+                TransactionOperation::Reinstall(old) => (
+                    OperationKind::Reinstall,
+                    Some(&old.repodata_record),
+                    Some(&old.repodata_record),
+                ),
+                TransactionOperation::Remove(old) => {
+                    (OperationKind::Remove, Some(&old.repodata_record), None)
+                }
+            };
+
+            let name = new
+                .or(old)
+                .map(|r| r.package_record.name.as_normalized().to_string())
+                .unwrap_or_default();
+            let new = match new {
+                Some(new) => {
+                    let cached = package_cache.contains(&new.package_record).await?;
+                    let size = new.package_record.size.unwrap_or_default();
+                    total_size += size;
+                    if !cached {
+                        download_size += size;
+                    }
+                    Some(PackageReport::new(new, Some(cached)))
+                }
+                None => None,
+            };
+
+            operations.push(OperationReport {
+                operation: kind,
+                name,
+                old: old.map(|old| PackageReport::new(old, None)),
+                new,
+            });
+        }
+
+        Ok(Self {
+            platform: transaction.platform,
+            operations,
+            total_size,
+            download_size,
+        })
+    }
+
+    /// Prints a human readable version of the report to the console.
+    fn print(&self) {
+        if self.operations.is_empty() {
+            println!("No operations necessary");
+            return;
+        }
+
+        let format_package = |package: &PackageReport| {
+            format!("{} {} {}", package.version, package.build, package.channel)
+        };
+        let format_size = |package: &PackageReport| match (package.cached, package.size) {
+            (Some(true), _) => String::from(" (cached)"),
+            (_, Some(size)) => format!(" ({})", HumanBytes(size)),
+            (_, None) => String::new(),
+        };
+
+        for operation in &self.operations {
+            match (operation.operation, &operation.old, &operation.new) {
+                (OperationKind::Install, _, Some(new)) => println!(
+                    "{} {} {}{}",
+                    console::style("+").green(),
+                    operation.name,
+                    format_package(new),
+                    format_size(new)
+                ),
+                (OperationKind::Change, Some(old), Some(new)) => println!(
+                    "{} {} {} -> {}{}",
+                    console::style("~").yellow(),
+                    operation.name,
+                    format_package(old),
+                    format_package(new),
+                    format_size(new)
+                ),
+                (OperationKind::Reinstall, _, Some(new)) => println!(
+                    "{} {} {}{}",
+                    console::style("~").yellow(),
+                    operation.name,
+                    format_package(new),
+                    format_size(new)
+                ),
+                (_, Some(old), _) => println!(
+                    "{} {} {}",
+                    console::style("-").red(),
+                    operation.name,
+                    format_package(old)
+                ),
+                _ => {}
+            }
+        }
+
+        println!(
+            "\nTotal size: {}, to download: {}",
+            HumanBytes(self.total_size),
+            HumanBytes(self.download_size)
+        );
+    }
+}
+
 /// Displays a spinner with the given message while running the specified function to completion.
 fn wrap_in_progress<T, F: FnOnce() -> T>(msg: impl Into<Cow<'static, str>>, func: F) -> T {
     let pb = ProgressBar::new_spinner();
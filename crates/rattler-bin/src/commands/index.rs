@@ -0,0 +1,113 @@
+use crate::global_multi_progress;
+use indicatif::{ProgressBar, ProgressStyle};
+use rattler_conda_types::Platform;
+use rattler_index::{index_async, IndexOptions, Reporter};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct Opt {
+    /// The directory that contains the channel to index
+    channel_directory: PathBuf,
+
+    /// Only index the subdir of this platform (and `noarch` if it was not
+    /// indexed before)
+    #[clap(long)]
+    target_platform: Option<Platform>,
+}
+
+pub async fn index(opt: Opt) -> anyhow::Result<()> {
+    let reporter = Arc::new(IndexReporter::default());
+    let options = IndexOptions {
+        reporter: Some(reporter.clone()),
+        ..IndexOptions::default()
+    };
+
+    let index_start = Instant::now();
+    let summary = index_async(
+        &opt.channel_directory,
+        opt.target_platform.as_ref(),
+        &options,
+    )
+    .await?;
+
+    if !summary.is_complete() {
+        for (path, error) in &summary.failed {
+            eprintln!(
+                "{} {}: {error}",
+                console::style(console::Emoji("✘", "x")).red(),
+                path.display()
+            );
+        }
+        anyhow::bail!(
+            "failed to read {} package archive(s) in {}",
+            summary.failed.len(),
+            opt.channel_directory.display()
+        );
+    }
+
+    println!(
+        "{} Successfully indexed {} in {:?}",
+        console::style(console::Emoji("✔", "")).green(),
+        opt.channel_directory.display(),
+        index_start.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Shows a progress bar for the subdir that is being indexed.
+#[derive(Default)]
+struct IndexReporter {
+    progress_bar: Mutex<Option<ProgressBar>>,
+}
+
+impl IndexReporter {
+    fn progress_bar(&self) -> Option<ProgressBar> {
+        self.progress_bar.lock().unwrap().clone()
+    }
+}
+
+impl Reporter for IndexReporter {
+    fn on_subdir_start(&self, subdir: &str, archives_to_read: usize) {
+        let pb = global_multi_progress().add(ProgressBar::new(archives_to_read as u64));
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} {prefix:20!} [{bar:20}] {pos:>4}/{len:4} {wide_msg:.dim}",
+            )
+            .unwrap()
+            .progress_chars("━━╾─"),
+        );
+        pb.set_prefix(format!("indexing {subdir}"));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        *self.progress_bar.lock().unwrap() = Some(pb);
+    }
+
+    fn on_read_start(&self, path: &Path) -> usize {
+        if let (Some(pb), Some(file_name)) = (self.progress_bar(), path.file_name()) {
+            pb.set_message(file_name.to_string_lossy().to_string());
+        }
+        0
+    }
+
+    fn on_read_complete(&self, _index: usize) {
+        if let Some(pb) = self.progress_bar() {
+            pb.inc(1);
+        }
+    }
+
+    fn on_subdir_complete(&self, subdir: &str) {
+        if let Some(pb) = self.progress_bar.lock().unwrap().take() {
+            pb.finish_and_clear();
+        }
+        global_multi_progress()
+            .println(format!(
+                "{} Indexed {subdir}",
+                console::style(console::Emoji("✔", "")).green()
+            ))
+            .ok();
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{commands::create::print_transaction, global_multi_progress};
+use anyhow::Context;
+use itertools::Itertools;
+use rattler::install::{IndicatifReporter, Installer};
+use rattler_conda_types::{ExplicitEnvironmentSpec, Platform, PrefixRecord};
+use rattler_lock::LockFile;
+use std::{env, path::PathBuf, time::Instant};
+
+#[derive(Debug, clap::Parser)]
+#[clap(group(clap::ArgGroup::new("source").required(true).args(["lock_file", "explicit"])))]
+pub struct Opt {
+    /// The lock file that contains the packages to install
+    #[clap(long)]
+    lock_file: Option<PathBuf>,
+
+    /// The environment in the lock file to install
+    #[clap(long, default_value = rattler_lock::DEFAULT_ENVIRONMENT_NAME, requires = "lock_file")]
+    environment: String,
+
+    /// An explicit environment file (`@EXPLICIT`) that contains the urls of
+    /// the packages to install
+    #[clap(long)]
+    explicit: Option<PathBuf>,
+
+    /// The platform to install the packages for, defaults to the platform of
+    /// the explicit environment or the current platform
+    #[clap(long)]
+    platform: Option<Platform>,
+
+    #[clap(long)]
+    target_prefix: Option<PathBuf>,
+}
+
+/// Installs the conda packages of an environment in a lock file or an
+/// explicit environment file without solving.
+pub async fn install(opt: Opt) -> anyhow::Result<()> {
+    let current_dir = env::current_dir()?;
+    let target_prefix = opt
+        .target_prefix
+        .unwrap_or_else(|| current_dir.join(".prefix"));
+
+    // Make the target prefix absolute
+    let target_prefix = std::path::absolute(target_prefix)?;
+    println!("Target prefix: {}", target_prefix.display());
+
+    // Determine the packages that are currently installed in the environment.
+    let installed_packages = PrefixRecord::collect_from_prefix(&target_prefix)?;
+
+    let installer = Installer::new()
+        .with_download_client(super::download_client())
+        .with_installed_packages(installed_packages)
+        .with_execute_link_scripts(true)
+        .with_reporter(
+            IndicatifReporter::builder()
+                .with_multi_progress(global_multi_progress())
+                .finish(),
+        );
+
+    let install_start = Instant::now();
+    let result = if let Some(explicit) = &opt.explicit {
+        let spec = ExplicitEnvironmentSpec::from_path(explicit)
+            .with_context(|| format!("failed to read {}", explicit.display()))?;
+        let install_platform = opt
+            .platform
+            .or(spec.platform)
+            .unwrap_or_else(Platform::current);
+        println!("Installing for platform: {install_platform:?}");
+
+        installer
+            .with_target_platform(install_platform)
+            .install_explicit(&target_prefix, &spec)
+            .await?
+    } else {
+        let lock_file_path = opt
+            .lock_file
+            .expect("either a lock file or explicit file is required");
+        let install_platform = opt.platform.unwrap_or_else(Platform::current);
+        println!("Installing for platform: {install_platform:?}");
+
+        let lock_file = LockFile::from_path(&lock_file_path)
+            .with_context(|| format!("failed to read {}", lock_file_path.display()))?;
+        let Some(environment) = lock_file.environment(&opt.environment) else {
+            anyhow::bail!(
+                "the lock file does not contain an environment named '{}', available environments are: {}",
+                opt.environment,
+                lock_file.environments().map(|(name, _)| name).sorted().format(", ")
+            );
+        };
+        let Some(records) = environment.conda_repodata_records_for_platform(install_platform)?
+        else {
+            anyhow::bail!(
+                "the environment '{}' is not locked for {install_platform}, available platforms are: {}",
+                opt.environment,
+                environment.platforms().sorted().format(", ")
+            );
+        };
+
+        installer
+            .with_target_platform(install_platform)
+            .install(&target_prefix, records)
+            .await?
+    };
+
+    if result.transaction.operations.is_empty() {
+        println!(
+            "{} Already up to date",
+            console::style(console::Emoji("✔", "")).green(),
+        );
+    } else {
+        println!(
+            "{} Successfully updated the environment in {:?}",
+            console::style(console::Emoji("✔", "")).green(),
+            install_start.elapsed()
+        );
+        print_transaction(&result.transaction);
+    }
+
+    Ok(())
+}
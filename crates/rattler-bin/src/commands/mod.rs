@@ -1,2 +1,29 @@
+use std::sync::Arc;
+
+use rattler_networking::{AuthenticationMiddleware, AuthenticationStorage};
+use reqwest::Client;
+use reqwest_middleware::ClientWithMiddleware;
+
 pub mod create;
+pub mod index;
+pub mod install;
 pub mod virtual_packages;
+
+/// Constructs the client that is used to download repodata and packages.
+pub fn download_client() -> ClientWithMiddleware {
+    let download_client = Client::builder()
+        .no_gzip()
+        .build()
+        .expect("failed to create client");
+
+    let authentication_storage = AuthenticationStorage::default();
+    reqwest_middleware::ClientBuilder::new(download_client.clone())
+        .with_arc(Arc::new(
+            AuthenticationMiddleware::new(authentication_storage)
+                .with_oauth_client(download_client),
+        ))
+        .with(rattler_networking::OciMiddleware)
+        .with(rattler_networking::GCSMiddleware)
+        .with(rattler_networking::S3Middleware::default())
+        .build()
+}
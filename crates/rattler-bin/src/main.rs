@@ -38,6 +38,8 @@ struct Opt {
 #[derive(Debug, clap::Subcommand)]
 enum Command {
     Create(commands::create::Opt),
+    Index(commands::index::Opt),
+    Install(commands::install::Opt),
     VirtualPackages(commands::virtual_packages::Opt),
 }
 
@@ -71,6 +73,8 @@ async fn main() -> anyhow::Result<()> {
     // Dispatch the selected comment
     match opt.command {
         Command::Create(opts) => commands::create::create(opts).await,
+        Command::Index(opts) => commands::index::index(opts).await,
+        Command::Install(opts) => commands::install::install(opts).await,
         Command::VirtualPackages(opts) => commands::virtual_packages::virtual_packages(opts),
     }
 }
@@ -1,7 +1,13 @@
 //! This module contains CLI common entrypoint for authentication.
+use std::time::{Duration, Instant};
+
 use clap::Parser;
-use rattler_networking::{Authentication, AuthenticationStorage};
+use rattler_networking::{
+    oauth::{DeviceTokenStatus, OAuthClient, OAuthError, OidcConfiguration},
+    Authentication, AuthenticationStorage,
+};
 use thiserror;
+use url::Url;
 
 /// Command line arguments that contain authentication data
 #[derive(Parser, Debug)]
@@ -24,6 +30,35 @@ pub struct LoginArgs {
     /// The token to use on anaconda.org / quetz authentication
     #[clap(long)]
     conda_token: Option<String>,
+
+    /// The access key ID to use for an S3 bucket (e.g. `s3://my-bucket`)
+    #[clap(long, requires = "s3_secret_access_key")]
+    s3_access_key_id: Option<String>,
+
+    /// The secret access key to use for an S3 bucket
+    #[clap(long, requires = "s3_access_key_id")]
+    s3_secret_access_key: Option<String>,
+
+    /// The session token to use for an S3 bucket with temporary credentials
+    #[clap(long, requires = "s3_access_key_id")]
+    s3_session_token: Option<String>,
+
+    /// The OIDC issuer to log in with through the device
+    /// authorization flow (e.g. `https://auth.example.com/realms/conda`)
+    #[clap(long, requires = "oauth_client_id")]
+    oauth_issuer: Option<Url>,
+
+    /// The client ID that is registered with the OIDC issuer
+    #[clap(long, requires = "oauth_issuer")]
+    oauth_client_id: Option<String>,
+
+    /// The scopes to request from the OIDC issuer
+    #[clap(
+        long = "oauth-scope",
+        requires = "oauth_issuer",
+        default_values = ["openid", "offline_access"]
+    )]
+    oauth_scopes: Vec<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -35,7 +70,7 @@ struct LogoutArgs {
 #[derive(Parser, Debug)]
 enum Subcommand {
     /// Store authentication information for a given host
-    Login(LoginArgs),
+    Login(Box<LoginArgs>),
     /// Remove authentication information for a given host
     Logout(LogoutArgs),
 }
@@ -71,6 +106,10 @@ pub enum AuthenticationCLIError {
     #[error("Authentication with anaconda.org requires a conda token. Use `--conda-token` to provide one")]
     AnacondaOrgBadMethod,
 
+    /// Logging in with the OIDC issuer failed
+    #[error("Failed to log in with the OIDC issuer")]
+    OAuthError(#[from] OAuthError),
+
     /// Wrapper for errors that are generated from the underlying storage system
     /// (keyring or file system)
     #[error("Failed to interact with the authentication storage system")]
@@ -95,7 +134,53 @@ fn get_url(url: &str) -> Result<String, AuthenticationCLIError> {
     Ok(host)
 }
 
-fn login(args: LoginArgs, storage: AuthenticationStorage) -> Result<(), AuthenticationCLIError> {
+/// Obtains tokens from an OIDC issuer with the device authorization
+/// flow: the user authorizes rattler in a browser while we poll for the tokens.
+async fn device_login(
+    issuer: &Url,
+    client_id: String,
+    scopes: &[String],
+) -> Result<Authentication, OAuthError> {
+    let client = reqwest::Client::new();
+    let configuration = OidcConfiguration::discover(&client, issuer).await?;
+    let device_authorization_endpoint = configuration
+        .device_authorization_endpoint
+        .ok_or(OAuthError::DeviceFlowNotSupported)?;
+
+    let oauth = OAuthClient::new(client, configuration.token_endpoint, client_id);
+    let device = oauth
+        .request_device_authorization(&device_authorization_endpoint, scopes)
+        .await?;
+
+    match &device.verification_uri_complete {
+        Some(uri) => println!("To authenticate, open {uri} in a browser"),
+        None => println!(
+            "To authenticate, open {} in a browser and enter the code {}",
+            device.verification_uri, device.user_code
+        ),
+    }
+
+    // Stop polling once the device code has expired, even if the provider does
+    // not report `expired_token`.
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval);
+    loop {
+        if Instant::now() + interval > deadline {
+            return Err(OAuthError::DeviceAuthorizationExpired);
+        }
+        tokio::time::sleep(interval).await;
+        match oauth.poll_device_token(&device).await? {
+            DeviceTokenStatus::Pending => {}
+            DeviceTokenStatus::SlowDown => interval += Duration::from_secs(5),
+            DeviceTokenStatus::Authorized(auth) => return Ok(auth),
+        }
+    }
+}
+
+async fn login(
+    args: LoginArgs,
+    storage: AuthenticationStorage,
+) -> Result<(), AuthenticationCLIError> {
     let host = get_url(&args.host)?;
     println!("Authenticating with {host}");
 
@@ -110,6 +195,16 @@ fn login(args: LoginArgs, storage: AuthenticationStorage) -> Result<(), Authenti
         }
     } else if let Some(token) = args.token {
         Authentication::BearerToken(token)
+    } else if let (Some(access_key_id), Some(secret_access_key)) =
+        (args.s3_access_key_id, args.s3_secret_access_key)
+    {
+        Authentication::S3Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token: args.s3_session_token,
+        }
+    } else if let (Some(issuer), Some(client_id)) = (args.oauth_issuer, args.oauth_client_id) {
+        device_login(&issuer, client_id, &args.oauth_scopes).await?
     } else {
         return Err(AuthenticationCLIError::NoAuthenticationMethod);
     };
@@ -144,7 +239,7 @@ pub async fn execute(args: Args) -> Result<(), AuthenticationCLIError> {
     let storage = AuthenticationStorage::default();
 
     match args.subcommand {
-        Subcommand::Login(args) => login(args, storage),
+        Subcommand::Login(args) => login(*args, storage).await,
         Subcommand::Logout(args) => logout(args, storage),
     }
 }
@@ -19,6 +19,10 @@ pub enum InstallerError {
     #[error("failed to construct a transaction")]
     FailedToConstructTransaction(#[from] TransactionError),
 
+    /// Failed to determine the records of an explicit environment
+    #[error("failed to resolve the packages of the explicit environment")]
+    ExplicitEnvironmentError(#[from] super::ExplicitEnvironmentError),
+
     /// Failed to populate the cache with the package
     #[error("failed to fetch {0}")]
     FailedToFetch(String, #[source] PackageCacheError),
@@ -0,0 +1,296 @@
+use futures::future::try_join_all;
+use rattler_conda_types::{
+    package::{ArchiveIdentifier, IndexJson, PackageFile},
+    ConvertSubdirError, ExplicitEnvironmentEntry, PackageArchiveHash, PackageRecord,
+    ParsePackageArchiveHashError, RepoDataRecord,
+};
+use rattler_digest::Md5Hash;
+use rattler_networking::retry_policies::default_retry_policy;
+use rattler_package_streaming::ExtractError;
+use simple_spawn_blocking::{tokio::run_blocking_task, Cancelled};
+use std::path::PathBuf;
+use url::Url;
+
+use crate::package_cache::{CacheKey, PackageCache, PackageCacheError};
+
+/// An error that can occur when resolving the entries of an explicit
+/// environment with [`resolve_explicit_environment`].
+#[derive(Debug, thiserror::Error)]
+pub enum ExplicitEnvironmentError {
+    /// The hash in the url of an entry could not be parsed.
+    #[error("invalid package archive hash in {0}")]
+    InvalidPackageArchiveHash(Url, #[source] ParsePackageArchiveHashError),
+
+    /// The url of an entry does not refer to a conda package archive.
+    #[error("{0} does not refer to a conda package archive")]
+    InvalidPackageUrl(Url),
+
+    /// Failed to populate the cache with the package.
+    #[error("failed to fetch {0}")]
+    FailedToFetch(String, #[source] PackageCacheError),
+
+    /// The md5 hash of the downloaded package does not match the hash in the
+    /// url of the entry.
+    #[error("the md5 hash of {0} is {2:x}, but {1:x} was expected")]
+    Md5Mismatch(String, Md5Hash, Md5Hash),
+
+    /// Failed to read the `info/index.json` of the cached package.
+    #[error("failed to read the index.json of {0}")]
+    FailedToReadIndexJson(String, #[source] std::io::Error),
+
+    /// The `info/index.json` of the cached package is invalid.
+    #[error("invalid index.json in {0}")]
+    InvalidIndexJson(String, #[source] ConvertSubdirError),
+
+    /// The operation was cancelled
+    #[error("the operation was cancelled")]
+    Cancelled,
+}
+
+impl From<Cancelled> for ExplicitEnvironmentError {
+    fn from(_: Cancelled) -> Self {
+        ExplicitEnvironmentError::Cancelled
+    }
+}
+
+/// Converts the entries of an explicit environment to [`RepoDataRecord`]s
+/// without fetching any repodata.
+///
+/// Every package is fetched into the `package_cache` if it is not already
+/// there, after which the record is constructed from the `info/index.json` of
+/// the cached package. The hash in the url of an entry, if any, is used to
+/// identify the package in the cache and is stored in the resulting record. An
+/// md5 hash is verified when the package is downloaded.
+///
+/// The records are returned in the same order as the entries.
+pub async fn resolve_explicit_environment(
+    entries: &[ExplicitEnvironmentEntry],
+    package_cache: &PackageCache,
+    downloader: reqwest_middleware::ClientWithMiddleware,
+) -> Result<Vec<RepoDataRecord>, ExplicitEnvironmentError> {
+    try_join_all(
+        entries
+            .iter()
+            .map(|entry| resolve_explicit_entry(entry, package_cache, downloader.clone())),
+    )
+    .await
+}
+
+async fn resolve_explicit_entry(
+    entry: &ExplicitEnvironmentEntry,
+    package_cache: &PackageCache,
+    downloader: reqwest_middleware::ClientWithMiddleware,
+) -> Result<RepoDataRecord, ExplicitEnvironmentError> {
+    let (md5, sha256) = match entry.package_archive_hash() {
+        Ok(Some(PackageArchiveHash::Md5(md5))) => (Some(md5), None),
+        Ok(Some(PackageArchiveHash::Sha256(sha256))) => (None, Some(sha256)),
+        Ok(None) => (None, None),
+        Err(e) => {
+            return Err(ExplicitEnvironmentError::InvalidPackageArchiveHash(
+                entry.url.clone(),
+                e,
+            ))
+        }
+    };
+
+    // The hash is not part of the location of the package.
+    let mut url = entry.url.clone();
+    url.set_fragment(None);
+
+    let identifier = ArchiveIdentifier::try_from_url(&url)
+        .ok_or_else(|| ExplicitEnvironmentError::InvalidPackageUrl(url.clone()))?;
+    let file_name = identifier.to_file_name();
+    let cache_key = CacheKey::from(identifier).with_opt_sha256(sha256);
+
+    let cache_lock = match md5 {
+        // The package cache only knows about sha256 hashes, so an md5 hash is
+        // verified while fetching the package.
+        Some(md5) => {
+            let url = url.clone();
+            package_cache
+                .get_or_fetch(
+                    cache_key,
+                    move |destination| {
+                        fetch_with_md5(downloader.clone(), url.clone(), destination, md5)
+                    },
+                    None,
+                )
+                .await
+        }
+        None => {
+            package_cache
+                .get_or_fetch_from_url_with_retry(
+                    cache_key,
+                    url.clone(),
+                    downloader,
+                    default_retry_policy(),
+                    None,
+                )
+                .await
+        }
+    }
+    .map_err(|e| {
+        if let (Some(md5), PackageCacheError::FetchError(err)) = (md5, &e) {
+            if let Some(Md5FetchError::Mismatch(actual)) = err.downcast_ref() {
+                return ExplicitEnvironmentError::Md5Mismatch(file_name.clone(), md5, *actual);
+            }
+        }
+        ExplicitEnvironmentError::FailedToFetch(file_name.clone(), e)
+    })?;
+
+    let package_dir = cache_lock.path().to_path_buf();
+    let index_json = {
+        let file_name = file_name.clone();
+        run_blocking_task(move || {
+            IndexJson::from_package_directory(package_dir)
+                .map_err(|e| ExplicitEnvironmentError::FailedToReadIndexJson(file_name, e))
+        })
+        .await?
+    };
+    let package_record = PackageRecord::from_index_json(index_json, None, sha256, md5)
+        .map_err(|e| ExplicitEnvironmentError::InvalidIndexJson(file_name.clone(), e))?;
+
+    // The channel is the url of the directory that contains the subdir.
+    let channel = url
+        .join("..")
+        .map_or_else(|_| String::new(), |channel| channel.to_string());
+
+    Ok(RepoDataRecord {
+        package_record,
+        file_name,
+        url,
+        channel,
+    })
+}
+
+/// An error that can occur when fetching a package with
+/// [`fetch_with_md5`].
+#[derive(Debug, thiserror::Error)]
+enum Md5FetchError {
+    #[error(transparent)]
+    Extract(#[from] ExtractError),
+
+    #[error("the md5 hash of the package is {0:x}")]
+    Mismatch(Md5Hash),
+}
+
+impl From<Cancelled> for Md5FetchError {
+    fn from(_: Cancelled) -> Self {
+        Md5FetchError::Extract(ExtractError::Cancelled)
+    }
+}
+
+/// Extracts the package at `url` to `destination` and verifies that the md5
+/// hash of the archive matches `md5`. On a mismatch the extracted package is
+/// removed again so it is not mistaken for a valid cache entry.
+async fn fetch_with_md5(
+    client: reqwest_middleware::ClientWithMiddleware,
+    url: Url,
+    destination: PathBuf,
+    md5: Md5Hash,
+) -> Result<(), Md5FetchError> {
+    let result =
+        rattler_package_streaming::reqwest::tokio::extract(client, url, &destination, None, None)
+            .await?;
+    if result.md5 == md5 {
+        return Ok(());
+    }
+
+    run_blocking_task(move || {
+        fs_err::remove_dir_all(&destination).map_err(|e| Md5FetchError::from(ExtractError::from(e)))
+    })
+    .await?;
+    Err(Md5FetchError::Mismatch(result.md5))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use rattler_conda_types::{ExplicitEnvironmentSpec, PrefixRecord};
+    use rattler_digest::{Md5, Sha256};
+    use tempfile::tempdir;
+    use url::Url;
+
+    use super::{resolve_explicit_environment, ExplicitEnvironmentError};
+    use crate::{get_test_data_dir, install::Installer, package_cache::PackageCache};
+
+    #[tokio::test]
+    async fn test_install_explicit_environment() {
+        let test_data_dir = get_test_data_dir().canonicalize().unwrap();
+        let package_path = test_data_dir.join("clobber/clobber-1-0.1.0-h4616a5c_0.tar.bz2");
+        let sha256 = rattler_digest::compute_file_digest::<Sha256>(&package_path).unwrap();
+        let md5 = rattler_digest::compute_file_digest::<Md5>(&package_path).unwrap();
+        let url = Url::from_file_path(&package_path).unwrap();
+        let spec = ExplicitEnvironmentSpec::from_str(&format!(
+            "# platform: linux-64\n@EXPLICIT\n{url}#{sha256:x}\n"
+        ))
+        .unwrap();
+
+        let cache_dir = tempdir().unwrap();
+        let package_cache = PackageCache::new(cache_dir.path());
+        let client = reqwest_middleware::ClientWithMiddleware::from(reqwest::Client::new());
+        let records = resolve_explicit_environment(&spec.packages, &package_cache, client.clone())
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.package_record.name.as_normalized(), "clobber-1");
+        assert_eq!(record.package_record.version.as_str(), "0.1.0");
+        assert_eq!(record.package_record.sha256, Some(sha256));
+        assert_eq!(record.package_record.md5, None);
+        assert_eq!(record.file_name, "clobber-1-0.1.0-h4616a5c_0.tar.bz2");
+        assert_eq!(record.url, url);
+        assert_eq!(
+            record.channel,
+            Url::from_directory_path(&test_data_dir)
+                .unwrap()
+                .to_string()
+        );
+
+        // An md5 hash is stored in the record as well.
+        let spec =
+            ExplicitEnvironmentSpec::from_str(&format!("@EXPLICIT\n{url}#{md5:x}\n")).unwrap();
+        let records = resolve_explicit_environment(&spec.packages, &package_cache, client.clone())
+            .await
+            .unwrap();
+        assert_eq!(records[0].package_record.md5, Some(md5));
+
+        // A package that does not match its md5 hash is rejected and not cached.
+        let wrong_md5 = rattler_digest::compute_bytes_digest::<Md5>("other");
+        let wrong_cache_dir = tempdir().unwrap();
+        let spec = ExplicitEnvironmentSpec::from_str(&format!("@EXPLICIT\n{url}#{wrong_md5:x}\n"))
+            .unwrap();
+        let err = resolve_explicit_environment(
+            &spec.packages,
+            &PackageCache::new(wrong_cache_dir.path()),
+            client.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ExplicitEnvironmentError::Md5Mismatch(_, expected, actual)
+                if expected == wrong_md5 && actual == md5
+        ));
+        assert!(!wrong_cache_dir
+            .path()
+            .join("clobber-1-0.1.0-h4616a5c_0")
+            .exists());
+
+        let spec =
+            ExplicitEnvironmentSpec::from_str(&format!("@EXPLICIT\n{url}#{md5:x}\n")).unwrap();
+
+        let prefix = tempdir().unwrap();
+        let result = Installer::new()
+            .with_package_cache(package_cache)
+            .with_download_client(client)
+            .install_explicit(prefix.path(), &spec)
+            .await
+            .unwrap();
+        assert_eq!(result.transaction.operations.len(), 1);
+        let installed = PrefixRecord::collect_from_prefix(prefix.path()).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert!(prefix.path().join("clobber.txt").is_file());
+    }
+}
@@ -1,4 +1,5 @@
 mod error;
+mod explicit;
 #[cfg(feature = "indicatif")]
 mod indicatif;
 mod reporter;
@@ -17,6 +18,7 @@ use crate::{
     package_cache::PackageCache,
 };
 pub use error::InstallerError;
+pub use explicit::{resolve_explicit_environment, ExplicitEnvironmentError};
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt, TryFutureExt};
 #[cfg(feature = "indicatif")]
 pub use indicatif::{
@@ -27,7 +29,7 @@ use rattler_cache::package_cache::CacheLock;
 use rattler_cache::package_cache::CacheReporter;
 use rattler_conda_types::{
     prefix_record::{Link, LinkType},
-    Platform, PrefixRecord, RepoDataRecord,
+    ExplicitEnvironmentSpec, Platform, PrefixRecord, RepoDataRecord,
 };
 use rattler_networking::retry_policies::default_retry_policy;
 pub use reporter::Reporter;
@@ -265,6 +267,36 @@ impl Installer {
         self
     }
 
+    /// Install the packages of an explicit environment in the given prefix.
+    ///
+    /// No repodata is required, instead the records of the packages are read
+    /// from the packages themselves, see [`resolve_explicit_environment`]. If
+    /// no target platform was set, the platform of the explicit environment is
+    /// used.
+    pub async fn install_explicit(
+        mut self,
+        prefix: impl AsRef<Path>,
+        spec: &ExplicitEnvironmentSpec,
+    ) -> Result<InstallationResult, InstallerError> {
+        let downloader = self
+            .downloader
+            .get_or_insert_with(
+                || reqwest_middleware::ClientWithMiddleware::from(Client::default()),
+            )
+            .clone();
+        let package_cache = self
+            .package_cache
+            .get_or_insert_with(default_package_cache)
+            .clone();
+        if self.target_platform.is_none() {
+            self.target_platform = spec.platform;
+        }
+
+        let records =
+            resolve_explicit_environment(&spec.packages, &package_cache, downloader).await?;
+        self.install(prefix, records).await
+    }
+
     /// Install the packages in the given prefix.
     pub async fn install(
         self,
@@ -274,13 +306,7 @@ impl Installer {
         let downloader = self
             .downloader
             .unwrap_or_else(|| reqwest_middleware::ClientWithMiddleware::from(Client::default()));
-        let package_cache = self.package_cache.unwrap_or_else(|| {
-            PackageCache::new(
-                default_cache_dir()
-                    .expect("failed to determine default cache directory")
-                    .join(rattler_cache::PACKAGE_CACHE_DIR),
-            )
-        });
+        let package_cache = self.package_cache.unwrap_or_else(default_package_cache);
 
         // Create a future to determine the currently installed packages. We
         // can start this in parallel with the other operations and resolve it
@@ -455,6 +481,15 @@ impl Installer {
     }
 }
 
+/// Returns the package cache in the default cache directory.
+fn default_package_cache() -> PackageCache {
+    PackageCache::new(
+        default_cache_dir()
+            .expect("failed to determine default cache directory")
+            .join(rattler_cache::PACKAGE_CACHE_DIR),
+    )
+}
+
 async fn link_package(
     record: &RepoDataRecord,
     target_prefix: &Path,
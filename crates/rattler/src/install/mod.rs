@@ -42,12 +42,14 @@ use std::{
 pub use apple_codesign::AppleCodeSignBehavior;
 pub use driver::InstallDriver;
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+pub use installer::{
+    resolve_explicit_environment, ExplicitEnvironmentError, Installer, InstallerError, Reporter,
+};
 #[cfg(feature = "indicatif")]
 pub use installer::{
     DefaultProgressFormatter, IndicatifReporter, IndicatifReporterBuilder, Placement,
     ProgressFormatter,
 };
-pub use installer::{Installer, InstallerError, Reporter};
 use itertools::Itertools;
 pub use link::{link_file, LinkFileError, LinkMethod};
 pub use python::PythonInfo;
@@ -108,6 +108,33 @@ impl PackageCache {
         }
     }
 
+    /// Returns `true` if the cache contains the specified package.
+    ///
+    /// Unlike [`Self::get_or_fetch`] this never fetches the package and does
+    /// not validate the contents of the cached package. If the sha256 hash of
+    /// the package is known it is compared with the hash that was recorded
+    /// when the package was added to the cache.
+    pub async fn contains(&self, pkg: impl Into<CacheKey>) -> Result<bool, PackageCacheError> {
+        let cache_key = pkg.into();
+        let cache_path = self.inner.path.join(cache_key.to_string());
+        if !cache_path.is_dir() {
+            return Ok(false);
+        }
+
+        let lock_file_path = cache_path.with_extension("lock");
+        let Some(given_sha) = cache_key.sha256.as_ref() else {
+            return Ok(true);
+        };
+        if !lock_file_path.is_file() {
+            return Ok(true);
+        }
+
+        let mut read_lock = CacheRwLock::acquire_read(&lock_file_path).await?;
+        Ok(read_lock
+            .read_sha256()?
+            .map_or(true, |locked_sha256| &locked_sha256 == given_sha))
+    }
+
     /// Returns the directory that contains the specified package.
     ///
     /// If the package was previously successfully fetched and stored in the
@@ -543,6 +570,40 @@ mod test {
         assert_eq!(current_paths, paths);
     }
 
+    #[tokio::test]
+    pub async fn test_package_cache_contains() {
+        let archive_path = get_test_data_dir().join("clobber/clobber-1-0.1.0-h4616a5c_0.tar.bz2");
+        let archive_identifier = ArchiveIdentifier::try_from_path(&archive_path).unwrap();
+        let sha256 = rattler_digest::compute_file_digest::<Sha256>(&archive_path).unwrap();
+        let cache_key = CacheKey::from(archive_identifier).with_sha256(sha256);
+
+        let packages_dir = tempdir().unwrap();
+        let cache = PackageCache::new(packages_dir.path());
+        assert!(!cache.contains(cache_key.clone()).await.unwrap());
+
+        cache
+            .get_or_fetch(
+                cache_key.clone(),
+                move |destination| {
+                    let archive_path = archive_path.clone();
+                    async move {
+                        rattler_package_streaming::tokio::fs::extract(&archive_path, &destination)
+                            .await
+                            .map(|_| ())
+                    }
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(cache.contains(cache_key.clone()).await.unwrap());
+
+        // A package with the same name but a different hash is not cached.
+        let other_key =
+            cache_key.with_sha256(rattler_digest::compute_bytes_digest::<Sha256>("other"));
+        assert!(!cache.contains(other_key).await.unwrap());
+    }
+
     /// A helper middleware function that fails the first two requests.
     async fn fail_the_first_two_requests(
         State(count): State<Arc<Mutex<i32>>>,
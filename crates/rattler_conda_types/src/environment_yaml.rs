@@ -7,7 +7,10 @@ use serde::{
     Deserializer, Serializer,
 };
 
-use crate::{MatchSpec, NamedChannelOrUrl, ParseStrictness};
+use crate::{
+    version_spec::EqualityOperator, MatchSpec, NamedChannelOrUrl, ParseStrictness, PrefixRecord,
+    RepoDataRecord, StringMatcher, VersionSpec,
+};
 
 /// A representation of an `environment.yaml` file.
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -65,6 +68,64 @@ impl MatchSpecOrSubSection {
 }
 
 impl EnvironmentYaml {
+    /// Constructs an `environment.yaml` that pins every package of a set of
+    /// records, for instance the records of a lock file environment or the
+    /// [`crate::PrefixRecord::repodata_record`]s of an installed prefix.
+    ///
+    /// Every package is pinned to its exact version and build string together
+    /// with the md5 and sha256 hashes of its archive when they are known. The
+    /// channels of the records are added in the order in which they first
+    /// appear.
+    pub fn from_repodata_records<'a>(
+        records: impl IntoIterator<Item = &'a RepoDataRecord>,
+    ) -> Self {
+        let mut channels = Vec::new();
+        let mut dependencies = Vec::new();
+        for record in records {
+            if !record.channel.is_empty() {
+                if let Ok(channel) = record.channel.parse::<NamedChannelOrUrl>() {
+                    if !channels.contains(&channel) {
+                        channels.push(channel);
+                    }
+                }
+            }
+
+            let package_record = &record.package_record;
+            dependencies.push(MatchSpec {
+                name: Some(package_record.name.clone()),
+                version: Some(VersionSpec::Exact(
+                    EqualityOperator::Equals,
+                    package_record.version.version().clone(),
+                )),
+                build: (!package_record.build.is_empty())
+                    .then(|| StringMatcher::Exact(package_record.build.clone())),
+                md5: package_record.md5,
+                sha256: package_record.sha256,
+                ..MatchSpec::default()
+            });
+        }
+
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            channels,
+            dependencies: dependencies
+                .into_iter()
+                .map(MatchSpecOrSubSection::MatchSpec)
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Constructs an `environment.yaml` that pins the packages installed in a
+    /// prefix, as read by [`PrefixRecord::collect_from_prefix`].
+    pub fn from_prefix(prefix: &Path) -> Result<Self, std::io::Error> {
+        let records = PrefixRecord::collect_from_prefix(prefix)?;
+        Ok(Self::from_repodata_records(
+            records.iter().map(|record| &record.repodata_record),
+        ))
+    }
+
     /// Returns all the matchspecs in the `dependencies` section of the file.
     pub fn match_specs(&self) -> impl DoubleEndedIterator<Item = &'_ MatchSpec> + '_ {
         self.dependencies
@@ -199,4 +260,42 @@ mod tests {
         .unwrap();
         insta::assert_debug_snapshot!(environment_yaml.pip_specs());
     }
+
+    #[test]
+    fn test_from_repodata_records() {
+        let repodata = crate::RepoData::from_path(
+            get_test_data_dir().join("channels/dummy/linux-64/repodata.json"),
+        )
+        .unwrap();
+        let records = repodata.into_repo_data_records(&crate::Channel::from_url(
+            url::Url::parse("https://conda.anaconda.org/dummy/").unwrap(),
+        ));
+
+        let environment_yaml = EnvironmentYaml::from_repodata_records(records.iter().take(5));
+        insta::assert_snapshot!(environment_yaml.to_yaml_string());
+
+        // Make sure the pinned specs round trip and match the records again.
+        let round_trip =
+            EnvironmentYaml::from_yaml_str(&environment_yaml.to_yaml_string()).unwrap();
+        assert_eq!(round_trip.dependencies, environment_yaml.dependencies);
+        for record in records.iter().take(5) {
+            assert!(round_trip
+                .match_specs()
+                .any(|spec| crate::Matches::matches(spec, record)));
+        }
+    }
+
+    #[test]
+    fn test_from_prefix() {
+        let prefix = get_test_data_dir();
+        let environment_yaml = EnvironmentYaml::from_prefix(&prefix).unwrap();
+
+        let records = PrefixRecord::collect_from_prefix(&prefix).unwrap();
+        assert_eq!(environment_yaml.match_specs().count(), records.len());
+        for record in &records {
+            assert!(environment_yaml
+                .match_specs()
+                .any(|spec| crate::Matches::matches(spec, &record.repodata_record)));
+        }
+    }
 }
@@ -9,7 +9,7 @@
 //!
 //! To create an explicit environment file, you can use the `conda env export` command.
 
-use crate::{ParsePlatformError, Platform};
+use crate::{PackageRecord, ParsePlatformError, Platform, PrefixRecord, RepoDataRecord};
 use serde::{Deserialize, Serialize};
 use std::{fs, fs::File, io::Read, path::Path, str::FromStr};
 use url::Url;
@@ -106,6 +106,26 @@ impl ExplicitEnvironmentEntry {
     }
 }
 
+impl ExplicitEnvironmentEntry {
+    /// Constructs an entry from the url of a [`RepoDataRecord`]. The hash of
+    /// the package archive is added as a fragment to the url. The md5 hash is
+    /// preferred because it is understood by all versions of conda, the
+    /// sha256 hash is used if no md5 hash is available.
+    pub fn from_repodata_record(record: &RepoDataRecord) -> Self {
+        let mut url = record.url.clone();
+        let fragment = match (
+            record.package_record.md5.as_ref(),
+            record.package_record.sha256.as_ref(),
+        ) {
+            (Some(md5), _) => Some(format!("{md5:x}")),
+            (None, Some(sha256)) => Some(format!("sha256:{sha256:x}")),
+            (None, None) => None,
+        };
+        url.set_fragment(fragment.as_deref());
+        ExplicitEnvironmentEntry { url }
+    }
+}
+
 impl From<Url> for ExplicitEnvironmentEntry {
     fn from(url: Url) -> Self {
         ExplicitEnvironmentEntry { url }
@@ -151,6 +171,52 @@ impl ExplicitEnvironmentSpec {
         Self::from_reader(File::open(path)?)
     }
 
+    /// Constructs an explicit environment from a set of records, for instance
+    /// the records of a lock file environment or the
+    /// [`crate::PrefixRecord::repodata_record`]s of an installed prefix.
+    ///
+    /// The packages are ordered topologically so dependencies are listed
+    /// before the packages that depend on them. If multiple records share a
+    /// package name, only one of them is part of the ordering and the others
+    /// follow at the end. Every url includes the hash
+    /// of the package archive, see
+    /// [`ExplicitEnvironmentEntry::from_repodata_record`].
+    pub fn from_repodata_records<'a>(
+        records: impl IntoIterator<Item = &'a RepoDataRecord>,
+        platform: Option<Platform>,
+    ) -> Self {
+        let records: Vec<&RepoDataRecord> = records.into_iter().collect();
+        let mut sorted = PackageRecord::sort_topologically(records.clone());
+
+        // Sorting only keeps one record per package name, the remaining
+        // records are listed after the sorted ones.
+        if sorted.len() < records.len() {
+            let remaining = records
+                .into_iter()
+                .filter(|record| !sorted.iter().any(|sorted| std::ptr::eq(*sorted, *record)))
+                .collect::<Vec<_>>();
+            sorted.extend(remaining);
+        }
+
+        Self {
+            platform,
+            packages: sorted
+                .into_iter()
+                .map(ExplicitEnvironmentEntry::from_repodata_record)
+                .collect(),
+        }
+    }
+
+    /// Constructs an explicit environment from the packages installed in a
+    /// prefix, as read by [`PrefixRecord::collect_from_prefix`].
+    pub fn from_prefix(prefix: &Path, platform: Option<Platform>) -> Result<Self, std::io::Error> {
+        let records = PrefixRecord::collect_from_prefix(prefix)?;
+        Ok(Self::from_repodata_records(
+            records.iter().map(|record| &record.repodata_record),
+            platform,
+        ))
+    }
+
     /// Converts an [`ExplicitEnvironmentSpec`] to a string representing a valid explicit
     /// environment file
     pub fn to_spec_string(&self) -> String {
@@ -223,7 +289,8 @@ mod test {
     use super::{ExplicitEnvironmentSpec, ParseExplicitEnvironmentSpecError};
     use crate::{
         explicit_environment_spec::{PackageArchiveHash, ParsePackageArchiveHashError},
-        get_test_data_dir, ExplicitEnvironmentEntry,
+        get_test_data_dir, ExplicitEnvironmentEntry, PackageName, PackageRecord, Platform,
+        RepoDataRecord, Version,
     };
     use assert_matches::assert_matches;
     use hex_literal::hex;
@@ -376,4 +443,76 @@ mod test {
             Err(ParsePackageArchiveHashError::InvalidMd5Hash(_))
         );
     }
+
+    fn repodata_record(name: &str, depends: &[&str]) -> RepoDataRecord {
+        let mut package_record = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            Version::from_str("1.0").unwrap(),
+            String::from("h123_0"),
+        );
+        package_record.depends = depends.iter().map(ToString::to_string).collect();
+        RepoDataRecord {
+            package_record,
+            file_name: format!("{name}-1.0-h123_0.conda"),
+            url: Url::parse(&format!(
+                "https://conda.anaconda.org/conda-forge/linux-64/{name}-1.0-h123_0.conda"
+            ))
+            .unwrap(),
+            channel: String::from("https://conda.anaconda.org/conda-forge/"),
+        }
+    }
+
+    #[test]
+    fn test_from_repodata_records() {
+        let mut foo = repodata_record("foo", &["bar"]);
+        foo.package_record.md5 = Some(hex!("a98ea1e3abfdbbd201d60ff6b43ea7e4").into());
+        foo.package_record.sha256 =
+            Some(hex!("315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3").into());
+        let mut bar = repodata_record("bar", &[]);
+        bar.package_record.sha256 =
+            Some(hex!("315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3").into());
+        let baz = repodata_record("baz", &[]);
+
+        let env = ExplicitEnvironmentSpec::from_repodata_records(
+            [&foo, &bar, &baz],
+            Some(Platform::Linux64),
+        );
+        insta::assert_snapshot!(env.to_spec_string());
+
+        // The hashes should survive a round trip
+        let round_trip = ExplicitEnvironmentSpec::from_str(&env.to_spec_string()).unwrap();
+        let hashes = round_trip
+            .packages
+            .iter()
+            .map(|entry| entry.package_archive_hash().unwrap())
+            .collect::<Vec<_>>();
+        assert_matches!(
+            hashes.as_slice(),
+            [
+                Some(PackageArchiveHash::Sha256(_)),
+                Some(PackageArchiveHash::Md5(_)),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_prefix() {
+        let prefix = get_test_data_dir();
+        let env = ExplicitEnvironmentSpec::from_prefix(&prefix, Some(Platform::OsxArm64)).unwrap();
+
+        let records = crate::PrefixRecord::collect_from_prefix(&prefix).unwrap();
+        assert_eq!(env.packages.len(), records.len());
+        assert_eq!(env.platform, Some(Platform::OsxArm64));
+
+        // Dependencies are listed before the packages that depend on them.
+        let position = |name: &str| {
+            env.packages
+                .iter()
+                .position(|entry| entry.url.path().contains(&format!("/{name}-")))
+                .unwrap()
+        };
+        assert!(position("libblas") < position("libcblas"));
+        assert!(position("libcxx") < position("libclang13"));
+    }
 }
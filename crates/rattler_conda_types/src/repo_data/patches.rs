@@ -163,11 +163,18 @@ pub fn apply_patches_impl(
         }
 
         // also apply the patch to the conda packages
-        if let Some((pkg_name, archive_type)) = ArchiveType::split_str(pkg) {
-            assert!(archive_type == ArchiveType::TarBz2);
-            if let Some(record) = conda_packages.get_mut(&format!("{pkg_name}.conda")) {
-                record.apply_patch(patch);
+        match ArchiveType::split_str(pkg) {
+            Some((pkg_name, ArchiveType::TarBz2)) => {
+                if let Some(record) = conda_packages.get_mut(&format!("{pkg_name}.conda")) {
+                    record.apply_patch(patch);
+                }
+            }
+            Some((_, ArchiveType::Conda)) => {
+                tracing::warn!(
+                    "ignoring patch for {pkg}: `.conda` packages must be patched through `packages.conda`"
+                );
             }
+            None => {}
         }
     }
 
@@ -297,6 +304,20 @@ mod test {
         insta::assert_yaml_snapshot!(repodata);
     }
 
+    #[test]
+    fn test_conda_package_in_packages_is_ignored() {
+        let mut repodata = load_test_repodata();
+        let original = repodata.clone();
+        let patch_instructions: PatchInstructions = serde_json::from_str(
+            r#"{"patch_instructions_version": 1, "packages": {"foo-1.0-0.conda": {"depends": []}}}"#,
+        )
+        .unwrap();
+
+        repodata.apply_patches(&patch_instructions);
+
+        assert_eq!(repodata, original);
+    }
+
     #[test]
     fn test_patch_purl() {
         // test data
@@ -10,7 +10,7 @@ use std::{
     iter,
 };
 
-use itertools::{Either, EitherOrBoth, Itertools};
+use itertools::{Either, EitherOrBoth, Itertools, Position};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::SmallVec;
@@ -316,8 +316,8 @@ impl Version {
     /// Check if this version version and local strings start with the same as other.
     pub fn starts_with(&self, other: &Self) -> bool {
         self.epoch() == other.epoch()
-            && segments_starts_with(self.segments(), other.segments())
-            && segments_starts_with(self.local_segments(), other.local_segments())
+            && segments_starts_with(self.segments(), other.segments(), !other.has_local())
+            && segments_starts_with(self.local_segments(), other.local_segments(), true)
     }
 
     /// Returns true if this version is compatible with the given `other`.
@@ -325,9 +325,13 @@ impl Version {
         self.ge(other)
             && self.epoch() == other.epoch()
             // Remove the last segment from the limit.
-            && segments_starts_with(self.segments(), other.segments().rev().skip(1).rev())
+            && segments_starts_with(
+                self.segments(),
+                other.segments().rev().skip(1).rev(),
+                !other.has_local(),
+            )
             // Local version comparison remains the same
-            && segments_starts_with(self.local_segments(), other.local_segments())
+            && segments_starts_with(self.local_segments(), other.local_segments(), true)
     }
 
     /// Returns a new version with only the given segments.
@@ -511,6 +515,10 @@ impl Version {
 }
 
 /// Returns true if the specified segments are considered to start with the other segments.
+///
+/// If `prefix_last` is true the last component of the other segments only has to be a prefix of
+/// the corresponding component if both are identifiers, e.g. `2013ab` starts with `2013a`. This
+/// matches the behavior of Conda.
 fn segments_starts_with<
     'a,
     'b,
@@ -519,12 +527,13 @@ fn segments_starts_with<
 >(
     a: A,
     b: B,
+    prefix_last: bool,
 ) -> bool {
-    for ranges in a.zip_longest(b) {
-        let (left, right) = match ranges {
+    for ranges in a.zip_longest(b.with_position()) {
+        let (left, (position, right)) = match ranges {
             EitherOrBoth::Both(left, right) => (left, right),
             EitherOrBoth::Left(_) => return true,
-            EitherOrBoth::Right(segment) => {
+            EitherOrBoth::Right((_, segment)) => {
                 // If the segment is zero we can skip it. As long as there are
                 // only zeros, the version is still considered to start with
                 // the other version.
@@ -534,9 +543,17 @@ fn segments_starts_with<
                 return false;
             }
         };
-        for values in left.components().zip_longest(right.components()) {
+        let is_last_segment = matches!(position, Position::Last | Position::Only);
+        for values in left
+            .components()
+            .zip_longest(right.components().with_position())
+        {
             if !match values {
-                EitherOrBoth::Both(a, b) => a == b,
+                EitherOrBoth::Both(a, (position, b)) => {
+                    let is_last =
+                        is_last_segment && matches!(position, Position::Last | Position::Only);
+                    a == b || (prefix_last && is_last && is_identifier_prefix(a, b))
+                }
                 EitherOrBoth::Left(_) => return true,
                 EitherOrBoth::Right(_) => return false,
             } {
@@ -547,6 +564,14 @@ fn segments_starts_with<
     true
 }
 
+/// Returns true if both components are identifiers and `prefix` is a prefix of `component`.
+fn is_identifier_prefix(component: &Component, prefix: &Component) -> bool {
+    match (component, prefix) {
+        (Component::Iden(component), Component::Iden(prefix)) => component.starts_with(&**prefix),
+        _ => false,
+    }
+}
+
 impl PartialEq<Self> for Version {
     fn eq(&self, other: &Self) -> bool {
         fn segments_equal<'i, I: Iterator<Item = SegmentIter<'i>>>(a: I, b: I) -> bool {
@@ -1247,6 +1272,17 @@ mod test {
         assert!(Version::from_str("1.2.3")
             .unwrap()
             .starts_with(&Version::from_str("1.2").unwrap()));
+
+        // The last identifier only has to be a prefix
+        assert!(Version::from_str("2013ab")
+            .unwrap()
+            .starts_with(&Version::from_str("2013a").unwrap()));
+        assert!(!Version::from_str("2013ab.1")
+            .unwrap()
+            .starts_with(&Version::from_str("2013a.1").unwrap()));
+        assert!(!Version::from_str("2013a")
+            .unwrap()
+            .starts_with(&Version::from_str("2013ab").unwrap()));
     }
 
     fn get_hash(spec: &impl Hash) -> u64 {
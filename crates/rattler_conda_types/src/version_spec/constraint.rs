@@ -230,6 +230,18 @@ mod test {
         );
     }
 
+    #[rstest]
+    fn test_glob_compatible(#[values(Lenient, Strict)] strictness: ParseStrictness) {
+        assert_matches!(
+            Constraint::from_str("~=1.2.*", strictness),
+            Err(ParseConstraintError::GlobVersionIncompatibleWithOperator(_))
+        );
+        assert_matches!(
+            Constraint::from_str("*.7.*", strictness),
+            Err(ParseConstraintError::RegexConstraintsNotSupported)
+        );
+    }
+
     #[test]
     fn test_glob_op_strict() {
         assert_matches!(
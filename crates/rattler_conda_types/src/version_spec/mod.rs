@@ -3,6 +3,8 @@
 
 mod constraint;
 pub(crate) mod parse;
+pub mod range;
+mod version_range;
 pub(crate) mod version_tree;
 
 use std::{
@@ -16,6 +18,7 @@ use constraint::Constraint;
 use parse::ParseConstraintError;
 use serde::{Deserialize, Serialize, Serializer};
 use thiserror::Error;
+pub use version_range::{VersionPoint, VersionRange};
 use version_tree::VersionTree;
 
 use crate::{
@@ -342,7 +345,7 @@ impl VersionSpec {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{path::Path, str::FromStr};
 
     use assert_matches::assert_matches;
     use rstest::rstest;
@@ -350,7 +353,7 @@ mod tests {
     use crate::{
         version_spec::{
             parse::ParseConstraintError, EqualityOperator, LogicalOperator, ParseVersionSpecError,
-            RangeOperator,
+            RangeOperator, StrictRangeOperator, VersionOperators,
         },
         ParseStrictness, Version, VersionSpec,
     };
@@ -589,4 +592,59 @@ mod tests {
             )
         );
     }
+
+    /// Matches the specs of Conda's own test suite against the versions and
+    /// verifies that the results are the same as in Conda.
+    #[rstest]
+    fn test_conda_corpus(
+        #[values(ParseStrictness::Lenient, ParseStrictness::Strict)] strictness: ParseStrictness,
+    ) {
+        let corpus = std::fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/conda_version_specs.txt"),
+        )
+        .unwrap();
+
+        let mut mismatches = Vec::new();
+        for line in corpus.lines() {
+            // Skip comments and empty lines
+            if line.trim_start().starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            let (expected, rest) = line.split_once(' ').unwrap();
+            if expected == "invalid" {
+                if let Ok(spec) = VersionSpec::from_str(rest, strictness) {
+                    mismatches.push(format!("{rest}: expected an error, got {spec}"));
+                }
+                continue;
+            }
+
+            let (version, spec_str) = rest.split_once(' ').unwrap();
+            let version = Version::from_str(version).unwrap();
+            match VersionSpec::from_str(spec_str, strictness) {
+                Ok(spec) => {
+                    if spec.matches(&version) != (expected == "match") {
+                        mismatches.push(format!(
+                            "{spec_str} ({spec}) on {version}: expected {expected}"
+                        ));
+                    }
+                }
+                // Regex constraints are deliberately not supported.
+                Err(ParseVersionSpecError::InvalidConstraint(
+                    ParseConstraintError::RegexConstraintsNotSupported,
+                )) => {}
+                // Strict parsing rejects globs that are superfluous for the operator
+                // (e.g. `=1.2.*` or `>1.2*`), Conda ignores them.
+                Err(ParseVersionSpecError::InvalidConstraint(
+                    ParseConstraintError::GlobVersionIncompatibleWithOperator(
+                        VersionOperators::Range(_)
+                        | VersionOperators::StrictRange(StrictRangeOperator::StartsWith),
+                    ),
+                )) if strictness == ParseStrictness::Strict => {}
+                Err(e) => mismatches.push(format!("{spec_str}: {e}")),
+            }
+        }
+
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
 }
@@ -117,6 +117,13 @@ fn any_constraint_parser(
     move |input: &str| {
         let (remaining, (_, trailing)) = tuple((tag("*"), opt(tag(".*"))))(input)?;
 
+        // A glob followed by more version components (e.g. `*.7.*`) is a regex
+        if remaining.starts_with('.') {
+            return Err(nom::Err::Failure(
+                ParseConstraintError::RegexConstraintsNotSupported,
+            ));
+        }
+
         // `*.*` is not allowed in strict mode
         if trailing.is_some() && strictness == ParseStrictness::Strict {
             return Err(nom::Err::Failure(ParseConstraintError::InvalidGlob));
@@ -227,15 +234,21 @@ fn logical_constraint_parser(
                 // even in strict mode we allow this.
                 VersionOperators::StrictRange(StrictRangeOperator::NotStartsWith)
             }
-            ("*" | ".*", Some(op), Lenient) => {
-                // In lenient mode we simply ignore the glob.
-                op
-            }
-            ("*" | ".*", Some(op), Strict) => {
+            // `~=1.2.*` is ambiguous so, like Conda, we reject it even in lenient mode.
+            (
+                "*" | ".*",
+                Some(op @ VersionOperators::StrictRange(StrictRangeOperator::Compatible)),
+                Lenient | Strict,
+            )
+            | ("*" | ".*", Some(op), Strict) => {
                 return Err(nom::Err::Failure(
                     ParseConstraintError::GlobVersionIncompatibleWithOperator(op),
                 ));
             }
+            ("*" | ".*", Some(op), Lenient) => {
+                // In lenient mode we simply ignore the glob.
+                op
+            }
             ("*" | ".*", None, _) => VersionOperators::StrictRange(StrictRangeOperator::StartsWith),
 
             // Support for edge case version spec that looks like `2023.*.*`.
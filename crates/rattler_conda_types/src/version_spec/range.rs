@@ -4,14 +4,14 @@
 //! representable as the concatenation, union, and complement
 //! of the ranges building blocks.
 
-use pubgrub::version_set::VersionSet;
 use smallvec::{smallvec, SmallVec};
 use std::cmp::Ordering;
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Display, Formatter};
 use std::ops::Bound::{self, Excluded, Included, Unbounded};
 
 type Interval<V> = (Bound<V>, Bound<V>);
 
+/// A set of versions represented as a sorted list of disjoint intervals.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Range<V> {
     segments: SmallVec<[Interval<V>; 2]>,
@@ -102,16 +102,15 @@ impl<V: Clone> Range<V> {
             Some((Unbounded, Excluded(v))) => {
                 Self::negate_segments(Included(v.clone()), &self.segments[1..])
             }
-            Some((Included(_), Included(_)))
-            | Some((Included(_), Excluded(_)))
-            | Some((Excluded(_), Included(_)))
-            | Some((Excluded(_), Excluded(_))) => Self::negate_segments(Unbounded, &self.segments),
+            Some((Included(_) | Excluded(_), Included(_) | Excluded(_))) => {
+                Self::negate_segments(Unbounded, &self.segments)
+            }
         }
     }
 
     /// Helper function performing the negation of intervals in segments.
     fn negate_segments(start: Bound<V>, segments: &[Interval<V>]) -> Self {
-        let mut complement_segments: SmallVec<[Interval<V>; 2]> = Default::default();
+        let mut complement_segments: SmallVec<[Interval<V>; 2]> = SmallVec::default();
         let mut start = start;
         for (v1, v2) in segments {
             complement_segments.push((
@@ -138,6 +137,18 @@ impl<V: Clone> Range<V> {
     }
 }
 
+impl<V> Range<V> {
+    /// Returns true if this Range does not contain any value.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the sorted, disjoint intervals that make up this Range.
+    pub fn segments(&self) -> &[(Bound<V>, Bound<V>)] {
+        &self.segments
+    }
+}
+
 impl<V: Ord> Range<V> {
     /// Returns true if the this Range contains the specified value.
     pub fn contains(&self, v: &V) -> bool {
@@ -166,141 +177,141 @@ impl<V: Ord + Clone> Range<V> {
         self.negate().intersection(&other.negate()).negate()
     }
 
+    /// Returns true if every value in this Range is also contained in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.intersection(&other.negate()).is_empty()
+    }
+
+    /// Returns true if this Range and `other` have no value in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).is_empty()
+    }
+
     /// Computes the intersection of two sets of versions.
     pub fn intersection(&self, other: &Self) -> Self {
-        let mut segments: SmallVec<[Interval<V>; 2]> = Default::default();
+        let mut segments: SmallVec<[Interval<V>; 2]> = SmallVec::default();
         let mut left_iter = self.segments.iter();
         let mut right_iter = other.segments.iter();
         let mut left = left_iter.next();
         let mut right = right_iter.next();
-        loop {
-            match (left, right) {
-                (Some((left_lower, left_upper)), Some((right_lower, right_upper))) => {
-                    // Check if the left range completely smaller than the right range.
-                    if let (
-                        Included(left_upper_version) | Excluded(left_upper_version),
-                        Included(right_lower_version) | Excluded(right_lower_version),
-                    ) = (left_upper, right_lower)
-                    {
-                        match left_upper_version.cmp(right_lower_version) {
-                            Ordering::Less => {
-                                // Left range is disjoint from the right range.
-                                left = left_iter.next();
-                                continue;
-                            }
-                            Ordering::Equal => {
-                                if !matches!((left_upper, right_lower), (Included(_), Included(_)))
-                                {
-                                    // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
-                                    left = left_iter.next();
-                                    continue;
-                                }
-                            }
-                            Ordering::Greater => {
-                                // Left upper bound is greater than right lower bound, so the lower bound is the right lower bound
-                            }
+        while let (Some((left_lower, left_upper)), Some((right_lower, right_upper))) = (left, right)
+        {
+            // Check if the left range completely smaller than the right range.
+            if let (
+                Included(left_upper_version) | Excluded(left_upper_version),
+                Included(right_lower_version) | Excluded(right_lower_version),
+            ) = (left_upper, right_lower)
+            {
+                match left_upper_version.cmp(right_lower_version) {
+                    Ordering::Less => {
+                        // Left range is disjoint from the right range.
+                        left = left_iter.next();
+                        continue;
+                    }
+                    Ordering::Equal => {
+                        if !matches!((left_upper, right_lower), (Included(_), Included(_))) {
+                            // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
+                            left = left_iter.next();
+                            continue;
                         }
                     }
-                    // Check if the right range completely smaller than the left range.
-                    if let (
-                        Included(left_lower_version) | Excluded(left_lower_version),
-                        Included(right_upper_version) | Excluded(right_upper_version),
-                    ) = (left_lower, right_upper)
-                    {
-                        match right_upper_version.cmp(left_lower_version) {
-                            Ordering::Less => {
-                                // Right range is disjoint from the left range.
-                                right = right_iter.next();
-                                continue;
-                            }
-                            Ordering::Equal => {
-                                if !matches!((right_upper, left_lower), (Included(_), Included(_)))
-                                {
-                                    // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
-                                    right = right_iter.next();
-                                    continue;
-                                }
-                            }
-                            Ordering::Greater => {
-                                // Right upper bound is greater than left lower bound, so the lower bound is the left lower bound
-                            }
+                    Ordering::Greater => {
+                        // Left upper bound is greater than right lower bound, so the lower bound is the right lower bound
+                    }
+                }
+            }
+            // Check if the right range completely smaller than the left range.
+            if let (
+                Included(left_lower_version) | Excluded(left_lower_version),
+                Included(right_upper_version) | Excluded(right_upper_version),
+            ) = (left_lower, right_upper)
+            {
+                match right_upper_version.cmp(left_lower_version) {
+                    Ordering::Less => {
+                        // Right range is disjoint from the left range.
+                        right = right_iter.next();
+                        continue;
+                    }
+                    Ordering::Equal => {
+                        if !matches!((right_upper, left_lower), (Included(_), Included(_))) {
+                            // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
+                            right = right_iter.next();
+                            continue;
                         }
                     }
+                    Ordering::Greater => {
+                        // Right upper bound is greater than left lower bound, so the lower bound is the left lower bound
+                    }
+                }
+            }
 
-                    // At this point we know there is an overlap between the versions, find the lowest bound
-                    let lower = match (left_lower, right_lower) {
-                        (Unbounded, Included(_) | Excluded(_)) => right_lower.clone(),
-                        (Included(_) | Excluded(_), Unbounded) => left_lower.clone(),
-                        (Unbounded, Unbounded) => Unbounded,
-                        (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
-                            Ordering::Less => right_lower.clone(),
-                            Ordering::Equal => match (left_lower, right_lower) {
-                                (Included(_), Excluded(v)) => Excluded(v.clone()),
-                                (Excluded(_), Excluded(v)) => Excluded(v.clone()),
-                                (Excluded(v), Included(_)) => Excluded(v.clone()),
-                                (Included(_), Included(v)) => Included(v.clone()),
-                                _ => unreachable!(),
-                            },
-                            Ordering::Greater => left_lower.clone(),
-                        },
-                    };
+            // At this point we know there is an overlap between the versions, find the lowest bound
+            let lower = match (left_lower, right_lower) {
+                (Unbounded, Included(_) | Excluded(_)) => right_lower.clone(),
+                (Included(_) | Excluded(_), Unbounded) => left_lower.clone(),
+                (Unbounded, Unbounded) => Unbounded,
+                (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
+                    Ordering::Less => right_lower.clone(),
+                    Ordering::Equal => match (left_lower, right_lower) {
+                        (Included(_) | Excluded(_), Excluded(v)) | (Excluded(v), Included(_)) => {
+                            Excluded(v.clone())
+                        }
+                        (Included(_), Included(v)) => Included(v.clone()),
+                        _ => unreachable!(),
+                    },
+                    Ordering::Greater => left_lower.clone(),
+                },
+            };
 
-                    // At this point we know there is an overlap between the versions, find the lowest bound
-                    let upper = match (left_upper, right_upper) {
-                        (Unbounded, Included(_) | Excluded(_)) => {
+            // At this point we know there is an overlap between the versions, find the lowest bound
+            let upper = match (left_upper, right_upper) {
+                (Unbounded, Included(_) | Excluded(_)) => {
+                    right = right_iter.next();
+                    right_upper.clone()
+                }
+                (Included(_) | Excluded(_), Unbounded) => {
+                    left = left_iter.next();
+                    left_upper.clone()
+                }
+                (Unbounded, Unbounded) => {
+                    left = left_iter.next();
+                    right = right_iter.next();
+                    Unbounded
+                }
+                (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
+                    Ordering::Less => {
+                        left = left_iter.next();
+                        left_upper.clone()
+                    }
+                    Ordering::Equal => match (left_upper, right_upper) {
+                        (Included(_), Excluded(v)) => {
                             right = right_iter.next();
-                            right_upper.clone()
+                            Excluded(v.clone())
                         }
-                        (Included(_) | Excluded(_), Unbounded) => {
+                        (Excluded(_), Excluded(v)) => {
                             left = left_iter.next();
-                            left_upper.clone()
+                            right = right_iter.next();
+                            Excluded(v.clone())
+                        }
+                        (Excluded(v), Included(_)) => {
+                            left = left_iter.next();
+                            Excluded(v.clone())
                         }
-                        (Unbounded, Unbounded) => {
+                        (Included(_), Included(v)) => {
                             left = left_iter.next();
                             right = right_iter.next();
-                            Unbounded
+                            Included(v.clone())
                         }
-                        (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
-                            Ordering::Less => {
-                                left = left_iter.next();
-                                left_upper.clone()
-                            }
-                            Ordering::Equal => match (left_upper, right_upper) {
-                                (Included(_), Excluded(v)) => {
-                                    right = right_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Excluded(_), Excluded(v)) => {
-                                    left = left_iter.next();
-                                    right = right_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Excluded(v), Included(_)) => {
-                                    left = left_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Included(_), Included(v)) => {
-                                    left = left_iter.next();
-                                    right = right_iter.next();
-                                    Included(v.clone())
-                                }
-                                _ => unreachable!(),
-                            },
-                            Ordering::Greater => {
-                                right = right_iter.next();
-                                right_upper.clone()
-                            }
-                        },
-                    };
-
-                    segments.push((lower, upper));
-                }
+                        _ => unreachable!(),
+                    },
+                    Ordering::Greater => {
+                        right = right_iter.next();
+                        right_upper.clone()
+                    }
+                },
+            };
 
-                // Left or right has ended
-                (None, _) | (_, None) => {
-                    break;
-                }
-            }
+            segments.push((lower, upper));
         }
 
         Self { segments }
@@ -323,9 +334,9 @@ impl<V: Display + Eq> Display for Range<V> {
                     (Included(v), Unbounded) => write!(f, ">={v}")?,
                     (Included(v), Included(b)) => {
                         if v == b {
-                            write!(f, "{v}")?
+                            write!(f, "{v}")?;
                         } else {
-                            write!(f, ">={v},<={b}")?
+                            write!(f, ">={v},<={b}")?;
                         }
                     }
                     (Included(v), Excluded(b)) => write!(f, ">={v}, <{b}")?,
@@ -339,38 +350,6 @@ impl<V: Display + Eq> Display for Range<V> {
     }
 }
 
-impl<T: Debug + Display + Clone + Eq + Ord> VersionSet for Range<T> {
-    type V = T;
-
-    fn empty() -> Self {
-        Range::none()
-    }
-
-    fn singleton(v: Self::V) -> Self {
-        Range::equal(v)
-    }
-
-    fn complement(&self) -> Self {
-        Range::negate(self)
-    }
-
-    fn intersection(&self, other: &Self) -> Self {
-        Range::intersection(self, other)
-    }
-
-    fn contains(&self, v: &Self::V) -> bool {
-        Range::contains(self, v)
-    }
-
-    fn full() -> Self {
-        Range::any()
-    }
-
-    fn union(&self, other: &Self) -> Self {
-        Range::union(self, other)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::Range as R;
@@ -408,12 +387,10 @@ mod tests {
 
                     let start = if rng.gen_bool(0.3) {
                         Unbounded
+                    } else if rng.gen_bool(0.5) {
+                        Included(first)
                     } else {
-                        if rng.gen_bool(0.5) {
-                            Included(first)
-                        } else {
-                            Excluded(first)
-                        }
+                        Excluded(first)
                     };
 
                     let end = next_bound(&mut iter, &mut rng);
@@ -425,7 +402,7 @@ mod tests {
                         segments.push((start, end));
                     }
                 }
-                return Range { segments };
+                Range { segments }
             })
     }
 
@@ -458,7 +435,7 @@ mod tests {
         assert_eq!(
             R::less(2).union(&R::greater_equal(3)),
             R::between(2, 3).negate()
-        )
+        );
     }
 
     #[test]
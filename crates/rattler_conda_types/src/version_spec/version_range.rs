@@ -0,0 +1,502 @@
+//! Conversion of a [`VersionSpec`] into a [`Range`] of versions which allows
+//! reasoning about the sets of versions that specs match, e.g. whether one
+//! spec is a subset of another or whether two specs conflict.
+
+use std::{
+    cmp::Ordering,
+    ops::Bound::{self, Excluded, Included, Unbounded},
+};
+
+use super::{
+    range::Range, EqualityOperator, LogicalOperator, RangeOperator, StrictRangeOperator,
+    VersionSpec,
+};
+use crate::{version::StrictVersion, Version};
+
+/// A point on the ordered line of versions that is used as the bound of a
+/// [`VersionRange`].
+///
+/// Besides actual versions the line also contains the points directly before
+/// and directly after all versions that start with a certain prefix. These are
+/// required to represent specs like `1.2.*` as an interval because there is no
+/// version that is the first or last version starting with `1.2`.
+#[derive(Debug, Clone)]
+pub enum VersionPoint {
+    /// A specific version.
+    Version(Version),
+
+    /// The point directly before all versions that start with the version.
+    StartOf(Version),
+
+    /// The point directly after all versions that start with the version.
+    EndOf(Version),
+}
+
+/// A set of versions represented as intervals of [`VersionPoint`]s.
+pub type VersionRange = Range<VersionPoint>;
+
+impl VersionPoint {
+    /// Returns true if this point lies within the set of versions that start
+    /// with `prefix`.
+    fn is_within(&self, prefix: &Version) -> bool {
+        match self {
+            VersionPoint::Version(version)
+            | VersionPoint::StartOf(version)
+            | VersionPoint::EndOf(version) => version.starts_with(prefix),
+        }
+    }
+}
+
+impl Ord for VersionPoint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (VersionPoint::Version(a), VersionPoint::Version(b)) => a.cmp(b),
+            (VersionPoint::Version(a), VersionPoint::StartOf(prefix)) => {
+                if a.starts_with(prefix) {
+                    Ordering::Greater
+                } else {
+                    a.cmp(prefix)
+                }
+            }
+            (VersionPoint::Version(a), VersionPoint::EndOf(prefix)) => {
+                if a.starts_with(prefix) {
+                    Ordering::Less
+                } else {
+                    a.cmp(prefix)
+                }
+            }
+            (VersionPoint::StartOf(a), VersionPoint::StartOf(b)) => {
+                match (a.starts_with(b), b.starts_with(a)) {
+                    (true, true) => Ordering::Equal,
+                    // The versions starting with `a` are a subset of the versions
+                    // starting with `b` so they start later.
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => a.cmp(b),
+                }
+            }
+            (VersionPoint::EndOf(a), VersionPoint::EndOf(b)) => {
+                match (a.starts_with(b), b.starts_with(a)) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                    (false, false) => a.cmp(b),
+                }
+            }
+            (VersionPoint::StartOf(a), VersionPoint::EndOf(b)) => {
+                if a.starts_with(b) || b.starts_with(a) {
+                    Ordering::Less
+                } else {
+                    a.cmp(b)
+                }
+            }
+            (VersionPoint::StartOf(_) | VersionPoint::EndOf(_), _) => other.cmp(self).reverse(),
+        }
+    }
+}
+
+impl PartialOrd for VersionPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for VersionPoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionPoint {}
+
+/// Returns the range of all versions that start with `prefix`.
+fn starts_with(prefix: &Version) -> VersionRange {
+    Range::between(
+        VersionPoint::StartOf(prefix.clone()),
+        VersionPoint::EndOf(prefix.clone()),
+    )
+}
+
+/// Returns the range of all versions that are compatible with `version`.
+fn compatible_with(version: &Version) -> VersionRange {
+    let greater_equal = Range::greater_equal(VersionPoint::Version(version.clone()));
+    match version.with_segments(..version.segment_count() - 1) {
+        Some(prefix) => greater_equal.intersection(&starts_with(&prefix)),
+        None => greater_equal,
+    }
+}
+
+/// Returns a spec that matches all versions starting with `prefix`.
+fn starts_with_spec(prefix: &Version) -> VersionSpec {
+    VersionSpec::StrictRange(
+        StrictRangeOperator::StartsWith,
+        StrictVersion(prefix.clone()),
+    )
+}
+
+/// Returns a spec that matches all versions that lie above `lower`.
+fn lower_bound_spec(
+    lower: &Bound<VersionPoint>,
+    upper: &Bound<VersionPoint>,
+) -> Option<VersionSpec> {
+    Some(match lower {
+        Unbounded => return None,
+        Included(VersionPoint::Version(version)) => {
+            VersionSpec::Range(RangeOperator::GreaterEquals, version.clone())
+        }
+        Excluded(VersionPoint::Version(version)) => {
+            VersionSpec::Range(RangeOperator::Greater, version.clone())
+        }
+        Included(VersionPoint::StartOf(prefix)) | Excluded(VersionPoint::StartOf(prefix)) => {
+            match upper {
+                Included(point) | Excluded(point) if point.is_within(prefix) => {
+                    starts_with_spec(prefix)
+                }
+                _ => VersionSpec::Group(
+                    LogicalOperator::Or,
+                    vec![
+                        starts_with_spec(prefix),
+                        VersionSpec::Range(RangeOperator::Greater, prefix.clone()),
+                    ],
+                ),
+            }
+        }
+        Included(VersionPoint::EndOf(prefix)) | Excluded(VersionPoint::EndOf(prefix)) => {
+            VersionSpec::Group(
+                LogicalOperator::And,
+                vec![
+                    VersionSpec::Range(RangeOperator::Greater, prefix.clone()),
+                    VersionSpec::StrictRange(
+                        StrictRangeOperator::NotStartsWith,
+                        StrictVersion(prefix.clone()),
+                    ),
+                ],
+            )
+        }
+    })
+}
+
+/// Returns a spec that matches all versions that lie below `upper`.
+fn upper_bound_spec(
+    lower: &Bound<VersionPoint>,
+    upper: &Bound<VersionPoint>,
+) -> Option<VersionSpec> {
+    Some(match upper {
+        Unbounded => return None,
+        Included(VersionPoint::Version(version)) => {
+            VersionSpec::Range(RangeOperator::LessEquals, version.clone())
+        }
+        Excluded(VersionPoint::Version(version)) => {
+            VersionSpec::Range(RangeOperator::Less, version.clone())
+        }
+        Included(VersionPoint::EndOf(prefix)) | Excluded(VersionPoint::EndOf(prefix)) => {
+            match lower {
+                Included(point) | Excluded(point) if point.is_within(prefix) => {
+                    starts_with_spec(prefix)
+                }
+                _ => VersionSpec::Group(
+                    LogicalOperator::Or,
+                    vec![
+                        starts_with_spec(prefix),
+                        VersionSpec::Range(RangeOperator::Less, prefix.clone()),
+                    ],
+                ),
+            }
+        }
+        Included(VersionPoint::StartOf(prefix)) | Excluded(VersionPoint::StartOf(prefix)) => {
+            VersionSpec::Group(
+                LogicalOperator::And,
+                vec![
+                    VersionSpec::Range(RangeOperator::Less, prefix.clone()),
+                    VersionSpec::StrictRange(
+                        StrictRangeOperator::NotStartsWith,
+                        StrictVersion(prefix.clone()),
+                    ),
+                ],
+            )
+        }
+    })
+}
+
+/// Converts a single interval of a range back into a spec.
+fn segment_spec(lower: &Bound<VersionPoint>, upper: &Bound<VersionPoint>) -> VersionSpec {
+    match (lower, upper) {
+        (Included(VersionPoint::Version(a)), Included(VersionPoint::Version(b))) if a == b => {
+            return VersionSpec::Exact(EqualityOperator::Equals, a.clone());
+        }
+        (
+            Included(VersionPoint::StartOf(a)) | Excluded(VersionPoint::StartOf(a)),
+            Included(VersionPoint::EndOf(b)) | Excluded(VersionPoint::EndOf(b)),
+        ) if a.starts_with(b) && b.starts_with(a) => return starts_with_spec(a),
+        _ => {}
+    }
+
+    let mut specs = Vec::new();
+    for spec in [
+        lower_bound_spec(lower, upper),
+        upper_bound_spec(lower, upper),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        match spec {
+            VersionSpec::Group(LogicalOperator::And, group) => specs.extend(group),
+            spec => specs.push(spec),
+        }
+    }
+    match specs.len() {
+        0 => VersionSpec::Any,
+        1 => specs.pop().expect("there is exactly one spec"),
+        _ => VersionSpec::Group(LogicalOperator::And, specs),
+    }
+}
+
+impl From<&VersionRange> for VersionSpec {
+    fn from(range: &VersionRange) -> Self {
+        if range.is_empty() {
+            return VersionSpec::None;
+        }
+
+        // Prefer a negated spec if the complement of the range is simpler.
+        if let [(lower, upper)] = range.negate().segments() {
+            match segment_spec(lower, upper) {
+                VersionSpec::Exact(EqualityOperator::Equals, version) => {
+                    return VersionSpec::Exact(EqualityOperator::NotEquals, version);
+                }
+                VersionSpec::StrictRange(StrictRangeOperator::StartsWith, prefix) => {
+                    return VersionSpec::StrictRange(StrictRangeOperator::NotStartsWith, prefix);
+                }
+                _ => {}
+            }
+        }
+
+        let mut specs = range
+            .segments()
+            .iter()
+            .map(|(lower, upper)| segment_spec(lower, upper))
+            .collect::<Vec<_>>();
+        if specs.len() == 1 {
+            specs.pop().expect("there is exactly one spec")
+        } else {
+            VersionSpec::Group(LogicalOperator::Or, specs)
+        }
+    }
+}
+
+impl VersionSpec {
+    /// Returns the set of versions that this spec matches as a range.
+    pub fn to_range(&self) -> VersionRange {
+        match self {
+            VersionSpec::None => Range::none(),
+            VersionSpec::Any => Range::any(),
+            VersionSpec::Exact(EqualityOperator::Equals, version) => {
+                Range::equal(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::Exact(EqualityOperator::NotEquals, version) => {
+                Range::not_equal(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::Range(RangeOperator::Greater, version) => {
+                Range::greater(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::Range(RangeOperator::GreaterEquals, version) => {
+                Range::greater_equal(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::Range(RangeOperator::Less, version) => {
+                Range::less(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::Range(RangeOperator::LessEquals, version) => {
+                Range::less_equal(VersionPoint::Version(version.clone()))
+            }
+            VersionSpec::StrictRange(StrictRangeOperator::StartsWith, prefix) => {
+                starts_with(&prefix.0)
+            }
+            VersionSpec::StrictRange(StrictRangeOperator::NotStartsWith, prefix) => {
+                starts_with(&prefix.0).negate()
+            }
+            VersionSpec::StrictRange(StrictRangeOperator::Compatible, version) => {
+                compatible_with(&version.0)
+            }
+            VersionSpec::StrictRange(StrictRangeOperator::NotCompatible, version) => {
+                compatible_with(&version.0).negate()
+            }
+            VersionSpec::Group(LogicalOperator::And, group) => {
+                group.iter().fold(Range::any(), |range, spec| {
+                    range.intersection(&spec.to_range())
+                })
+            }
+            VersionSpec::Group(LogicalOperator::Or, group) => group
+                .iter()
+                .fold(Range::none(), |range, spec| range.union(&spec.to_range())),
+        }
+    }
+
+    /// Returns true if this spec does not match any version, e.g. `>2,<1`.
+    pub fn is_empty(&self) -> bool {
+        self.to_range().is_empty()
+    }
+
+    /// Returns true if every version matched by this spec is also matched by
+    /// `other`.
+    pub fn is_subset(&self, other: &VersionSpec) -> bool {
+        self.to_range().is_subset(&other.to_range())
+    }
+
+    /// Returns true if there is no version that matches both this spec and
+    /// `other`, i.e. the two specs conflict.
+    pub fn is_disjoint(&self, other: &VersionSpec) -> bool {
+        self.to_range().is_disjoint(&other.to_range())
+    }
+
+    /// Returns a spec that matches the versions matched by both this spec and
+    /// `other`.
+    pub fn intersection(&self, other: &VersionSpec) -> VersionSpec {
+        VersionSpec::from(&self.to_range().intersection(&other.to_range()))
+    }
+
+    /// Returns a spec that matches the versions matched by either this spec or
+    /// `other`.
+    pub fn union(&self, other: &VersionSpec) -> VersionSpec {
+        VersionSpec::from(&self.to_range().union(&other.to_range()))
+    }
+
+    /// Returns an equivalent spec in which redundant and overlapping
+    /// constraints have been merged, e.g. `>=1.2,>=1.4,<2|<1` becomes
+    /// `<1|>=1.4,<2`.
+    pub fn simplify(&self) -> VersionSpec {
+        VersionSpec::from(&self.to_range())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::{ParseStrictness, Version, VersionSpec};
+
+    fn spec(spec: &str) -> VersionSpec {
+        VersionSpec::from_str(spec, ParseStrictness::Lenient).unwrap()
+    }
+
+    #[rstest]
+    #[case(">=1.2,>=1.4,<2|<1", "<1|>=1.4,<2")]
+    #[case(">=1|<2", "*")]
+    #[case(">2,<1", "!")]
+    #[case("1.2.*,1.2.3", "==1.2.3")]
+    #[case("1.2.*|1.2.3", "1.2.*")]
+    #[case("1.2.*|1.3.*", "1.2.*|1.3.*")]
+    #[case("1.*,1.2.*", "1.2.*")]
+    #[case("<1.2|>1.2", "!=1.2")]
+    #[case("!=1.2.*,!=1.2.3", "!=1.2.*")]
+    #[case("~=1.2.3", ">=1.2.3,1.2.*")]
+    #[case(">=1.2.5,1.2.*|>=1.2.7", ">=1.2.5")]
+    #[case(">=1.2.5,1.2.*|>=1.3", ">=1.2.5,1.2.*|>=1.3")]
+    #[case(">=1.2,!=1.4.*", ">=1.2,<1.4,!=1.4.*|>1.4,!=1.4.*")]
+    #[case(">=1.2,<=1.2", "==1.2")]
+    fn test_simplify(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(spec(input).simplify().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("==1.2.3", "1.2.*", true)]
+    #[case("1.2.*", "1.*", true)]
+    #[case("1.*", "1.2.*", false)]
+    #[case("~=1.2.3", ">=1.2,<1.3", true)]
+    #[case(">=1.2,<1.3", "~=1.2.3", false)]
+    #[case("~=1.2.3", ">=1.2", true)]
+    #[case(">=1.2,<1.3", "1.2.*", false)]
+    #[case(">=1.2.3,<1.2.5", "1.2.*", true)]
+    #[case("1.2.*", "*", true)]
+    #[case(">=3.8", "!=3.7.*", true)]
+    #[case("!=3.7.*", ">=3.8", false)]
+    fn test_is_subset(#[case] a: &str, #[case] b: &str, #[case] expected: bool) {
+        assert_eq!(spec(a).is_subset(&spec(b)), expected);
+    }
+
+    #[rstest]
+    #[case(">=2", "<2", true)]
+    #[case(">=2", "<=2", false)]
+    #[case("1.2.*", "1.3.*", true)]
+    #[case("1.2.*", ">=1.2.5", false)]
+    #[case("1.2.*", ">1.2", false)]
+    #[case("1.2.*", "<1.2", false)]
+    #[case("1.2.*", "<1.1.9", true)]
+    #[case("2013a*", "2013ab", false)]
+    #[case("~=1.2.3", "1.3.*", true)]
+    #[case("!=1.2.*", "1.2.3", true)]
+    fn test_is_disjoint(#[case] a: &str, #[case] b: &str, #[case] expected: bool) {
+        assert_eq!(spec(a).is_disjoint(&spec(b)), expected);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(spec(">2,<1").is_empty());
+        assert!(spec("1.2.*,1.3.*").is_empty());
+        assert!(spec("1.2.*,!=1.2.*").is_empty());
+        assert!(!spec(">=1.2,<1.3").is_empty());
+        assert!(!spec("1.2.*,>=1.2.99").is_empty());
+    }
+
+    #[test]
+    fn test_intersection_union() {
+        assert_eq!(
+            spec(">=1.2").intersection(&spec("<2|>=3")).to_string(),
+            ">=1.2,<2|>=3"
+        );
+        assert_eq!(spec(">=1.2").union(&spec("<2|>=3")).to_string(), "*");
+        assert_eq!(spec("1.2.*").union(&spec("1.3")).to_string(), "1.2.*|==1.3");
+    }
+
+    /// The operations on the ranges must agree with matching the specs against
+    /// versions.
+    #[test]
+    fn test_range_matches() {
+        let specs = [
+            ">=1.2,<2",
+            "1.2.*",
+            "!=1.2.*",
+            "~=1.2.3",
+            "!=1.2.4",
+            "<=1.2|>=1.3.1",
+            "1.2.*,>=1.2.4|2.*",
+            "2013a*",
+            ">1.2.3+4",
+        ];
+        let versions = [
+            "1", "1.1", "1.2", "1.2.0", "1.2.3", "1.2.4", "1.2.9", "1.2a", "1.2dev", "1.3dev",
+            "1.3", "1.3.1", "1.20", "2", "2.1", "2013a", "2013ab", "2013b", "1.2.3+4", "1.2.3+5",
+            "1!1.2",
+        ]
+        .map(|version| Version::from_str(version).unwrap());
+
+        for a in specs.map(spec) {
+            let simplified = a.simplify();
+            for version in &versions {
+                assert_eq!(
+                    a.matches(version),
+                    simplified.matches(version),
+                    "`{a}` simplified to `{simplified}` does not agree on {version}"
+                );
+            }
+            for b in specs.map(spec) {
+                if a.is_subset(&b) {
+                    for version in versions.iter().filter(|version| a.matches(version)) {
+                        assert!(
+                            b.matches(version),
+                            "`{a}` ⊆ `{b}` but {version} is not in `{b}`"
+                        );
+                    }
+                }
+                if a.is_disjoint(&b) {
+                    for version in &versions {
+                        assert!(
+                            !(a.matches(version) && b.matches(version)),
+                            "`{a}` and `{b}` are disjoint but both match {version}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
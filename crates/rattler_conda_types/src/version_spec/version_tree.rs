@@ -138,10 +138,11 @@ pub(crate) fn recognize_constraint<'a, E: ParseError<&'a str> + ContextError<&'a
     input: &'a str,
 ) -> Result<(&'a str, &'a str), nom::Err<E>> {
     alt((
-        // Any (* or *.*)
-        terminated(tag("*"), cut(opt(tag(".*")))),
-        // Regex
-        recognize(delimited(opt(tag("^")), not(tag("$")), tag("$"))),
+        // Any (* or *.*), unless the glob is followed by more version components
+        // (e.g. `*.7.*`).
+        terminated(tag("*"), terminated(opt(tag(".*")), not(tag(".")))),
+        // Regex, everything between a ^ and a $
+        recognize(tuple((tag("^"), take_while(|c: char| c != '$'), tag("$")))),
         // Version with optional operator followed by optional glob.
         recognize(preceded(
             opt(delimited(
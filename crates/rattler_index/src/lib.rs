@@ -1,21 +1,31 @@
 //! Indexing of packages in a output folder to create up to date repodata.json files
 #![deny(missing_docs)]
 
+mod reporter;
+
 use rattler_conda_types::{
-    package::ArchiveType, package::IndexJson, package::PackageFile, ChannelInfo, PackageRecord,
-    Platform, RepoData,
+    package::ArchiveType, package::IndexJson, package::PackageFile, package::RunExportsJson,
+    ChannelInfo, PackageRecord, Platform, RepoData, RepoDataPatch, Shard, ShardedRepodata,
+    ShardedSubdirInfo,
 };
 use rattler_package_streaming::{read, seek};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::OsStr,
     io::{Read, Write},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
 };
 
 use fs_err::File;
+use futures::{StreamExt, TryStreamExt};
+use rattler_digest::HashingReader;
 use walkdir::WalkDir;
 
+pub use reporter::Reporter;
+
 /// Extract the package record from an `index.json` file.
 pub fn package_record_from_index_json<T: Read>(
     file: &Path,
@@ -23,9 +33,14 @@ pub fn package_record_from_index_json<T: Read>(
 ) -> Result<PackageRecord, std::io::Error> {
     let index = IndexJson::from_reader(index_json_reader)?;
 
-    let sha256_result = rattler_digest::compute_file_digest::<rattler_digest::Sha256>(file)?;
-    let md5_result = rattler_digest::compute_file_digest::<rattler_digest::Md5>(file)?;
-    let size = std::fs::metadata(file)?.len();
+    // Compute both hashes in a single pass over the archive.
+    let mut hashing_reader =
+        HashingReader::<_, rattler_digest::Md5>::new(
+            HashingReader::<_, rattler_digest::Sha256>::new(std::fs::File::open(file)?),
+        );
+    let size = std::io::copy(&mut hashing_reader, &mut std::io::sink())?;
+    let (sha256_reader, md5_result) = hashing_reader.finalize();
+    let (_, sha256_result) = sha256_reader.finalize();
 
     let package_record = PackageRecord {
         name: index.name,
@@ -55,52 +70,448 @@ pub fn package_record_from_index_json<T: Read>(
     Ok(package_record)
 }
 
-/// Extract the package record from a `.tar.bz2` package file.
-/// This function will look for the `info/index.json` file in the conda package and extract the
-/// package record from it.
-pub fn package_record_from_tar_bz2(file: &Path) -> Result<PackageRecord, std::io::Error> {
-    let reader = std::fs::File::open(file)?;
-    let mut archive = read::stream_tar_bz2(reader);
+/// Extract the package record from the `info/` section of a package archive. If the archive
+/// contains an `info/run_exports.json` file its contents are stored in
+/// [`PackageRecord::run_exports`].
+fn package_record_from_info_archive(
+    file: &Path,
+    archive: &mut tar::Archive<impl Read>,
+) -> Result<PackageRecord, std::io::Error> {
+    let mut package_record = None;
+    let mut run_exports = None;
     for entry in archive.entries()?.flatten() {
         let mut entry = entry;
-        let path = entry.path()?;
+        let path = entry.path()?.into_owned();
         if path.as_os_str().eq("info/index.json") {
-            return package_record_from_index_json(file, &mut entry);
+            package_record = Some(package_record_from_index_json(file, &mut entry)?);
+        } else if path.as_path() == RunExportsJson::package_path() {
+            run_exports = Some(RunExportsJson::from_reader(&mut entry)?);
+        } else if package_record.is_some() && !path.starts_with("info") {
+            // The `info/` section is stored at the start of the archive, there is no need to read
+            // the rest of it.
+            break;
+        }
+
+        if package_record.is_some() && run_exports.is_some() {
+            break;
         }
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "No index.json found",
-    ))
+
+    let mut package_record = package_record
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No index.json found"))?;
+    package_record.run_exports = run_exports;
+    Ok(package_record)
+}
+
+/// Extract the package record from a `.tar.bz2` package file.
+/// This function will look for the `info/index.json` file in the conda package and extract the
+/// package record from it. The contents of `info/run_exports.json` are stored in
+/// [`PackageRecord::run_exports`] if the package has one.
+pub fn package_record_from_tar_bz2(file: &Path) -> Result<PackageRecord, std::io::Error> {
+    let reader = std::fs::File::open(file)?;
+    let mut archive = read::stream_tar_bz2(reader);
+    package_record_from_info_archive(file, &mut archive)
 }
 
 /// Extract the package record from a `.conda` package file.
 /// This function will look for the `info/index.json` file in the conda package and extract the
-/// package record from it.
+/// package record from it. The contents of `info/run_exports.json` are stored in
+/// [`PackageRecord::run_exports`] if the package has one.
 pub fn package_record_from_conda(file: &Path) -> Result<PackageRecord, std::io::Error> {
     let reader = std::fs::File::open(file)?;
-    let mut archive = seek::stream_conda_info(reader).expect("Could not open conda file");
+    let mut archive = seek::stream_conda_info(reader)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    package_record_from_info_archive(file, &mut archive)
+}
 
-    for entry in archive.entries()?.flatten() {
-        let mut entry = entry;
-        let path = entry.path()?;
-        if path.as_os_str().eq("info/index.json") {
-            return package_record_from_index_json(file, &mut entry);
+/// Options that control how [`index_with_options`] and [`index_async`] index a channel.
+#[derive(Clone, Default)]
+pub struct IndexOptions {
+    /// When `true`, the records of an existing `repodata.json` are reused for
+    /// all archives whose size and modification time did not change since the
+    /// previous run. Only new or modified archives are read and records of
+    /// archives that no longer exist are dropped.
+    pub incremental: bool,
+
+    /// When `true`, a zstd compressed copy of `repodata.json` is written to
+    /// `repodata.json.zst`.
+    pub write_zst: bool,
+
+    /// When `true`, sharded repodata is written. The index of the shards is
+    /// stored in `repodata_shards.msgpack.zst` and every package name gets its
+    /// own content addressed shard in the `shards` directory.
+    pub write_shards: bool,
+
+    /// When `true`, the run exports of all packages are collected in a
+    /// `run_exports.json` file.
+    pub write_run_exports: bool,
+
+    /// Repodata patches that are applied to the records read from the archives.
+    /// When set, the unpatched records are written to
+    /// `repodata_from_packages.json` and `repodata.json` contains the patched
+    /// records. Use [`repodata_patch_from_path`] to read the patches from a
+    /// patch package or directory.
+    pub repodata_patch: Option<RepoDataPatch>,
+
+    /// The maximum number of archives that [`index_async`] reads concurrently.
+    /// Defaults to the number of available CPUs.
+    pub max_parallel: Option<usize>,
+
+    /// A reporter that is notified of the progress of indexing.
+    pub reporter: Option<Arc<dyn Reporter>>,
+}
+
+impl std::fmt::Debug for IndexOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IndexOptions")
+            .field("incremental", &self.incremental)
+            .field("write_zst", &self.write_zst)
+            .field("write_shards", &self.write_shards)
+            .field("write_run_exports", &self.write_run_exports)
+            .field("repodata_patch", &self.repodata_patch)
+            .field("max_parallel", &self.max_parallel)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The outcome of indexing a channel.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct IndexSummary {
+    /// The archives that could not be read together with the error. These
+    /// packages are missing from the written `repodata.json`.
+    pub failed: Vec<(PathBuf, std::io::Error)>,
+}
+
+impl IndexSummary {
+    /// Returns true if all archives were read and are part of the index.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Reads repodata patch instructions from `path`. This is either a repodata patches package
+/// (`.tar.bz2` or `.conda`) or a directory that contains a `<subdir>/patch_instructions.json` file
+/// for every subdir that is patched, like the extracted contents of such a package.
+pub fn repodata_patch_from_path(path: &Path) -> Result<RepoDataPatch, std::io::Error> {
+    if path.is_dir() {
+        return RepoDataPatch::from_package(path);
+    }
+
+    match ArchiveType::try_from(path) {
+        Some(ArchiveType::TarBz2) => {
+            let reader = std::fs::File::open(path)?;
+            repodata_patch_from_archive(&mut read::stream_tar_bz2(reader))
+        }
+        Some(ArchiveType::Conda) => {
+            let reader = std::fs::File::open(path)?;
+            let mut archive = seek::stream_conda_content(reader)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            repodata_patch_from_archive(&mut archive)
         }
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "{} is neither a directory nor a conda package",
+                path.display()
+            ),
+        )),
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "No index.json found",
-    ))
+}
+
+/// Reads all `<subdir>/patch_instructions.json` files from the contents of a repodata patches
+/// package.
+fn repodata_patch_from_archive(
+    archive: &mut tar::Archive<impl Read>,
+) -> Result<RepoDataPatch, std::io::Error> {
+    let mut patch = RepoDataPatch::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut components = path.components();
+        if let (Some(subdir), Some(file_name), None) =
+            (components.next(), components.next(), components.next())
+        {
+            if file_name.as_os_str() == "patch_instructions.json" {
+                let instructions = serde_json::from_reader(&mut entry)?;
+                patch.subdirs.insert(
+                    subdir.as_os_str().to_string_lossy().to_string(),
+                    instructions,
+                );
+            }
+        }
+    }
+    Ok(patch)
+}
+
+/// The path of the file, relative to a subdir, that stores the size and
+/// modification time of every archive that was indexed. This is used to
+/// determine which archives changed between incremental runs.
+const STAT_CACHE_PATH: &str = ".cache/stat.json";
+
+/// The name of the file, relative to a subdir, that stores the unpatched records if repodata
+/// patches are applied.
+const REPODATA_FROM_PACKAGES_FILENAME: &str = "repodata_from_packages.json";
+
+/// The name of the file, relative to a subdir, that stores the index of the sharded repodata.
+const REPODATA_SHARDS_FILENAME: &str = "repodata_shards.msgpack.zst";
+
+/// The directory, relative to a subdir, in which the individual shards are stored.
+const SHARDS_DIR: &str = "shards";
+
+/// The contents of a `run_exports.json` file. This file contains the run exports of all packages
+/// in a subdir so that they can be determined without downloading the packages themselves.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RunExportsRepodata {
+    info: Option<RunExportsInfo>,
+    #[serde(default)]
+    packages: BTreeMap<String, PackageRunExports>,
+    #[serde(default, rename = "packages.conda")]
+    conda_packages: BTreeMap<String, PackageRunExports>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunExportsInfo {
+    subdir: String,
+    version: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PackageRunExports {
+    #[serde(default)]
+    run_exports: RunExportsJson,
+}
+
+/// The size and modification time of an archive when it was last indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStat {
+    size: u64,
+    mtime_ns: Option<u64>,
+}
+
+impl FileStat {
+    fn from_path(path: &Path) -> Result<Self, std::io::Error> {
+        let metadata = fs_err::metadata(path)?;
+        let mtime_ns = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .and_then(|duration| u64::try_from(duration.as_nanos()).ok());
+        Ok(Self {
+            size: metadata.len(),
+            mtime_ns,
+        })
+    }
+
+    /// Returns true if the file is known to be unchanged compared to `previous`.
+    /// If the modification time is not available the file is always considered
+    /// changed.
+    fn is_unchanged_since(&self, previous: &FileStat) -> bool {
+        self.mtime_ns.is_some() && self == previous
+    }
+}
+
+/// The index written by a previous run.
+struct PreviousIndex {
+    repodata: RepoData,
+    stat_cache: HashMap<String, FileStat>,
+    run_exports: Option<RunExportsRepodata>,
+}
+
+impl PreviousIndex {
+    /// Returns the record of `file_name` from the previous run if the archive did not change since.
+    /// If `with_run_exports` is `true` the record is only returned if the run exports of the
+    /// archive are known as well.
+    fn reusable_record(
+        &self,
+        file_name: &str,
+        archive_type: ArchiveType,
+        stat: &FileStat,
+        with_run_exports: bool,
+    ) -> Option<PackageRecord> {
+        let previous_stat = self.stat_cache.get(file_name)?;
+        if !stat.is_unchanged_since(previous_stat) {
+            return None;
+        }
+
+        let (records, run_exports) = match archive_type {
+            ArchiveType::TarBz2 => (
+                &self.repodata.packages,
+                self.run_exports.as_ref().map(|r| &r.packages),
+            ),
+            ArchiveType::Conda => (
+                &self.repodata.conda_packages,
+                self.run_exports.as_ref().map(|r| &r.conda_packages),
+            ),
+        };
+        let mut record = records
+            .get(file_name)
+            .filter(|record| record.size == Some(stat.size))
+            .cloned()?;
+        if with_run_exports {
+            record.run_exports = Some(run_exports?.get(file_name)?.run_exports.clone());
+        }
+        Some(record)
+    }
+}
+
+/// Reads the unpatched repodata, stat cache and `run_exports.json` written by a previous run. The
+/// unpatched repodata is read from `repodata_from_packages.json` if the previous run applied
+/// patches, and from `repodata.json` otherwise. Returns `None` if either the repodata or the stat
+/// cache is missing or cannot be parsed, in which case all archives have to be read again.
+fn read_previous_index(subdir_path: &Path) -> Option<PreviousIndex> {
+    let repodata_path = Some(subdir_path.join(REPODATA_FROM_PACKAGES_FILENAME))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| subdir_path.join("repodata.json"));
+    let stat_cache_path = subdir_path.join(STAT_CACHE_PATH);
+    if !repodata_path.is_file() || !stat_cache_path.is_file() {
+        return None;
+    }
+
+    let repodata = match RepoData::from_path(&repodata_path) {
+        Ok(repodata) => repodata,
+        Err(e) => {
+            tracing::warn!(
+                "Could not read {}, reindexing all packages: {e}",
+                repodata_path.display()
+            );
+            return None;
+        }
+    };
+
+    let stat_cache = match fs_err::read_to_string(&stat_cache_path)
+        .and_then(|content| serde_json::from_str(&content).map_err(std::io::Error::from))
+    {
+        Ok(stat_cache) => stat_cache,
+        Err(e) => {
+            tracing::warn!(
+                "Could not read {}, reindexing all packages: {e}",
+                stat_cache_path.display()
+            );
+            return None;
+        }
+    };
+
+    // A missing or invalid `run_exports.json` only means that the run exports have to be read
+    // from the archives again.
+    let run_exports = fs_err::read_to_string(subdir_path.join("run_exports.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+
+    Some(PreviousIndex {
+        repodata,
+        stat_cache,
+        run_exports,
+    })
 }
 
 /// Create a new `repodata.json` for all packages in the given output folder. If `target_platform` is
 /// `Some`, only that specific subdir is indexed. Otherwise indexes all subdirs and creates a
 /// `repodata.json` for each.
+///
+/// Archives that cannot be read are left out of the index and returned in the
+/// [`IndexSummary`].
 pub fn index(
     output_folder: &Path,
     target_platform: Option<&Platform>,
-) -> Result<(), std::io::Error> {
+) -> Result<IndexSummary, std::io::Error> {
+    index_with_options(output_folder, target_platform, &IndexOptions::default())
+}
+
+/// Create or update the `repodata.json` for all packages in the given output folder using the
+/// given [`IndexOptions`]. If `target_platform` is `Some`, only that specific subdir is indexed.
+/// Otherwise indexes all subdirs and creates a `repodata.json` for each.
+///
+/// The archives are read one at a time on the current thread, use [`index_async`] to read them
+/// in parallel.
+pub fn index_with_options(
+    output_folder: &Path,
+    target_platform: Option<&Platform>,
+    options: &IndexOptions,
+) -> Result<IndexSummary, std::io::Error> {
+    let mut summary = IndexSummary::default();
+    let reporter = options.reporter.as_deref();
+    for (platform, archives) in find_subdirs(output_folder, target_platform, options)? {
+        let (mut subdir, to_read) =
+            SubdirIndex::new(output_folder.join(&platform), platform, archives, options);
+        if let Some(reporter) = reporter {
+            reporter.on_subdir_start(&subdir.platform, to_read.len());
+        }
+        for archive in to_read {
+            let record = read_archive(&archive, reporter);
+            subdir.add_read_result(archive, record);
+        }
+        summary.failed.extend(subdir.finish()?);
+    }
+
+    Ok(summary)
+}
+
+/// Create or update the `repodata.json` for all packages in the given output folder using the
+/// given [`IndexOptions`]. This is the asynchronous version of [`index_with_options`].
+///
+/// The archives are read in parallel on the blocking thread pool of tokio, at most
+/// [`IndexOptions::max_parallel`] at the same time.
+pub async fn index_async(
+    output_folder: &Path,
+    target_platform: Option<&Platform>,
+    options: &IndexOptions,
+) -> Result<IndexSummary, std::io::Error> {
+    let mut summary = IndexSummary::default();
+    let max_parallel = options.max_parallel.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+
+    for (platform, archives) in find_subdirs(output_folder, target_platform, options)? {
+        let (mut subdir, to_read) =
+            SubdirIndex::new(output_folder.join(&platform), platform, archives, options);
+        if let Some(reporter) = &options.reporter {
+            reporter.on_subdir_start(&subdir.platform, to_read.len());
+        }
+
+        let mut results = futures::stream::iter(to_read)
+            .map(|archive| {
+                let reporter = options.reporter.clone();
+                async move {
+                    match tokio::task::spawn_blocking(move || {
+                        let record = read_archive(&archive, reporter.as_deref());
+                        (archive, record)
+                    })
+                    .await
+                    {
+                        Ok(result) => Ok(result),
+                        Err(err) => {
+                            if let Ok(reason) = err.try_into_panic() {
+                                std::panic::resume_unwind(reason);
+                            }
+                            Err(std::io::Error::new(
+                                std::io::ErrorKind::Interrupted,
+                                "reading the archive was cancelled",
+                            ))
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(max_parallel.max(1));
+        while let Some((archive, record)) = results.try_next().await? {
+            subdir.add_read_result(archive, record);
+        }
+        summary.failed.extend(subdir.finish()?);
+    }
+
+    Ok(summary)
+}
+
+/// The paths and types of the archives in a subdir.
+type Archives = Vec<(PathBuf, ArchiveType)>;
+
+/// Finds all subdirs in `output_folder` that need to be indexed together with the archives they
+/// contain.
+fn find_subdirs(
+    output_folder: &Path,
+    target_platform: Option<&Platform>,
+    options: &IndexOptions,
+) -> Result<Vec<(String, Archives)>, std::io::Error> {
     let entries = WalkDir::new(output_folder).into_iter();
     let entries: Vec<(PathBuf, ArchiveType)> = entries
         .filter_entry(|e| e.depth() <= 2)
@@ -124,7 +535,17 @@ pub fn index(
                 }
             })
         })
-        .collect::<std::collections::HashSet<_>>();
+        .collect::<HashSet<_>>();
+
+    // When updating an existing index, subdirs from which all packages have been removed still
+    // need to be reindexed.
+    if options.incremental {
+        for entry in fs_err::read_dir(output_folder)?.filter_map(Result::ok) {
+            if entry.path().join("repodata.json").is_file() {
+                platforms.insert(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
 
     // Always create noarch subdir
     if !output_folder.join("noarch").exists() {
@@ -145,6 +566,7 @@ pub fn index(
         }
     }
 
+    let mut subdirs = Vec::new();
     for platform in platforms {
         if let Some(target_platform) = target_platform {
             if platform != target_platform.to_string() {
@@ -159,52 +581,383 @@ pub fn index(
             }
         }
 
-        let mut repodata = RepoData {
+        let subdir_entries = entries
+            .iter()
+            .filter(|(p, _)| {
+                p.parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|file_name| file_name == OsStr::new(&platform))
+            })
+            .cloned()
+            .collect();
+        subdirs.push((platform, subdir_entries));
+    }
+
+    Ok(subdirs)
+}
+
+/// An archive that is not part of the previous index and therefore has to be read.
+struct ArchiveToRead {
+    path: PathBuf,
+    archive_type: ArchiveType,
+    file_name: String,
+    stat: FileStat,
+}
+
+/// Reads the package record of an archive and notifies the reporter.
+fn read_archive(
+    archive: &ArchiveToRead,
+    reporter: Option<&dyn Reporter>,
+) -> Result<PackageRecord, std::io::Error> {
+    let index = reporter.map(|reporter| reporter.on_read_start(&archive.path));
+    let record = match archive.archive_type {
+        ArchiveType::TarBz2 => package_record_from_tar_bz2(&archive.path),
+        ArchiveType::Conda => package_record_from_conda(&archive.path),
+    };
+    if let Some(reporter) = reporter {
+        if let Err(e) = &record {
+            reporter.on_read_failed(&archive.path, e);
+        }
+        if let Some(index) = index {
+            reporter.on_read_complete(index);
+        }
+    }
+    record
+}
+
+/// The state of a single subdir while it is being indexed.
+struct SubdirIndex<'o> {
+    subdir_path: PathBuf,
+    platform: String,
+    options: &'o IndexOptions,
+    previous: Option<PreviousIndex>,
+    repodata: RepoData,
+    run_exports: RunExportsRepodata,
+    stat_cache: HashMap<String, FileStat>,
+    reused_records: usize,
+    failed: Vec<(PathBuf, std::io::Error)>,
+}
+
+impl<'o> SubdirIndex<'o> {
+    /// Starts indexing a subdir. Records of archives that did not change since the previous run
+    /// are reused, all other archives are returned and have to be read and passed to
+    /// [`Self::add_read_result`].
+    fn new(
+        subdir_path: PathBuf,
+        platform: String,
+        archives: Archives,
+        options: &'o IndexOptions,
+    ) -> (Self, Vec<ArchiveToRead>) {
+        let previous = if options.incremental {
+            read_previous_index(&subdir_path)
+        } else {
+            None
+        };
+
+        let repodata = RepoData {
             info: Some(ChannelInfo {
                 subdir: platform.clone(),
                 base_url: None,
             }),
             packages: HashMap::default(),
             conda_packages: HashMap::default(),
-            removed: HashSet::default(),
+            removed: previous
+                .as_ref()
+                .map(|previous| previous.repodata.removed.clone())
+                .unwrap_or_default(),
             version: Some(2),
         };
+        let run_exports = RunExportsRepodata {
+            info: Some(RunExportsInfo {
+                subdir: platform.clone(),
+                version: 1,
+            }),
+            ..RunExportsRepodata::default()
+        };
+        let mut subdir = Self {
+            subdir_path,
+            platform,
+            options,
+            previous,
+            repodata,
+            run_exports,
+            stat_cache: HashMap::new(),
+            reused_records: 0,
+            failed: Vec::new(),
+        };
 
-        for (p, t) in entries.iter().filter_map(|(p, t)| {
-            p.parent().and_then(|parent| {
-                parent.file_name().and_then(|file_name| {
-                    if file_name == OsStr::new(&platform) {
-                        // If the file_name is the platform we're looking for, return Some((p, t))
-                        Some((p, t))
-                    } else {
-                        // Otherwise, we return None to filter out this item
-                        None
-                    }
-                })
-            })
-        }) {
-            let record = match t {
-                ArchiveType::TarBz2 => package_record_from_tar_bz2(p),
-                ArchiveType::Conda => package_record_from_conda(p),
-            };
-            let (Ok(record), Some(file_name)) = (record, p.file_name()) else {
-                tracing::info!("Could not read package record from {:?}", p);
+        let mut to_read = Vec::new();
+        for (path, archive_type) in archives {
+            let Some(file_name) = path.file_name().map(|f| f.to_string_lossy().to_string()) else {
                 continue;
             };
-            match t {
-                ArchiveType::TarBz2 => repodata
+            let stat = match FileStat::from_path(&path) {
+                Ok(stat) => stat,
+                Err(e) => {
+                    tracing::warn!("Could not read package record from {:?}: {e}", path);
+                    if let Some(reporter) = &options.reporter {
+                        reporter.on_read_failed(&path, &e);
+                    }
+                    subdir.failed.push((path, e));
+                    continue;
+                }
+            };
+
+            // Reuse the record from the previous run if the archive did not change.
+            let previous_record = subdir.previous.as_ref().and_then(|previous| {
+                previous.reusable_record(&file_name, archive_type, &stat, options.write_run_exports)
+            });
+            if let Some(record) = previous_record {
+                subdir.reused_records += 1;
+                subdir.add_record(file_name, archive_type, stat, record);
+            } else {
+                to_read.push(ArchiveToRead {
+                    path,
+                    archive_type,
+                    file_name,
+                    stat,
+                });
+            }
+        }
+
+        (subdir, to_read)
+    }
+
+    /// Adds the result of reading an archive returned by [`Self::new`] to the index.
+    fn add_read_result(
+        &mut self,
+        archive: ArchiveToRead,
+        record: Result<PackageRecord, std::io::Error>,
+    ) {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::warn!("Could not read package record from {:?}: {e}", archive.path);
+                self.failed.push((archive.path, e));
+                return;
+            }
+        };
+        self.add_record(
+            archive.file_name,
+            archive.archive_type,
+            archive.stat,
+            record,
+        );
+    }
+
+    fn add_record(
+        &mut self,
+        file_name: String,
+        archive_type: ArchiveType,
+        stat: FileStat,
+        mut record: PackageRecord,
+    ) {
+        // Run exports are not part of the `repodata.json` but are stored separately.
+        let package_run_exports = PackageRunExports {
+            run_exports: record.run_exports.take().unwrap_or_default(),
+        };
+        match archive_type {
+            ArchiveType::TarBz2 => {
+                self.repodata.packages.insert(file_name.clone(), record);
+                self.run_exports
                     .packages
-                    .insert(file_name.to_string_lossy().to_string(), record),
-                ArchiveType::Conda => repodata
+                    .insert(file_name.clone(), package_run_exports);
+            }
+            ArchiveType::Conda => {
+                self.repodata
                     .conda_packages
-                    .insert(file_name.to_string_lossy().to_string(), record),
-            };
+                    .insert(file_name.clone(), record);
+                self.run_exports
+                    .conda_packages
+                    .insert(file_name.clone(), package_run_exports);
+            }
+        };
+        self.stat_cache.insert(file_name, stat);
+    }
+
+    /// Writes the `repodata.json` of the subdir, together with the additional files requested in
+    /// the options. Returns the archives that could not be read.
+    fn finish(self) -> Result<Vec<(PathBuf, std::io::Error)>, std::io::Error> {
+        let Self {
+            subdir_path,
+            platform,
+            options,
+            previous,
+            mut repodata,
+            run_exports,
+            stat_cache,
+            reused_records,
+            failed,
+        } = self;
+
+        if let Some(previous) = &previous {
+            let removed_records = previous
+                .repodata
+                .packages
+                .keys()
+                .filter(|file_name| !repodata.packages.contains_key(*file_name))
+                .chain(
+                    previous
+                        .repodata
+                        .conda_packages
+                        .keys()
+                        .filter(|file_name| !repodata.conda_packages.contains_key(*file_name)),
+                )
+                .count();
+            tracing::info!(
+                "Updated index of {platform}: reused {reused_records} records, read {} archives, dropped {removed_records} records",
+                stat_cache.len() - reused_records,
+            );
+        }
+
+        // Files that are derived from the packages in the subdir are removed when they are not
+        // written, otherwise they would go out of sync with the `repodata.json`.
+        let repodata_from_packages_file = subdir_path.join(REPODATA_FROM_PACKAGES_FILENAME);
+        if let Some(patch) = &options.repodata_patch {
+            File::create(&repodata_from_packages_file)?
+                .write_all(serde_json::to_string_pretty(&repodata)?.as_bytes())?;
+            if let Some(instructions) = patch.subdirs.get(&platform) {
+                repodata.apply_patches(instructions);
+            }
+        } else {
+            remove_file_if_exists(&repodata_from_packages_file)?;
+        }
+
+        let repodata_bytes = serde_json::to_vec_pretty(&repodata)?;
+        File::create(subdir_path.join("repodata.json"))?.write_all(&repodata_bytes)?;
+
+        let zst_file = subdir_path.join("repodata.json.zst");
+        if options.write_zst {
+            let compressed =
+                zstd::encode_all(repodata_bytes.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            File::create(&zst_file)?.write_all(&compressed)?;
+        } else {
+            remove_file_if_exists(&zst_file)?;
         }
-        let out_file = output_folder.join(platform).join("repodata.json");
-        File::create(&out_file)?.write_all(serde_json::to_string_pretty(&repodata)?.as_bytes())?;
+
+        if options.write_shards {
+            write_shards(&subdir_path, &platform, &repodata)?;
+        } else {
+            remove_file_if_exists(&subdir_path.join(REPODATA_SHARDS_FILENAME))?;
+            remove_shards(&subdir_path.join(SHARDS_DIR))?;
+        }
+
+        let run_exports_file = subdir_path.join("run_exports.json");
+        if options.write_run_exports {
+            File::create(&run_exports_file)?
+                .write_all(serde_json::to_string_pretty(&run_exports)?.as_bytes())?;
+        } else {
+            remove_file_if_exists(&run_exports_file)?;
+        }
+
+        let stat_cache_file = subdir_path.join(STAT_CACHE_PATH);
+        if let Some(parent) = stat_cache_file.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        File::create(&stat_cache_file)?
+            .write_all(serde_json::to_string(&stat_cache)?.as_bytes())?;
+
+        if let Some(reporter) = &options.reporter {
+            reporter.on_subdir_complete(&platform);
+        }
+
+        Ok(failed)
     }
+}
+
+/// Writes the sharded repodata of a subdir. Every package name is stored in its own shard which is
+/// named after the sha256 hash of its contents. Shards that are no longer referenced by the index
+/// are removed.
+fn write_shards(
+    subdir_path: &Path,
+    platform: &str,
+    repodata: &RepoData,
+) -> Result<(), std::io::Error> {
+    let mut shards: HashMap<&str, Shard> = HashMap::new();
+    let empty_shard = || Shard {
+        packages: HashMap::default(),
+        conda_packages: HashMap::default(),
+        removed: HashSet::default(),
+    };
+    for (file_name, record) in &repodata.packages {
+        shards
+            .entry(record.name.as_normalized())
+            .or_insert_with(empty_shard)
+            .packages
+            .insert(file_name.clone(), record.clone());
+    }
+    for (file_name, record) in &repodata.conda_packages {
+        shards
+            .entry(record.name.as_normalized())
+            .or_insert_with(empty_shard)
+            .conda_packages
+            .insert(file_name.clone(), record.clone());
+    }
+
+    let shards_dir = subdir_path.join(SHARDS_DIR);
+    fs_err::create_dir_all(&shards_dir)?;
+
+    let mut shard_hashes = HashMap::default();
+    let mut shard_files = HashSet::new();
+    for (name, shard) in shards {
+        let encoded = rmp_serde::to_vec_named(&shard)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let compressed = zstd::encode_all(encoded.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        let hash = rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(&compressed);
+        let shard_file = format!("{hash:x}.msgpack.zst");
+        File::create(shards_dir.join(&shard_file))?.write_all(&compressed)?;
+        shard_files.insert(shard_file);
+        shard_hashes.insert(name.to_string(), hash);
+    }
+
+    prune_shards(&shards_dir, &shard_files)?;
+
+    let sharded_repodata = ShardedRepodata {
+        info: ShardedSubdirInfo {
+            subdir: platform.to_string(),
+            base_url: "./".to_string(),
+            shards_base_url: format!("./{SHARDS_DIR}/"),
+        },
+        shards: shard_hashes,
+    };
+    let encoded = rmp_serde::to_vec_named(&sharded_repodata)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let compressed = zstd::encode_all(encoded.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    File::create(subdir_path.join(REPODATA_SHARDS_FILENAME))?.write_all(&compressed)?;
 
     Ok(())
 }
 
+/// Removes the shards in `shards_dir` that are not in `keep`.
+fn prune_shards(shards_dir: &Path, keep: &HashSet<String>) -> Result<(), std::io::Error> {
+    for entry in fs_err::read_dir(shards_dir)?.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.ends_with(".msgpack.zst") && !keep.contains(&file_name) {
+            fs_err::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes all shards of a previous index and the shards directory itself if
+/// it does not contain any other files.
+fn remove_shards(shards_dir: &Path) -> Result<(), std::io::Error> {
+    if !shards_dir.is_dir() {
+        return Ok(());
+    }
+    prune_shards(shards_dir, &HashSet::new())?;
+    if fs_err::read_dir(shards_dir)?.next().is_none() {
+        fs_err::remove_dir(shards_dir)?;
+    }
+    Ok(())
+}
+
+/// Removes the file at `path` if it exists.
+fn remove_file_if_exists(path: &Path) -> Result<(), std::io::Error> {
+    match fs_err::remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
 // TODO: write proper unit tests for above functions
@@ -0,0 +1,32 @@
+use std::path::Path;
+
+/// A trait that enables being notified of the progress of indexing a channel.
+pub trait Reporter: Send + Sync {
+    /// Called when indexing of a subdir starts.
+    ///
+    /// The `archives_to_read` parameter is the number of archives that have to
+    /// be read. Archives whose records are reused from a previous run are not
+    /// included.
+    fn on_subdir_start(&self, _subdir: &str, _archives_to_read: usize) {}
+
+    /// Called when reading an archive started.
+    ///
+    /// Returns an index that can be used to identify the archive in subsequent
+    /// calls.
+    fn on_read_start(&self, _path: &Path) -> usize {
+        0
+    }
+
+    /// Called when reading an archive finished, regardless of whether a
+    /// package record could be read from it.
+    ///
+    /// The `index` parameter is the index returned by `on_read_start`.
+    fn on_read_complete(&self, _index: usize) {}
+
+    /// Called when no package record could be read from an archive. The
+    /// archive is not included in the index.
+    fn on_read_failed(&self, _path: &Path, _error: &std::io::Error) {}
+
+    /// Called when all files of a subdir have been written.
+    fn on_subdir_complete(&self, _subdir: &str) {}
+}
@@ -2,10 +2,17 @@ use std::{
     fs,
     fs::File,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
-use rattler_conda_types::Platform;
-use rattler_index::index;
+use rattler_conda_types::{Platform, RepoData, Shard, ShardedRepodata};
+use rattler_index::{
+    index, index_async, index_with_options, repodata_patch_from_path, IndexOptions, Reporter,
+};
+use rattler_package_streaming::write::{write_tar_bz2_package, CompressionLevel};
 use serde_json::Value;
 
 fn test_data_dir() -> PathBuf {
@@ -91,3 +98,355 @@ fn test_index_empty_directory() {
     assert!(res.is_ok());
     assert_eq!(fs::read_dir(temp_dir).unwrap().count(), 0);
 }
+
+/// Writes a minimal `.tar.bz2` package that only contains an `info/index.json` and, if
+/// `weak_run_exports` is not empty, an `info/run_exports.json`.
+fn write_dummy_package(
+    subdir: &Path,
+    name: &str,
+    version: &str,
+    depends: &[&str],
+    weak_run_exports: &[&str],
+) -> PathBuf {
+    let package_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(package_dir.path().join("info")).unwrap();
+    let index_json = serde_json::json!({
+        "name": name,
+        "version": version,
+        "build": "h123_0",
+        "build_number": 0,
+        "subdir": subdir.file_name().unwrap().to_str().unwrap(),
+        "depends": depends,
+    });
+    fs::write(
+        package_dir.path().join("info/index.json"),
+        index_json.to_string(),
+    )
+    .unwrap();
+    let mut paths = vec![package_dir.path().join("info/index.json")];
+    if !weak_run_exports.is_empty() {
+        let run_exports_json = serde_json::json!({ "weak": weak_run_exports });
+        fs::write(
+            package_dir.path().join("info/run_exports.json"),
+            run_exports_json.to_string(),
+        )
+        .unwrap();
+        paths.push(package_dir.path().join("info/run_exports.json"));
+    }
+
+    let archive_path = subdir.join(format!("{name}-{version}-h123_0.tar.bz2"));
+    write_tar_bz2_package(
+        File::create(&archive_path).unwrap(),
+        package_dir.path(),
+        &paths,
+        CompressionLevel::Default,
+        None,
+        None,
+    )
+    .unwrap();
+    archive_path
+}
+
+#[test]
+fn test_index_incremental() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let subdir = temp_dir.path().join("linux-64");
+    fs::create_dir(&subdir).unwrap();
+    let foo = write_dummy_package(&subdir, "foo", "1.0", &[], &[]);
+    let bar = write_dummy_package(&subdir, "bar", "1.0", &[], &[]);
+
+    let options = IndexOptions {
+        incremental: true,
+        ..IndexOptions::default()
+    };
+    assert!(index_with_options(temp_dir.path(), None, &options)
+        .unwrap()
+        .is_complete());
+
+    let repodata_path = subdir.join("repodata.json");
+    let repodata = RepoData::from_path(&repodata_path).unwrap();
+    assert_eq!(repodata.packages.len(), 2);
+
+    // Modify the existing record on disk. Because the archive did not change the record from
+    // the existing repodata.json should be reused.
+    let mut repodata_json: Value =
+        serde_json::from_reader(File::open(&repodata_path).unwrap()).unwrap();
+    repodata_json["packages"]["foo-1.0-h123_0.tar.bz2"]["depends"] = serde_json::json!(["baz"]);
+    fs::write(&repodata_path, repodata_json.to_string()).unwrap();
+
+    // Remove a package and add a new one.
+    fs::remove_file(bar).unwrap();
+    write_dummy_package(&subdir, "qux", "2.0", &[], &[]);
+
+    assert!(index_with_options(temp_dir.path(), None, &options)
+        .unwrap()
+        .is_complete());
+    let repodata = RepoData::from_path(&repodata_path).unwrap();
+    let mut file_names = repodata.packages.keys().cloned().collect::<Vec<_>>();
+    file_names.sort();
+    assert_eq!(
+        file_names,
+        vec!["foo-1.0-h123_0.tar.bz2", "qux-2.0-h123_0.tar.bz2"]
+    );
+    assert_eq!(
+        repodata.packages["foo-1.0-h123_0.tar.bz2"].depends,
+        vec!["baz".to_string()]
+    );
+
+    // Touching the archive causes it to be read again.
+    File::options()
+        .write(true)
+        .open(&foo)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+        .unwrap();
+    assert!(index_with_options(temp_dir.path(), None, &options)
+        .unwrap()
+        .is_complete());
+    let repodata = RepoData::from_path(&repodata_path).unwrap();
+    assert!(repodata.packages["foo-1.0-h123_0.tar.bz2"]
+        .depends
+        .is_empty());
+
+    // A full reindex ignores the existing repodata.json.
+    let mut repodata_json: Value =
+        serde_json::from_reader(File::open(&repodata_path).unwrap()).unwrap();
+    repodata_json["packages"]["qux-2.0-h123_0.tar.bz2"]["depends"] = serde_json::json!(["baz"]);
+    fs::write(&repodata_path, repodata_json.to_string()).unwrap();
+    assert!(index(temp_dir.path(), None).unwrap().is_complete());
+    let repodata = RepoData::from_path(&repodata_path).unwrap();
+    assert!(repodata.packages["qux-2.0-h123_0.tar.bz2"]
+        .depends
+        .is_empty());
+}
+
+#[test]
+fn test_index_additional_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let subdir = temp_dir.path().join("linux-64");
+    fs::create_dir(&subdir).unwrap();
+    write_dummy_package(&subdir, "foo", "1.0", &[], &["foo >=1.0,<2"]);
+    write_dummy_package(&subdir, "foo", "1.1", &[], &["foo >=1.1,<2"]);
+    write_dummy_package(&subdir, "bar", "1.0", &["foo"], &[]);
+
+    let options = IndexOptions {
+        incremental: true,
+        write_zst: true,
+        write_shards: true,
+        write_run_exports: true,
+        ..IndexOptions::default()
+    };
+    assert!(index_with_options(temp_dir.path(), None, &options)
+        .unwrap()
+        .is_complete());
+
+    // The compressed repodata is identical to the uncompressed one.
+    let repodata_json = fs::read(subdir.join("repodata.json")).unwrap();
+    let repodata_zst = fs::read(subdir.join("repodata.json.zst")).unwrap();
+    assert_eq!(
+        zstd::decode_all(repodata_zst.as_slice()).unwrap(),
+        repodata_json
+    );
+    let repodata = RepoData::from_path(subdir.join("repodata.json")).unwrap();
+    assert!(repodata
+        .packages
+        .values()
+        .all(|record| record.run_exports.is_none()));
+
+    // Every package name has its own shard that contains all its records.
+    let shards_index = fs::read(subdir.join("repodata_shards.msgpack.zst")).unwrap();
+    let shards_index: ShardedRepodata =
+        rmp_serde::from_slice(&zstd::decode_all(shards_index.as_slice()).unwrap()).unwrap();
+    assert_eq!(shards_index.info.subdir, "linux-64");
+    assert_eq!(shards_index.shards.len(), 2);
+    let shard_path = subdir
+        .join("shards")
+        .join(format!("{:x}.msgpack.zst", shards_index.shards["foo"]));
+    let shard: Shard =
+        rmp_serde::from_slice(&zstd::decode_all(fs::read(shard_path).unwrap().as_slice()).unwrap())
+            .unwrap();
+    let mut file_names = shard.packages.keys().cloned().collect::<Vec<_>>();
+    file_names.sort();
+    assert_eq!(
+        file_names,
+        vec!["foo-1.0-h123_0.tar.bz2", "foo-1.1-h123_0.tar.bz2"]
+    );
+
+    // The run exports of all packages are collected.
+    let expected_run_exports = serde_json::json!({
+        "info": { "subdir": "linux-64", "version": 1 },
+        "packages": {
+            "bar-1.0-h123_0.tar.bz2": { "run_exports": {} },
+            "foo-1.0-h123_0.tar.bz2": { "run_exports": { "weak": ["foo >=1.0,<2"] } },
+            "foo-1.1-h123_0.tar.bz2": { "run_exports": { "weak": ["foo >=1.1,<2"] } },
+        },
+        "packages.conda": {},
+    });
+    let run_exports_path = subdir.join("run_exports.json");
+    let run_exports: Value =
+        serde_json::from_reader(File::open(&run_exports_path).unwrap()).unwrap();
+    assert_eq!(run_exports, expected_run_exports);
+
+    // Run exports of reused records are retained and stale shards are removed.
+    fs::remove_file(subdir.join("foo-1.1-h123_0.tar.bz2")).unwrap();
+    assert!(index_with_options(temp_dir.path(), None, &options)
+        .unwrap()
+        .is_complete());
+    let run_exports: Value =
+        serde_json::from_reader(File::open(&run_exports_path).unwrap()).unwrap();
+    assert_eq!(
+        run_exports["packages"]["foo-1.0-h123_0.tar.bz2"],
+        expected_run_exports["packages"]["foo-1.0-h123_0.tar.bz2"]
+    );
+    assert_eq!(fs::read_dir(subdir.join("shards")).unwrap().count(), 2);
+
+    // Files that are not requested are removed so they don't go out of sync.
+    assert!(index(temp_dir.path(), None).unwrap().is_complete());
+    assert!(!subdir.join("repodata.json.zst").exists());
+    assert!(!subdir.join("repodata_shards.msgpack.zst").exists());
+    assert!(!subdir.join("shards").exists());
+    assert!(!run_exports_path.exists());
+}
+
+#[test]
+fn test_index_repodata_patch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let subdir = temp_dir.path().join("linux-64");
+    fs::create_dir(&subdir).unwrap();
+    write_dummy_package(&subdir, "foo", "1.0", &[], &[]);
+    write_dummy_package(&subdir, "bar", "1.0", &[], &[]);
+
+    // Create a repodata patches package.
+    let patch_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(patch_dir.path().join("linux-64")).unwrap();
+    let patch_instructions = serde_json::json!({
+        "remove": ["bar-1.0-h123_0.tar.bz2"],
+        "packages": { "foo-1.0-h123_0.tar.bz2": { "depends": ["baz"] } },
+    });
+    let patch_instructions_path = patch_dir.path().join("linux-64/patch_instructions.json");
+    fs::write(&patch_instructions_path, patch_instructions.to_string()).unwrap();
+    let patch_package_dir = tempfile::tempdir().unwrap();
+    let patch_package_path = patch_package_dir
+        .path()
+        .join("repodata-patches-1.0-0.tar.bz2");
+    write_tar_bz2_package(
+        File::create(&patch_package_path).unwrap(),
+        patch_dir.path(),
+        &[patch_instructions_path],
+        CompressionLevel::Default,
+        None,
+        None,
+    )
+    .unwrap();
+
+    // Reading the patches from the package or the directory gives the same result.
+    let patch = repodata_patch_from_path(&patch_package_path).unwrap();
+    assert_eq!(
+        patch.subdirs,
+        repodata_patch_from_path(patch_dir.path()).unwrap().subdirs
+    );
+
+    let options = IndexOptions {
+        incremental: true,
+        repodata_patch: Some(patch),
+        ..IndexOptions::default()
+    };
+    for _ in 0..2 {
+        assert!(index_with_options(temp_dir.path(), None, &options)
+            .unwrap()
+            .is_complete());
+
+        let repodata = RepoData::from_path(subdir.join("repodata.json")).unwrap();
+        assert_eq!(repodata.packages.len(), 1);
+        assert_eq!(
+            repodata.packages["foo-1.0-h123_0.tar.bz2"].depends,
+            vec!["baz".to_string()]
+        );
+        assert!(repodata.removed.contains("bar-1.0-h123_0.tar.bz2"));
+
+        let repodata_from_packages =
+            RepoData::from_path(subdir.join("repodata_from_packages.json")).unwrap();
+        assert_eq!(repodata_from_packages.packages.len(), 2);
+        assert!(repodata_from_packages.packages["foo-1.0-h123_0.tar.bz2"]
+            .depends
+            .is_empty());
+        assert!(repodata_from_packages.removed.is_empty());
+    }
+
+    // Without patches the `repodata_from_packages.json` is removed again.
+    assert!(index(temp_dir.path(), None).unwrap().is_complete());
+    assert!(!subdir.join("repodata_from_packages.json").exists());
+    let repodata = RepoData::from_path(subdir.join("repodata.json")).unwrap();
+    assert_eq!(repodata.packages.len(), 2);
+}
+
+#[derive(Default)]
+struct CountingReporter {
+    archives_to_read: AtomicUsize,
+    started: AtomicUsize,
+    completed: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl Reporter for CountingReporter {
+    fn on_subdir_start(&self, _subdir: &str, archives_to_read: usize) {
+        self.archives_to_read
+            .fetch_add(archives_to_read, Ordering::SeqCst);
+    }
+
+    fn on_read_start(&self, _path: &Path) -> usize {
+        self.started.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn on_read_complete(&self, _index: usize) {
+        self.completed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_read_failed(&self, _path: &Path, _error: &std::io::Error) {
+        self.failed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_index_async() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let subdir = temp_dir.path().join("linux-64");
+    fs::create_dir(&subdir).unwrap();
+    for i in 0..10 {
+        write_dummy_package(&subdir, &format!("foo{i}"), "1.0", &[], &[]);
+    }
+    fs::write(subdir.join("broken-1.0-h123_0.tar.bz2"), "not an archive").unwrap();
+
+    let summary = index(temp_dir.path(), None).unwrap();
+    assert_eq!(summary.failed.len(), 1);
+    assert!(summary.failed[0].0.ends_with("broken-1.0-h123_0.tar.bz2"));
+    let expected = fs::read_to_string(subdir.join("repodata.json")).unwrap();
+
+    let reporter = Arc::new(CountingReporter::default());
+    let options = IndexOptions {
+        max_parallel: Some(3),
+        reporter: Some(reporter.clone()),
+        ..IndexOptions::default()
+    };
+    let summary = index_async(temp_dir.path(), None, &options).await.unwrap();
+    assert_eq!(summary.failed.len(), 1);
+
+    // The result is the same as indexing sequentially.
+    assert_eq!(
+        fs::read_to_string(subdir.join("repodata.json")).unwrap(),
+        expected
+    );
+    assert_eq!(
+        RepoData::from_path(subdir.join("repodata.json"))
+            .unwrap()
+            .packages
+            .len(),
+        10
+    );
+
+    // The broken archive is reported as well.
+    assert_eq!(reporter.archives_to_read.load(Ordering::SeqCst), 11);
+    assert_eq!(reporter.started.load(Ordering::SeqCst), 11);
+    assert_eq!(reporter.completed.load(Ordering::SeqCst), 11);
+    assert_eq!(reporter.failed.load(Ordering::SeqCst), 1);
+}
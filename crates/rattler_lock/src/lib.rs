@@ -87,7 +87,10 @@ use std::{
 
 use fxhash::FxHashMap;
 use pep508_rs::{ExtraName, Requirement};
-use rattler_conda_types::{MatchSpec, PackageRecord, Platform, RepoDataRecord};
+use rattler_conda_types::{
+    EnvironmentYaml, ExplicitEnvironmentSpec, MatchSpec, MatchSpecOrSubSection, NamedChannelOrUrl,
+    PackageRecord, Platform, RepoDataRecord,
+};
 use url::Url;
 
 mod builder;
@@ -399,6 +402,64 @@ impl Environment {
         )
     }
 
+    /// Converts the conda packages for the specified platform into an
+    /// [`ExplicitEnvironmentSpec`]. The url of every package includes the
+    /// hash of the package archive. Returns `None` if the specified platform
+    /// is not defined for this environment.
+    ///
+    /// Pypi packages cannot be represented in an explicit environment and are
+    /// ignored.
+    pub fn to_explicit_environment_spec(
+        &self,
+        platform: Platform,
+    ) -> Result<Option<ExplicitEnvironmentSpec>, ConversionError> {
+        Ok(self
+            .conda_repodata_records_for_platform(platform)?
+            .map(|records| {
+                ExplicitEnvironmentSpec::from_repodata_records(&records, Some(platform))
+            }))
+    }
+
+    /// Converts the packages for the specified platform into an
+    /// [`EnvironmentYaml`] that pins all conda packages, including the hashes
+    /// of their archives, and lists the pypi packages in a `pip` subsection.
+    /// The channels of this environment are used in order of priority.
+    /// Returns `None` if the specified platform is not defined for this
+    /// environment.
+    pub fn to_environment_yaml(
+        &self,
+        platform: Platform,
+    ) -> Result<Option<EnvironmentYaml>, ConversionError> {
+        let Some(records) = self.conda_repodata_records_for_platform(platform)? else {
+            return Ok(None);
+        };
+
+        let mut environment_yaml = EnvironmentYaml::from_repodata_records(&records);
+        environment_yaml.channels = self
+            .channels()
+            .iter()
+            .filter_map(|channel| channel.url.parse::<NamedChannelOrUrl>().ok())
+            .collect();
+
+        let mut pypi_specs = self
+            .pypi_packages_for_platform(platform)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(package, _)| format!("{}=={}", package.name, package.version))
+            .collect::<Vec<_>>();
+        pypi_specs.sort();
+        if !pypi_specs.is_empty() {
+            environment_yaml
+                .dependencies
+                .push(MatchSpecOrSubSection::SubSection(
+                    String::from("pip"),
+                    pypi_specs,
+                ));
+        }
+
+        Ok(Some(environment_yaml))
+    }
+
     /// Returns the version of the lock-file that contained this environment.
     pub fn version(&self) -> FileFormatVersion {
         self.inner.version
@@ -665,6 +726,31 @@ mod test {
         insta::assert_yaml_snapshot!(file_name, conda_lock);
     }
 
+    #[test]
+    fn test_export_environment() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/conda-lock")
+            .join("v4/pypi-matplotlib-lock.yml");
+        let conda_lock = LockFile::from_path(&path).unwrap();
+        let environment = conda_lock.default_environment().unwrap();
+
+        insta::assert_snapshot!(environment
+            .to_explicit_environment_spec(Platform::Linux64)
+            .unwrap()
+            .unwrap()
+            .to_spec_string());
+        insta::assert_snapshot!(environment
+            .to_environment_yaml(Platform::Linux64)
+            .unwrap()
+            .unwrap()
+            .to_yaml_string());
+
+        assert!(environment
+            .to_explicit_environment_spec(Platform::EmscriptenWasm32)
+            .unwrap()
+            .is_none());
+    }
+
     /// Absolute paths on Windows are not properly parsed.
     /// See: <https://github.com/conda/rattler/issues/615>
     #[test]
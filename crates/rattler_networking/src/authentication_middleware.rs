@@ -1,18 +1,25 @@
 //! `reqwest` middleware that authenticates requests with data from the `AuthenticationStorage`
-use crate::{Authentication, AuthenticationStorage};
+use crate::authentication_storage::run_blocking;
+use crate::{oauth, Authentication, AuthenticationStorage};
 use async_trait::async_trait;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
-use reqwest::{Request, Response};
+use reqwest::{Request, Response, StatusCode};
 use reqwest_middleware::{Middleware, Next};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, OnceLock};
 use url::Url;
 
 /// `reqwest` middleware to authenticate requests
 #[derive(Clone, Default)]
 pub struct AuthenticationMiddleware {
     auth_storage: AuthenticationStorage,
+    /// The client used to renew OAuth access tokens
+    oauth_client: reqwest::Client,
+    /// Locks per host that ensure that concurrent requests renew the OAuth
+    /// credentials of a host only once
+    refresh_locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
 }
 
 #[async_trait]
@@ -29,7 +36,8 @@ impl Middleware for AuthenticationMiddleware {
         }
 
         let url = req.url().clone();
-        match self.auth_storage.get_by_url(url) {
+        let auth_storage = self.auth_storage.clone();
+        match run_blocking(move || auth_storage.get_by_url(url)).await {
             Err(_) => {
                 // Forward error to caller (invalid URL)
                 next.run(req, extensions).await
@@ -40,8 +48,39 @@ impl Middleware for AuthenticationMiddleware {
                 let mut req = req;
                 *req.url_mut() = url;
 
+                // Renew expired OAuth tokens before sending the request
+                let mut refreshed = false;
+                let auth = match auth {
+                    Some(credentials) if oauth::needs_refresh(&credentials) => {
+                        refreshed = true;
+                        Some(self.refresh(&req, credentials).await)
+                    }
+                    auth => auth,
+                };
+
+                // Keep a copy of the request to retry it with a renewed token
+                let retry = match &auth {
+                    Some(Authentication::OAuth {
+                        refresh_token: Some(_),
+                        ..
+                    }) if !refreshed => req.try_clone(),
+                    _ => None,
+                };
+
                 let req = Self::authenticate_request(req, &auth).await?;
-                next.run(req, extensions).await
+                let response = next.clone().run(req, extensions).await?;
+
+                match (retry, auth) {
+                    (Some(retry), Some(credentials))
+                        if response.status() == StatusCode::UNAUTHORIZED =>
+                    {
+                        tracing::debug!("the OAuth access token was rejected, refreshing it");
+                        let auth = Some(self.refresh(&retry, credentials).await);
+                        let retry = Self::authenticate_request(retry, &auth).await?;
+                        next.run(retry, extensions).await
+                    }
+                    _ => Ok(response),
+                }
             }
         }
     }
@@ -50,7 +89,74 @@ impl Middleware for AuthenticationMiddleware {
 impl AuthenticationMiddleware {
     /// Create a new authentication middleware with the given authentication storage
     pub fn new(auth_storage: AuthenticationStorage) -> Self {
-        Self { auth_storage }
+        Self {
+            auth_storage,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the client that is used to renew OAuth access tokens
+    pub fn with_oauth_client(self, oauth_client: reqwest::Client) -> Self {
+        Self {
+            oauth_client,
+            ..self
+        }
+    }
+
+    /// Renews the access token of OAuth credentials and stores the renewed
+    /// credentials. If renewing fails the given credentials are returned, the
+    /// server then rejects the request with a meaningful status.
+    ///
+    /// Only one request per host renews the credentials at a time. Requests
+    /// that waited for another request use the credentials it stored.
+    async fn refresh(&self, req: &Request, credentials: Authentication) -> Authentication {
+        let auth_storage = self.auth_storage.clone();
+        let url = req.url().clone();
+        let host = run_blocking(move || auth_storage.get_entry_by_url(&url))
+            .await
+            .map(|(host, _)| host);
+
+        let lock = host.as_ref().map(|host| {
+            self.refresh_locks
+                .lock()
+                .unwrap()
+                .entry(host.clone())
+                .or_default()
+                .clone()
+        });
+        let _guard = match &lock {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        };
+
+        // Another request may have renewed the credentials in the meantime
+        if let Some(host) = host.clone() {
+            let auth_storage = self.auth_storage.clone();
+            if let Ok(Some(stored)) = run_blocking(move || auth_storage.get(&host)).await {
+                if stored != credentials && !oauth::needs_refresh(&stored) {
+                    return stored;
+                }
+            }
+        }
+
+        match oauth::refresh(&self.oauth_client, &credentials).await {
+            Ok(renewed) => {
+                if let Some(host) = &host {
+                    let auth_storage = self.auth_storage.clone();
+                    let (key, credentials) = (host.clone(), renewed.clone());
+                    if let Err(e) =
+                        run_blocking(move || auth_storage.store(&key, &credentials)).await
+                    {
+                        tracing::warn!("failed to store renewed credentials for {host}: {e}");
+                    }
+                }
+                renewed
+            }
+            Err(e) => {
+                tracing::warn!("failed to refresh the OAuth access token: {e}");
+                credentials
+            }
+        }
     }
 
     /// Authenticate the given URL with the given authentication information
@@ -82,7 +188,11 @@ impl AuthenticationMiddleware {
     ) -> reqwest_middleware::Result<reqwest::Request> {
         if let Some(credentials) = auth {
             match credentials {
-                Authentication::BearerToken(token) => {
+                Authentication::BearerToken(token)
+                | Authentication::OAuth {
+                    access_token: token,
+                    ..
+                } => {
                     let bearer_auth = format!("Bearer {token}");
 
                     let mut header_value = reqwest::header::HeaderValue::from_str(&bearer_auth)
@@ -105,7 +215,7 @@ impl AuthenticationMiddleware {
                         .insert(reqwest::header::AUTHORIZATION, header_value);
                     Ok(req)
                 }
-                Authentication::CondaToken(_) => Ok(req),
+                Authentication::CondaToken(_) | Authentication::S3Credentials { .. } => Ok(req),
             }
         } else {
             Ok(req)
@@ -370,6 +480,125 @@ mod tests {
         Ok(())
     }
 
+    /// Starts a server that only accepts the access token `access-1`.
+    async fn serve_protected_channel() -> Url {
+        use axum::{http::HeaderMap, http::StatusCode, Router};
+        use std::future::IntoFuture;
+
+        let router = Router::new().fallback(|headers: HeaderMap| async move {
+            match headers.get(reqwest::header::AUTHORIZATION) {
+                Some(value) if value == "Bearer access-1" => StatusCode::OK,
+                _ => StatusCode::UNAUTHORIZED,
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router.into_make_service()).into_future());
+        format!("http://{address}/channel/noarch/repodata.json")
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_oauth_refresh() -> anyhow::Result<()> {
+        use crate::oauth::tests::{serve_provider, Provider};
+        use chrono::{DateTime, Duration, Utc};
+        use std::sync::atomic::Ordering;
+
+        for (access_token, expires_at) in [
+            // The access token has expired, it is renewed before the request
+            (
+                "access-0",
+                DateTime::<Utc>::from(std::time::SystemTime::now()) - Duration::minutes(1),
+            ),
+            // The server rejects the access token, it is renewed and the
+            // request retried
+            (
+                "revoked",
+                DateTime::<Utc>::from(std::time::SystemTime::now()) + Duration::hours(1),
+            ),
+        ] {
+            let provider = Arc::new(Provider::default());
+            let issuer = serve_provider(provider.clone()).await;
+            let channel = serve_protected_channel().await;
+
+            let tdir = tempdir()?;
+            let mut storage = AuthenticationStorage::new();
+            storage.add_backend(Arc::from(FileStorage::new(tdir.path().join("auth.json"))?));
+            storage.store(
+                channel.host_str().unwrap(),
+                &Authentication::OAuth {
+                    access_token: access_token.to_string(),
+                    refresh_token: Some("refresh-0".to_string()),
+                    expires_at: Some(expires_at),
+                    token_endpoint: issuer.join("realm/token")?,
+                    client_id: "rattler".to_string(),
+                },
+            )?;
+
+            let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::default())
+                .with(AuthenticationMiddleware::new(storage.clone()))
+                .build();
+            let response = client.get(channel.clone()).send().await?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            assert_eq!(provider.refreshes.load(Ordering::SeqCst), 1);
+
+            // The renewed credentials are stored and used for the next request
+            let Some(Authentication::OAuth { access_token, .. }) =
+                storage.get(channel.host_str().unwrap())?
+            else {
+                panic!("expected OAuth credentials");
+            };
+            assert_eq!(access_token, "access-1");
+            let response = client.get(channel).send().await?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            assert_eq!(provider.refreshes.load(Ordering::SeqCst), 1);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth_refresh_concurrent() -> anyhow::Result<()> {
+        use crate::oauth::tests::{serve_provider, Provider};
+        use chrono::{DateTime, Duration, Utc};
+        use std::sync::atomic::Ordering;
+
+        let provider = Arc::new(Provider::default());
+        let issuer = serve_provider(provider.clone()).await;
+        let channel = serve_protected_channel().await;
+
+        let tdir = tempdir()?;
+        let mut storage = AuthenticationStorage::new();
+        storage.add_backend(Arc::from(FileStorage::new(tdir.path().join("auth.json"))?));
+        storage.store(
+            channel.host_str().unwrap(),
+            &Authentication::OAuth {
+                access_token: "access-0".to_string(),
+                refresh_token: Some("refresh-0".to_string()),
+                expires_at: Some(
+                    DateTime::<Utc>::from(std::time::SystemTime::now()) - Duration::minutes(1),
+                ),
+                token_endpoint: issuer.join("realm/token")?,
+                client_id: "rattler".to_string(),
+            },
+        )?;
+
+        // Concurrent requests with expired credentials renew them only once
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::default())
+            .with(AuthenticationMiddleware::new(storage))
+            .build();
+        let (first, second) = tokio::join!(
+            client.get(channel.clone()).send(),
+            client.get(channel).send()
+        );
+        assert_eq!(first?.status(), reqwest::StatusCode::OK);
+        assert_eq!(second?.status(), reqwest::StatusCode::OK);
+        assert_eq!(provider.refreshes.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_host_wildcard_expansion() -> anyhow::Result<()> {
         for (host, should_succeed) in [
@@ -1,7 +1,9 @@
 //! Authentication methods for the conda ecosystem
 use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 /// The different Authentication methods that are supported in the conda
 /// ecosystem
@@ -19,6 +21,31 @@ pub enum Authentication {
     },
     /// A conda token is sent in the URL as `/t/{TOKEN}/...`
     CondaToken(String),
+    /// Credentials of an S3 compatible object storage that are used to sign
+    /// requests to `s3://` URLs
+    S3Credentials {
+        /// The access key ID
+        access_key_id: String,
+        /// The secret access key
+        secret_access_key: String,
+        /// The session token of temporary credentials
+        session_token: Option<String>,
+    },
+    /// Tokens issued by an OAuth 2.0 / OIDC provider. The access token
+    /// is sent as a bearer token and renewed with the refresh token when it
+    /// expires, see [`crate::oauth`].
+    OAuth {
+        /// The access token that is sent with requests
+        access_token: String,
+        /// The refresh token used to obtain a new access token
+        refresh_token: Option<String>,
+        /// The time at which the access token expires
+        expires_at: Option<DateTime<Utc>>,
+        /// The token endpoint of the provider
+        token_endpoint: Url,
+        /// The client ID the tokens were issued to
+        client_id: String,
+    },
 }
 
 /// An error that can occur when parsing an authentication string
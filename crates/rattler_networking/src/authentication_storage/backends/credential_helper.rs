@@ -0,0 +1,451 @@
+//! Backend that retrieves credentials from an external credential helper.
+//!
+//! The helper speaks the protocol of git credential helpers: it is invoked with
+//! `get`, `store` or `erase` as its last argument and receives the request as
+//! `key=value` lines on stdin, terminated by an empty line. For `get` it prints
+//! the credentials in the same format on stdout. The following keys are used:
+//!
+//! * `protocol` and `host` identify the credentials, the host can be a
+//!   wildcard like `*.prefix.dev`.
+//! * `username` and `password` describe basic HTTP credentials.
+//! * `authtype=Bearer` together with `credential` describes a bearer token.
+//! * `authentication` contains any credentials in the JSON format of
+//!   [`Authentication`], e.g. `{"CondaToken":"..."}`.
+//!
+//! Unknown keys in the output of a helper are ignored.
+//!
+//! Hosts for which the helper returned no credentials are remembered for a
+//! short time, so the helper is not invoked again for every request to such a
+//! host while credentials that are added later are still picked up.
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io::Write,
+    process::{Command, ExitStatus, Stdio},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{authentication_storage::StorageBackend, Authentication};
+
+/// How long the backend remembers that the helper has no credentials for a
+/// host.
+pub const DEFAULT_MISS_TTL: Duration = Duration::from_secs(60);
+
+/// An error that can occur when invoking a credential helper
+#[derive(thiserror::Error, Debug)]
+pub enum CredentialHelperError {
+    /// The helper could not be executed
+    #[error("failed to run credential helper `{program}`")]
+    Spawn {
+        /// The program that was invoked
+        program: String,
+        /// The underlying IO error
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The helper exited with a non-zero status
+    #[error("credential helper `{program}` failed with {status}")]
+    Failed {
+        /// The program that was invoked
+        program: String,
+        /// The exit status of the helper
+        status: ExitStatus,
+    },
+
+    /// A value contains a newline or NUL character and cannot be sent
+    #[error("the value of `{0}` contains a newline or NUL character")]
+    InvalidValue(String),
+
+    /// The output of the helper could not be interpreted
+    #[error("invalid output of credential helper: {0}")]
+    InvalidResponse(String),
+}
+
+/// A storage backend that calls an external credential helper executable,
+/// e.g. to retrieve credentials from a secrets vault without writing them to
+/// disk.
+#[derive(Debug, Clone)]
+pub struct CredentialHelperStorage {
+    program: OsString,
+    args: Vec<OsString>,
+    /// Hosts for which the helper had no credentials and when that was
+    /// determined
+    misses: Arc<Mutex<HashMap<String, Instant>>>,
+    miss_ttl: Duration,
+}
+
+impl CredentialHelperStorage {
+    /// Create a new backend that invokes the given program. The operation
+    /// (`get`, `store` or `erase`) is appended to the given arguments.
+    pub fn new(
+        program: impl Into<OsString>,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            misses: Arc::default(),
+            miss_ttl: DEFAULT_MISS_TTL,
+        }
+    }
+
+    /// Sets how long the backend remembers that the helper has no
+    /// credentials for a host, [`DEFAULT_MISS_TTL`] by default.
+    #[must_use]
+    pub fn with_miss_ttl(self, miss_ttl: Duration) -> Self {
+        Self { miss_ttl, ..self }
+    }
+
+    /// Create a new backend from the `RATTLER_CREDENTIAL_HELPER` environment
+    /// variable which contains the command to invoke, e.g.
+    /// `vault-helper --mount conda`. The command is split on whitespace.
+    /// Returns `None` if the variable is not set or empty.
+    pub fn from_env() -> Option<Self> {
+        let command = std::env::var_os("RATTLER_CREDENTIAL_HELPER")?;
+        let command = command.to_string_lossy();
+        let mut parts = command.split_whitespace();
+        let program = parts.next()?;
+        Some(Self::new(program, parts))
+    }
+
+    /// Returns true if the helper recently had no credentials for the host.
+    fn is_recent_miss(&self, host: &str) -> bool {
+        self.misses
+            .lock()
+            .unwrap()
+            .get(host)
+            .is_some_and(|at| at.elapsed() < self.miss_ttl)
+    }
+
+    /// Remembers that the helper has no credentials for the host and forgets
+    /// the misses that expired.
+    fn add_miss(&self, host: &str) {
+        let mut misses = self.misses.lock().unwrap();
+        misses.retain(|_, at| at.elapsed() < self.miss_ttl);
+        misses.insert(host.to_string(), Instant::now());
+    }
+
+    /// Invokes the helper with the given operation and request and returns
+    /// its output.
+    fn invoke(
+        &self,
+        operation: &str,
+        request: &[(&str, &str)],
+    ) -> Result<String, CredentialHelperError> {
+        let mut input = String::new();
+        for (key, value) in request {
+            if value.contains(['\n', '\0']) {
+                return Err(CredentialHelperError::InvalidValue((*key).to_string()));
+            }
+            input.push_str(&format!("{key}={value}\n"));
+        }
+        input.push('\n');
+
+        let program = self.program.to_string_lossy().into_owned();
+        let spawn_error = |source| CredentialHelperError::Spawn {
+            program: program.clone(),
+            source,
+        };
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg(operation)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(spawn_error)?;
+
+        // The helper may exit without reading its input, so a broken pipe is
+        // not an error by itself.
+        if let Some(mut stdin) = child.stdin.take() {
+            match stdin.write_all(input.as_bytes()) {
+                Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+                    return Err(spawn_error(e));
+                }
+                _ => {}
+            }
+        }
+
+        let output = child.wait_with_output().map_err(spawn_error)?;
+        if !output.status.success() {
+            return Err(CredentialHelperError::Failed {
+                program,
+                status: output.status,
+            });
+        }
+
+        String::from_utf8(output.stdout)
+            .map_err(|_err| CredentialHelperError::InvalidResponse("output is not UTF-8".into()))
+    }
+}
+
+/// Parses the `key=value` lines written by a helper. Parsing stops at the
+/// first empty line.
+fn parse_response(output: &str) -> HashMap<&str, &str> {
+    output
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+/// Converts the response of a helper to credentials.
+fn response_to_authentication(
+    response: &HashMap<&str, &str>,
+) -> Result<Option<Authentication>, CredentialHelperError> {
+    if let Some(authentication) = response.get("authentication") {
+        return Authentication::from_str(authentication)
+            .map(Some)
+            .map_err(|_err| {
+                CredentialHelperError::InvalidResponse(
+                    "`authentication` does not contain valid credentials".into(),
+                )
+            });
+    }
+
+    if let (Some(authtype), Some(credential)) =
+        (response.get("authtype"), response.get("credential"))
+    {
+        return if authtype.eq_ignore_ascii_case("bearer") {
+            Ok(Some(Authentication::BearerToken((*credential).to_string())))
+        } else {
+            Err(CredentialHelperError::InvalidResponse(format!(
+                "unsupported authtype `{authtype}`"
+            )))
+        };
+    }
+
+    match (response.get("username"), response.get("password")) {
+        (username, Some(password)) => Ok(Some(Authentication::BasicHTTP {
+            username: username.copied().unwrap_or_default().to_string(),
+            password: (*password).to_string(),
+        })),
+        _ => Ok(None),
+    }
+}
+
+impl StorageBackend for CredentialHelperStorage {
+    fn store(&self, host: &str, authentication: &Authentication) -> Result<()> {
+        let mut request = vec![("protocol", "https"), ("host", host)];
+        let json;
+        match authentication {
+            Authentication::BasicHTTP { username, password } => {
+                request.push(("username", username));
+                request.push(("password", password));
+            }
+            Authentication::BearerToken(token) => {
+                request.push(("authtype", "Bearer"));
+                request.push(("credential", token));
+            }
+            _ => {
+                json = serde_json::to_string(authentication)?;
+                request.push(("authentication", &json));
+            }
+        }
+
+        self.invoke("store", &request)?;
+        self.misses.lock().unwrap().remove(host);
+        Ok(())
+    }
+
+    fn get(&self, host: &str) -> Result<Option<Authentication>> {
+        if self.is_recent_miss(host) {
+            return Ok(None);
+        }
+
+        let output = self.invoke("get", &[("protocol", "https"), ("host", host)])?;
+        let authentication = response_to_authentication(&parse_response(&output))?;
+        if authentication.is_none() {
+            self.add_miss(host);
+        }
+        Ok(authentication)
+    }
+
+    fn delete(&self, host: &str) -> Result<()> {
+        self.invoke("erase", &[("protocol", "https"), ("host", host)])?;
+        self.add_miss(host);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(
+            "protocol=https\nhost=repo.prefix.dev\nusername=user\npassword=a=b\n\nignored=1\n",
+        );
+        assert_eq!(
+            response_to_authentication(&response).unwrap(),
+            Some(Authentication::BasicHTTP {
+                username: "user".to_string(),
+                password: "a=b".to_string(),
+            })
+        );
+
+        let response = parse_response("authtype=Bearer\ncredential=token\n");
+        assert_eq!(
+            response_to_authentication(&response).unwrap(),
+            Some(Authentication::BearerToken("token".to_string()))
+        );
+
+        let response = parse_response("authentication={\"CondaToken\":\"token\"}\n");
+        assert_eq!(
+            response_to_authentication(&response).unwrap(),
+            Some(Authentication::CondaToken("token".to_string()))
+        );
+
+        assert_eq!(
+            response_to_authentication(&parse_response("")).unwrap(),
+            None
+        );
+        assert!(
+            response_to_authentication(&parse_response("authtype=Digest\ncredential=x\n")).is_err()
+        );
+    }
+
+    /// A helper that keeps the requests it receives for `store` in a
+    /// directory and replays them for `get`.
+    #[cfg(unix)]
+    fn directory_helper(dir: &std::path::Path) -> CredentialHelperStorage {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = dir.join("helper.sh");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+input=$(sed '/^$/q')
+host=$(printf '%s\n' "$input" | sed -n 's/^host=//p')
+case "$1" in
+  get) [ -f "$STORE/$host" ] && cat "$STORE/$host" ;;
+  store) printf '%s\n' "$input" > "$STORE/$host" ;;
+  erase) rm -f "$STORE/$host" ;;
+esac
+exit 0
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        CredentialHelperStorage::new(
+            "env",
+            [
+                format!("STORE={}", dir.display()),
+                script.display().to_string(),
+            ],
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credential_helper_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = directory_helper(dir.path());
+
+        assert_eq!(storage.get("repo.prefix.dev").unwrap(), None);
+
+        for authentication in [
+            Authentication::BearerToken("token".to_string()),
+            Authentication::BasicHTTP {
+                username: "user".to_string(),
+                password: "password".to_string(),
+            },
+            Authentication::CondaToken("token".to_string()),
+        ] {
+            storage.store("*.prefix.dev", &authentication).unwrap();
+            assert_eq!(storage.get("*.prefix.dev").unwrap(), Some(authentication));
+        }
+
+        storage.delete("*.prefix.dev").unwrap();
+        assert_eq!(storage.get("*.prefix.dev").unwrap(), None);
+
+        // Values are sent line by line, so they cannot contain newlines
+        assert!(storage
+            .store(
+                "repo.prefix.dev",
+                &Authentication::BearerToken("a\nb".to_string())
+            )
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credential_helper_caches_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = directory_helper(dir.path());
+
+        // Credentials written by another process are not seen once the host
+        // is known to have none
+        assert_eq!(storage.get("repo.prefix.dev").unwrap(), None);
+        let authentication = Authentication::BearerToken("token".to_string());
+        directory_helper(dir.path())
+            .store("repo.prefix.dev", &authentication)
+            .unwrap();
+        assert_eq!(storage.get("repo.prefix.dev").unwrap(), None);
+
+        // Storing through the backend itself forgets the miss
+        storage.store("repo.prefix.dev", &authentication).unwrap();
+        assert_eq!(
+            storage.get("repo.prefix.dev").unwrap(),
+            Some(authentication)
+        );
+
+        // Clones share the cached misses
+        storage.delete("repo.prefix.dev").unwrap();
+        std::fs::remove_file(dir.path().join("helper.sh")).unwrap();
+        assert_eq!(storage.clone().get("repo.prefix.dev").unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credential_helper_misses_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = directory_helper(dir.path()).with_miss_ttl(Duration::from_millis(100));
+
+        assert_eq!(storage.get("repo.prefix.dev").unwrap(), None);
+        let authentication = Authentication::BearerToken("token".to_string());
+        directory_helper(dir.path())
+            .store("repo.prefix.dev", &authentication)
+            .unwrap();
+        assert_eq!(storage.get("repo.prefix.dev").unwrap(), None);
+
+        // Credentials that were added in the meantime are found once the miss
+        // expired.
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(
+            storage.get("repo.prefix.dev").unwrap(),
+            Some(authentication)
+        );
+
+        // Expired misses are forgotten when another miss is remembered.
+        assert_eq!(storage.get("other.prefix.dev").unwrap(), None);
+        assert_eq!(storage.misses.lock().unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credential_helper_failure() {
+        let storage = CredentialHelperStorage::new("false", Vec::<String>::new());
+        let err = storage.get("repo.prefix.dev").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CredentialHelperError>(),
+            Some(CredentialHelperError::Failed { .. })
+        ));
+
+        let storage = CredentialHelperStorage::new("/does/not/exist", Vec::<String>::new());
+        let err = storage.get("repo.prefix.dev").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CredentialHelperError>(),
+            Some(CredentialHelperError::Spawn { .. })
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 //! Multiple backends for storing authentication data.
 
+pub mod credential_helper;
 pub mod file;
 pub mod keyring;
 
@@ -17,3 +17,22 @@ pub trait StorageBackend: std::fmt::Debug {
     /// Delete the authentication information for the given host
     fn delete(&self, host: &str) -> Result<()>;
 }
+
+/// Runs a call into the storage backends, which may spawn processes or access
+/// the keyring, on the blocking thread pool of the current tokio runtime so it
+/// does not stall other requests. Without a runtime the call runs in place.
+pub(crate) async fn run_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return match handle.spawn_blocking(f).await {
+            Ok(result) => result,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        };
+    }
+
+    f()
+}
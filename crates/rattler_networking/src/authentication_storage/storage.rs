@@ -10,7 +10,10 @@ use url::Url;
 
 use super::{
     authentication::Authentication,
-    backends::{file::FileStorage, keyring::KeyringAuthenticationStorage, netrc::NetRcStorage},
+    backends::{
+        credential_helper::CredentialHelperStorage, file::FileStorage,
+        keyring::KeyringAuthenticationStorage, netrc::NetRcStorage,
+    },
     StorageBackend,
 };
 
@@ -29,6 +32,10 @@ impl Default for AuthenticationStorage {
     fn default() -> Self {
         let mut storage = Self::new();
 
+        // An explicitly configured credential helper takes precedence
+        if let Some(helper) = CredentialHelperStorage::from_env() {
+            storage.add_backend(Arc::from(helper));
+        }
         storage.add_backend(Arc::from(KeyringAuthenticationStorage::default()));
         storage.add_backend(Arc::from(FileStorage::default()));
         storage.add_backend(Arc::from(NetRcStorage::from_env().unwrap_or_else(
@@ -149,40 +156,37 @@ impl AuthenticationStorage {
         url: U,
     ) -> Result<(Url, Option<Authentication>), reqwest::Error> {
         let url = url.into_url()?;
-        let Some(host) = url.host_str() else {
-            return Ok((url, None));
-        };
+        let credentials = self
+            .get_entry_by_url(&url)
+            .map(|(_, credentials)| credentials);
+        Ok((url, credentials))
+    }
+
+    /// Retrieve the authentication information for the given URL together
+    /// with the host it is stored under, which is either the host of the URL
+    /// or a wildcard host like `*.prefix.dev`.
+    pub(crate) fn get_entry_by_url(&self, url: &Url) -> Option<(String, Authentication)> {
+        let host = url.host_str()?;
 
         match self.get(host) {
             Ok(None) => {}
-            Err(_) => return Ok((url, None)),
-            Ok(Some(credentials)) => return Ok((url, Some(credentials))),
+            Err(_) => return None,
+            Ok(Some(credentials)) => return Some((host.to_string(), credentials)),
         };
 
         // Check for credentials under e.g. `*.prefix.dev`
-        let Some(mut domain) = url.domain() else {
-            return Ok((url, None));
-        };
+        let mut domain = url.domain()?;
 
         loop {
             let wildcard_host = format!("*.{domain}");
 
-            let Ok(credentials) = self.get(&wildcard_host) else {
-                return Ok((url, None));
-            };
-
-            if let Some(credentials) = credentials {
-                return Ok((url, Some(credentials)));
+            if let Some(credentials) = self.get(&wildcard_host).ok()? {
+                return Some((wildcard_host, credentials));
             }
 
-            let possible_rest = domain.split_once('.').map(|(_, rest)| rest);
-
-            match possible_rest {
-                Some(rest) => {
-                    domain = rest;
-                }
-                _ => return Ok((url, None)), // No more subdomains to check
-            }
+            // No more subdomains to check
+            let (_, rest) = domain.split_once('.')?;
+            domain = rest;
         }
     }
 
@@ -4,7 +4,8 @@
 pub use authentication_middleware::AuthenticationMiddleware;
 pub use authentication_storage::{authentication::Authentication, storage::AuthenticationStorage};
 pub use mirror_middleware::MirrorMiddleware;
-pub use oci_middleware::OciMiddleware;
+pub use oci_middleware::{OciMiddleware, OciUploader};
+pub use s3_middleware::S3Middleware;
 
 #[cfg(feature = "google-cloud-auth")]
 pub mod gcs_middleware;
@@ -15,5 +16,7 @@ pub mod authentication_middleware;
 pub mod authentication_storage;
 
 pub mod mirror_middleware;
+pub mod oauth;
 pub mod oci_middleware;
 pub mod retry_policies;
+pub mod s3_middleware;
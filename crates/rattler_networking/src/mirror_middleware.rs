@@ -1,7 +1,11 @@
 //! Middleware to handle mirrors
 use std::{
     collections::HashMap,
-    sync::atomic::{self, AtomicUsize},
+    sync::{
+        atomic::{self, AtomicUsize},
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use http::{Extensions, StatusCode};
@@ -10,6 +14,10 @@ use reqwest::{Request, Response, ResponseBuilderExt};
 use reqwest_middleware::{Middleware, Next, Result};
 use url::Url;
 
+/// The default time after which a mirror that exceeded its maximum number of
+/// failures is tried again.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Settings for the specific mirror (e.g. no zstd or bz2 support)
 pub struct Mirror {
@@ -21,42 +29,135 @@ pub struct Mirror {
     pub no_bz2: bool,
     /// Disable jlap support (for repodata.jlap files)
     pub no_jlap: bool,
-    /// Allowed number of failures before the mirror is considered dead
+    /// Allowed number of consecutive failures before the mirror is considered
+    /// dead until the cooldown has passed
     pub max_failures: Option<usize>,
 }
 
-#[allow(dead_code)]
+impl Mirror {
+    /// Returns the reason why the mirror cannot serve the file, if any.
+    fn unsupported_reason(&self, path: &str) -> Option<&'static str> {
+        if path.ends_with(".json.zst") && self.no_zstd {
+            Some("Mirror does not support zstd")
+        } else if path.ends_with(".json.bz2") && self.no_bz2 {
+            Some("Mirror does not support bz2")
+        } else if path.ends_with(".jlap") && self.no_jlap {
+            Some("Mirror does not support jlap")
+        } else {
+            None
+        }
+    }
+}
+
+/// How the [`MirrorMiddleware`] chooses between the mirrors of a URL.
+///
+/// Regardless of the strategy, mirrors that are considered dead are skipped
+/// and a request that fails on one mirror is retried on the next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MirrorStrategy {
+    /// Use the mirrors in the order in which they are specified, only falling
+    /// back to the next mirror if a mirror fails. Until the cooldown has
+    /// passed, mirrors that failed are tried after the mirrors with fewer
+    /// consecutive failures, even if they have no maximum number of failures.
+    #[default]
+    Failover,
+
+    /// Spread the requests evenly over all mirrors.
+    RoundRobin,
+
+    /// Prefer the mirror that responded the fastest so far. Mirrors that have
+    /// not been used yet are tried first to measure their latency.
+    LowestLatency,
+}
+
 struct MirrorState {
-    failures: AtomicUsize,
     mirror: Mirror,
+    /// The number of consecutive failures
+    failures: AtomicUsize,
+    last_failure: Mutex<Option<Instant>>,
+    /// A moving average of the time it took the mirror to respond
+    latency: Mutex<Option<Duration>>,
 }
 
 impl MirrorState {
+    fn new(mirror: Mirror) -> Self {
+        Self {
+            mirror,
+            failures: AtomicUsize::new(0),
+            last_failure: Mutex::new(None),
+            latency: Mutex::new(None),
+        }
+    }
+
     pub fn add_failure(&self) {
         self.failures.fetch_add(1, atomic::Ordering::Relaxed);
+        *self.last_failure.lock().unwrap() = Some(Instant::now());
     }
+
+    fn add_success(&self, latency: Duration) {
+        self.failures.store(0, atomic::Ordering::Relaxed);
+        let mut average = self.latency.lock().unwrap();
+        *average = Some(match *average {
+            Some(average) => (average * 3 + latency) / 4,
+            None => latency,
+        });
+    }
+
+    /// Returns true if the mirror has not exceeded its maximum number of
+    /// failures, or if the last failure was longer than `cooldown` ago.
+    fn is_available(&self, cooldown: Duration) -> bool {
+        let failures = self.failures.load(atomic::Ordering::Relaxed);
+        if self.mirror.max_failures.map_or(true, |max| failures < max) {
+            return true;
+        }
+        self.cooled_down(cooldown)
+    }
+
+    /// Returns the number of consecutive failures, or zero if the last failure
+    /// was longer than `cooldown` ago.
+    fn recent_failures(&self, cooldown: Duration) -> usize {
+        if self.cooled_down(cooldown) {
+            0
+        } else {
+            self.failures.load(atomic::Ordering::Relaxed)
+        }
+    }
+
+    fn cooled_down(&self, cooldown: Duration) -> bool {
+        self.last_failure
+            .lock()
+            .unwrap()
+            .map_or(true, |last_failure| last_failure.elapsed() >= cooldown)
+    }
+}
+
+/// The mirrors of a single URL
+struct MirrorGroup {
+    mirrors: Vec<MirrorState>,
+    /// The index of the first mirror for the next request with the
+    /// round-robin strategy
+    next: AtomicUsize,
 }
 
 /// Middleware to handle mirrors
 pub struct MirrorMiddleware {
-    mirror_map: HashMap<Url, Vec<MirrorState>>,
+    mirror_map: HashMap<Url, MirrorGroup>,
     sorted_keys: Vec<(String, Url)>,
+    strategy: MirrorStrategy,
+    cooldown: Duration,
 }
 
 impl MirrorMiddleware {
     /// Create a new `MirrorMiddleware` from a map of mirrors
     pub fn from_map(mirror_map: HashMap<Url, Vec<Mirror>>) -> Self {
-        let mirror_map: HashMap<Url, Vec<MirrorState>> = mirror_map
+        let mirror_map: HashMap<Url, MirrorGroup> = mirror_map
             .into_iter()
             .map(|(url, mirrors)| {
-                let mirrors = mirrors
-                    .into_iter()
-                    .map(|mirror| MirrorState {
-                        failures: AtomicUsize::new(0),
-                        mirror,
-                    })
-                    .collect();
-                (url, mirrors)
+                let group = MirrorGroup {
+                    mirrors: mirrors.into_iter().map(MirrorState::new).collect(),
+                    next: AtomicUsize::new(0),
+                };
+                (url, group)
             })
             .collect();
 
@@ -70,43 +171,62 @@ impl MirrorMiddleware {
         Self {
             mirror_map,
             sorted_keys,
+            strategy: MirrorStrategy::default(),
+            cooldown: DEFAULT_COOLDOWN,
         }
     }
 
+    /// Sets the strategy that is used to choose between mirrors
+    pub fn with_strategy(self, strategy: MirrorStrategy) -> Self {
+        Self { strategy, ..self }
+    }
+
+    /// Sets the time after which a mirror that exceeded its maximum number of
+    /// failures is tried again. Defaults to one minute.
+    pub fn with_cooldown(self, cooldown: Duration) -> Self {
+        Self { cooldown, ..self }
+    }
+
     /// Get sorted keys. The keys are sorted by length of the path,
     /// so the longest path comes first.
     pub fn keys(&self) -> &[(String, Url)] {
         &self.sorted_keys
     }
-}
 
-fn select_mirror(mirrors: &[MirrorState]) -> Option<&MirrorState> {
-    let mut min_failures = usize::MAX;
-    let mut min_failures_index = usize::MAX;
-
-    for (i, mirror) in mirrors.iter().enumerate() {
-        let failures = mirror.failures.load(atomic::Ordering::Relaxed);
-        if failures < min_failures
-            && mirror
-                .mirror
-                .max_failures
-                .map_or(true, |max| failures < max)
-        {
-            min_failures = failures;
-            min_failures_index = i;
+    /// Returns the available mirrors of the group in the order in which they
+    /// should be tried.
+    fn select_mirrors<'a>(&self, group: &'a MirrorGroup) -> Vec<&'a MirrorState> {
+        let available = group
+            .mirrors
+            .iter()
+            .filter(|mirror| mirror.is_available(self.cooldown));
+        match self.strategy {
+            // The sort is stable, so mirrors with the same number of failures keep
+            // their configured order.
+            MirrorStrategy::Failover => available
+                .sorted_by_key(|mirror| mirror.recent_failures(self.cooldown))
+                .collect(),
+            MirrorStrategy::RoundRobin => {
+                let mut available = available.collect::<Vec<_>>();
+                if !available.is_empty() {
+                    let start = group.next.fetch_add(1, atomic::Ordering::Relaxed);
+                    let len = available.len();
+                    available.rotate_left(start % len);
+                }
+                available
+            }
+            MirrorStrategy::LowestLatency => available
+                .sorted_by_key(|mirror| *mirror.latency.lock().unwrap())
+                .collect(),
         }
     }
-    if min_failures_index == usize::MAX {
-        return None;
-    }
-    Some(&mirrors[min_failures_index])
 }
 
 #[async_trait::async_trait]
 impl Middleware for MirrorMiddleware {
     async fn handle(
         &self,
-        mut req: Request,
+        req: Request,
         extensions: &mut Extensions,
         next: Next<'_>,
     ) -> Result<Response> {
@@ -115,48 +235,60 @@ impl Middleware for MirrorMiddleware {
         for (key, url) in self.keys() {
             if let Some(url_rest) = url_str.strip_prefix(key) {
                 let url_rest = url_rest.trim_start_matches('/');
-                // replace the key with the mirror
-                let mirrors = self.mirror_map.get(url).unwrap();
-                let selected_mirror = select_mirror(mirrors);
+                let group = self.mirror_map.get(url).unwrap();
+                let mirrors = self.select_mirrors(group);
 
-                let Some(selected_mirror) = selected_mirror else {
+                let Some(first_mirror) = mirrors.first() else {
                     return Ok(create_404_response(req.url(), "All mirrors are dead"));
                 };
 
-                let mirror = &selected_mirror.mirror;
-                let selected_url = mirror.url.join(url_rest).unwrap();
-
-                // Short-circuit if the mirror does not support the file type
-                if url_rest.ends_with(".json.zst") && mirror.no_zstd {
-                    return Ok(create_404_response(
-                        &selected_url,
-                        "Mirror does not support zstd",
-                    ));
-                }
-                if url_rest.ends_with(".json.bz2") && mirror.no_bz2 {
-                    return Ok(create_404_response(
-                        &selected_url,
-                        "Mirror does not support bz2",
-                    ));
-                }
-                if url_rest.ends_with(".jlap") && mirror.no_jlap {
+                // Short-circuit if none of the mirrors support the file type
+                let mirrors = mirrors
+                    .iter()
+                    .filter(|state| state.mirror.unsupported_reason(url_rest).is_none())
+                    .collect::<Vec<_>>();
+                if mirrors.is_empty() {
+                    let mirror = &first_mirror.mirror;
                     return Ok(create_404_response(
-                        &selected_url,
-                        "Mirror does not support jlap",
+                        &mirror.url.join(url_rest).unwrap(),
+                        mirror.unsupported_reason(url_rest).unwrap_or_default(),
                     ));
                 }
 
-                *req.url_mut() = selected_url;
-                let res = next.run(req, extensions).await;
-
-                // record a failure if the request failed so we can avoid the mirror in the future
-                match res.as_ref() {
-                    Ok(res) if res.status().is_server_error() => selected_mirror.add_failure(),
-                    Err(_) => selected_mirror.add_failure(),
-                    _ => {}
+                // Try the mirrors in order until one of them succeeds. The
+                // request can only be retried if its body can be cloned.
+                let mut req = Some(req);
+                let mut mirrors = mirrors.into_iter().peekable();
+                while let (Some(mirror), Some(current)) = (mirrors.next(), req.take()) {
+                    if mirrors.peek().is_some() {
+                        req = current.try_clone();
+                    }
+
+                    let mut current = current;
+                    *current.url_mut() = mirror.mirror.url.join(url_rest).unwrap();
+                    let start = Instant::now();
+                    let res = next.clone().run(current, extensions).await;
+
+                    // record a failure if the request failed so we can avoid the mirror in the future
+                    let failed = match res.as_ref() {
+                        Ok(res) => res.status().is_server_error(),
+                        Err(_) => true,
+                    };
+                    if !failed {
+                        mirror.add_success(start.elapsed());
+                        return res;
+                    }
+                    mirror.add_failure();
+
+                    if req.is_none() {
+                        return res;
+                    }
+                    tracing::debug!(
+                        "mirror {} failed, trying the next mirror",
+                        mirror.mirror.url
+                    );
                 }
-
-                return res;
+                unreachable!("there is at least one mirror to try");
             }
         }
 
@@ -177,7 +309,15 @@ pub(crate) fn create_404_response(url: &Url, body: &str) -> Response {
 
 #[cfg(test)]
 mod test {
-    use std::{future::IntoFuture, net::SocketAddr};
+    use std::{
+        future::IntoFuture,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
 
     use axum::{extract::State, http::StatusCode, routing::get, Router};
     use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
@@ -185,7 +325,7 @@ mod test {
 
     use crate::MirrorMiddleware;
 
-    use super::Mirror;
+    use super::{Mirror, MirrorStrategy};
 
     async fn count(State(name): State<String>) -> String {
         format!("Hi from counter: {name}")
@@ -195,6 +335,53 @@ mod test {
         StatusCode::INTERNAL_SERVER_ERROR
     }
 
+    async fn slow_count(State(name): State<String>) -> String {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        format!("Hi from counter: {name}")
+    }
+
+    async fn flaky_count(State(broken): State<Arc<AtomicBool>>) -> (StatusCode, String) {
+        if broken.load(Ordering::SeqCst) {
+            (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+        } else {
+            (
+                StatusCode::OK,
+                String::from("Hi from counter: flaky server"),
+            )
+        }
+    }
+
+    async fn serve(router: Router) -> Url {
+        let addr = SocketAddr::new([127, 0, 0, 1].into(), 0);
+        let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(axum::serve(listener, router.into_make_service()).into_future());
+        format!("http://{}:{}", addr.ip(), addr.port())
+            .parse()
+            .unwrap()
+    }
+
+    async fn get_count(client: &reqwest_middleware::ClientWithMiddleware) -> String {
+        let res = client.get("http://bla.com/count").send().await.unwrap();
+        assert!(res.status().is_success());
+        res.text().await.unwrap()
+    }
+
+    fn client_with_mirrors(
+        mirrors: Vec<Mirror>,
+        strategy: MirrorStrategy,
+    ) -> reqwest_middleware::ClientWithMiddleware {
+        let mirror_map =
+            std::collections::HashMap::from([("http://bla.com".parse().unwrap(), mirrors)]);
+        let middleware = MirrorMiddleware::from_map(mirror_map)
+            .with_strategy(strategy)
+            .with_cooldown(Duration::from_millis(100));
+        reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(middleware)
+            .build()
+    }
+
     async fn test_server(name: &str, broken: bool) -> Url {
         let state = String::from(name);
 
@@ -268,9 +455,10 @@ mod test {
             .with(middleware)
             .build();
 
+        // the request fails on the first server and is retried on the second one
         let res = client.get("http://bla.com/count").send().await.unwrap();
-        assert!(res.status().is_server_error());
-        // only the second server should be used
+        assert!(res.status().is_success());
+        assert!(res.text().await.unwrap() == "Hi from counter: server 2");
         let res = client.get("http://bla.com/count").send().await.unwrap();
         assert!(res.status().is_success());
         assert!(res.text().await.unwrap() == "Hi from counter: server 2");
@@ -290,6 +478,117 @@ mod test {
         assert!(res.text().await.unwrap() == "Hi from counter: server 2");
     }
 
+    #[tokio::test]
+    async fn test_mirror_middleware_all_broken() {
+        let addr_1 = test_server("server 1", true).await;
+        let addr_2 = test_server("server 2", true).await;
+
+        let client = client_with_mirrors(
+            vec![mirror_setting(addr_1), mirror_setting(addr_2)],
+            MirrorStrategy::Failover,
+        );
+
+        // the response of the last mirror is returned
+        let res = client.get("http://bla.com/count").send().await.unwrap();
+        assert!(res.status().is_server_error());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_round_robin() {
+        let addr_1 = test_server("server 1", false).await;
+        let addr_2 = test_server("server 2", false).await;
+
+        let client = client_with_mirrors(
+            vec![mirror_setting(addr_1), mirror_setting(addr_2)],
+            MirrorStrategy::RoundRobin,
+        );
+
+        assert_eq!(get_count(&client).await, "Hi from counter: server 1");
+        assert_eq!(get_count(&client).await, "Hi from counter: server 2");
+        assert_eq!(get_count(&client).await, "Hi from counter: server 1");
+    }
+
+    #[tokio::test]
+    async fn test_mirror_lowest_latency() {
+        let slow = serve(
+            Router::new()
+                .route("/count", get(slow_count))
+                .with_state(String::from("slow server")),
+        )
+        .await;
+        let fast = test_server("fast server", false).await;
+
+        let client = client_with_mirrors(
+            vec![mirror_setting(slow), mirror_setting(fast)],
+            MirrorStrategy::LowestLatency,
+        );
+
+        // Both mirrors are tried once to measure their latency.
+        assert_eq!(get_count(&client).await, "Hi from counter: slow server");
+        assert_eq!(get_count(&client).await, "Hi from counter: fast server");
+
+        // After that the fastest mirror is preferred.
+        assert_eq!(get_count(&client).await, "Hi from counter: fast server");
+        assert_eq!(get_count(&client).await, "Hi from counter: fast server");
+    }
+
+    #[tokio::test]
+    async fn test_mirror_cooldown() {
+        let broken = Arc::new(AtomicBool::new(true));
+        let flaky = serve(
+            Router::new()
+                .route("/count", get(flaky_count))
+                .with_state(broken.clone()),
+        )
+        .await;
+        let fallback = test_server("fallback server", false).await;
+
+        let mut flaky_mirror = mirror_setting(flaky);
+        flaky_mirror.max_failures = Some(1);
+        let client = client_with_mirrors(
+            vec![flaky_mirror, mirror_setting(fallback)],
+            MirrorStrategy::Failover,
+        );
+
+        assert_eq!(get_count(&client).await, "Hi from counter: fallback server");
+
+        // The flaky mirror is dead, so it is not used even though it recovered.
+        broken.store(false, Ordering::SeqCst);
+        assert_eq!(get_count(&client).await, "Hi from counter: fallback server");
+
+        // After the cooldown the mirror is used again.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(get_count(&client).await, "Hi from counter: flaky server");
+        assert_eq!(get_count(&client).await, "Hi from counter: flaky server");
+    }
+
+    #[tokio::test]
+    async fn test_mirror_failover_order() {
+        let broken = Arc::new(AtomicBool::new(true));
+        let flaky = serve(
+            Router::new()
+                .route("/count", get(flaky_count))
+                .with_state(broken.clone()),
+        )
+        .await;
+        let fallback = test_server("fallback server", false).await;
+
+        let mut flaky_mirror = mirror_setting(flaky);
+        flaky_mirror.max_failures = None;
+        let client = client_with_mirrors(
+            vec![flaky_mirror, mirror_setting(fallback)],
+            MirrorStrategy::Failover,
+        );
+
+        assert_eq!(get_count(&client).await, "Hi from counter: fallback server");
+
+        // The flaky mirror is never considered dead, but the mirror without failures
+        // is tried first.
+        broken.store(false, Ordering::SeqCst);
+        assert_eq!(get_count(&client).await, "Hi from counter: fallback server");
+        assert_eq!(get_count(&client).await, "Hi from counter: fallback server");
+    }
+
     #[test]
     fn test_mirror_sort() {
         let keys: Vec<Url> = vec![
@@ -0,0 +1,446 @@
+//! OAuth 2.0 / OIDC support to obtain and renew the tokens of an
+//! [`Authentication::OAuth`]. Tokens are obtained with the device
+//! authorization grant (RFC 8628) and renewed with the refresh token grant.
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use url::Url;
+
+use crate::Authentication;
+
+/// The grant type of the device authorization grant.
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Access tokens are renewed when they expire within this margin, so they do
+/// not expire while a request is in flight.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// An error that can occur when talking to an OAuth 2.0 provider.
+#[derive(thiserror::Error, Debug)]
+pub enum OAuthError {
+    /// The request to the provider failed.
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+
+    /// The provider responded with an OAuth 2.0 error, e.g. `invalid_grant` when
+    /// the refresh token has been revoked.
+    #[error("the authorization server returned `{error}`{}", .description.as_ref().map(|d| format!(": {d}")).unwrap_or_default())]
+    Server {
+        /// The error code
+        error: String,
+        /// The human readable description of the error
+        description: Option<String>,
+    },
+
+    /// The credentials do not contain a refresh token.
+    #[error("the credentials do not contain a refresh token")]
+    MissingRefreshToken,
+
+    /// The provider does not support the device authorization grant.
+    #[error("the provider does not support the device authorization grant")]
+    DeviceFlowNotSupported,
+
+    /// The device authorization expired before the user approved it.
+    #[error("the device authorization expired before it was approved")]
+    DeviceAuthorizationExpired,
+}
+
+/// The parts of the OIDC discovery document that are needed for the
+/// device authorization grant.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcConfiguration {
+    /// The endpoint to request and renew tokens
+    pub token_endpoint: Url,
+
+    /// The endpoint to start the device authorization grant
+    pub device_authorization_endpoint: Option<Url>,
+}
+
+impl OidcConfiguration {
+    /// Fetches the configuration from the
+    /// `{issuer}/.well-known/openid-configuration` document of the issuer.
+    pub async fn discover(client: &reqwest::Client, issuer: &Url) -> Result<Self, OAuthError> {
+        let mut url = issuer.clone();
+        url.set_path(&format!(
+            "{}/.well-known/openid-configuration",
+            issuer.path().trim_end_matches('/')
+        ));
+
+        Ok(client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+/// The response to a device authorization request. The user has to visit the
+/// verification URI and enter the user code to authorize the device.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    /// The code that identifies the device when polling for the token
+    pub device_code: String,
+
+    /// The code the user enters at the verification URI
+    pub user_code: String,
+
+    /// The URI the user visits to authorize the device
+    pub verification_uri: String,
+
+    /// The verification URI that already contains the user code
+    pub verification_uri_complete: Option<String>,
+
+    /// The lifetime of the device code in seconds
+    pub expires_in: u64,
+
+    /// The number of seconds to wait between polling for the token
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// The state of a device authorization after polling for the token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceTokenStatus {
+    /// The user has not yet authorized the device.
+    Pending,
+
+    /// The device polls too fast and should increase its interval by five
+    /// seconds.
+    SlowDown,
+
+    /// The user authorized the device.
+    Authorized(Authentication),
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// A client of the token endpoint of an OAuth 2.0 provider.
+#[derive(Debug, Clone)]
+pub struct OAuthClient {
+    client: reqwest::Client,
+    token_endpoint: Url,
+    client_id: String,
+}
+
+impl OAuthClient {
+    /// Creates a new client for the given token endpoint and client ID.
+    pub fn new(client: reqwest::Client, token_endpoint: Url, client_id: String) -> Self {
+        Self {
+            client,
+            token_endpoint,
+            client_id,
+        }
+    }
+
+    /// Starts the device authorization grant.
+    pub async fn request_device_authorization(
+        &self,
+        device_authorization_endpoint: &Url,
+        scopes: &[String],
+    ) -> Result<DeviceAuthorization, OAuthError> {
+        let scope = scopes.join(" ");
+        let response = self
+            .client
+            .post(device_authorization_endpoint.clone())
+            .form(&[("client_id", self.client_id.as_str()), ("scope", &scope)])
+            .send()
+            .await?;
+
+        Ok(error_for_oauth_status(response).await?.json().await?)
+    }
+
+    /// Polls the token endpoint once for the outcome of a device
+    /// authorization. The caller waits [`DeviceAuthorization::interval`]
+    /// seconds between calls.
+    pub async fn poll_device_token(
+        &self,
+        device: &DeviceAuthorization,
+    ) -> Result<DeviceTokenStatus, OAuthError> {
+        let result = self
+            .request_token(&[
+                ("grant_type", DEVICE_CODE_GRANT_TYPE),
+                ("device_code", &device.device_code),
+                ("client_id", &self.client_id),
+            ])
+            .await;
+
+        match result {
+            Ok(response) => Ok(DeviceTokenStatus::Authorized(
+                self.to_authentication(response, None),
+            )),
+            Err(OAuthError::Server { error, .. }) if error == "authorization_pending" => {
+                Ok(DeviceTokenStatus::Pending)
+            }
+            Err(OAuthError::Server { error, .. }) if error == "slow_down" => {
+                Ok(DeviceTokenStatus::SlowDown)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Obtains a new access token with the given refresh token. The refresh
+    /// token is kept if the provider does not issue a new one.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Authentication, OAuthError> {
+        let response = self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", &self.client_id),
+            ])
+            .await?;
+
+        Ok(self.to_authentication(response, Some(refresh_token)))
+    }
+
+    async fn request_token(&self, form: &[(&str, &str)]) -> Result<TokenResponse, OAuthError> {
+        let response = self
+            .client
+            .post(self.token_endpoint.clone())
+            .form(form)
+            .send()
+            .await?;
+
+        Ok(error_for_oauth_status(response).await?.json().await?)
+    }
+
+    fn to_authentication(
+        &self,
+        response: TokenResponse,
+        previous_refresh_token: Option<&str>,
+    ) -> Authentication {
+        Authentication::OAuth {
+            access_token: response.access_token,
+            refresh_token: response
+                .refresh_token
+                .or_else(|| previous_refresh_token.map(ToOwned::to_owned)),
+            expires_at: response.expires_in.map(|expires_in| {
+                DateTime::<Utc>::from(SystemTime::now() + Duration::from_secs(expires_in))
+            }),
+            token_endpoint: self.token_endpoint.clone(),
+            client_id: self.client_id.clone(),
+        }
+    }
+}
+
+/// Turns an OAuth 2.0 error response into an [`OAuthError::Server`].
+async fn error_for_oauth_status(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, OAuthError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status_error = response.error_for_status_ref().err();
+    match response.json::<ErrorResponse>().await {
+        Ok(error) => Err(OAuthError::Server {
+            error: error.error,
+            description: error.error_description,
+        }),
+        Err(err) => Err(status_error.unwrap_or(err).into()),
+    }
+}
+
+/// Returns true if the credentials are OAuth tokens that can be refreshed and
+/// whose access token has expired (or is about to).
+pub(crate) fn needs_refresh(authentication: &Authentication) -> bool {
+    match authentication {
+        Authentication::OAuth {
+            refresh_token: Some(_),
+            expires_at: Some(expires_at),
+            ..
+        } => *expires_at <= DateTime::<Utc>::from(SystemTime::now() + EXPIRY_MARGIN),
+        _ => false,
+    }
+}
+
+/// Refreshes the access token of OAuth credentials.
+pub(crate) async fn refresh(
+    client: &reqwest::Client,
+    authentication: &Authentication,
+) -> Result<Authentication, OAuthError> {
+    let Authentication::OAuth {
+        refresh_token: Some(refresh_token),
+        token_endpoint,
+        client_id,
+        ..
+    } = authentication
+    else {
+        return Err(OAuthError::MissingRefreshToken);
+    };
+
+    OAuthClient::new(client.clone(), token_endpoint.clone(), client_id.clone())
+        .refresh(refresh_token)
+        .await
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        future::IntoFuture,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use axum::{
+        extract::State,
+        http::StatusCode,
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[derive(Default)]
+    pub(crate) struct Provider {
+        pub(crate) polls: AtomicUsize,
+        pub(crate) refreshes: AtomicUsize,
+    }
+
+    async fn device(Form(form): Form<Vec<(String, String)>>) -> Json<Value> {
+        assert!(form.contains(&("client_id".to_string(), "rattler".to_string())));
+        Json(json!({
+            "device_code": "device-code",
+            "user_code": "ABCD-EFGH",
+            "verification_uri": "https://auth.example.com/device",
+            "expires_in": 600,
+            "interval": 0,
+        }))
+    }
+
+    async fn token(
+        State(provider): State<Arc<Provider>>,
+        Form(form): Form<Vec<(String, String)>>,
+    ) -> (StatusCode, Json<Value>) {
+        let grant_type = form
+            .iter()
+            .find(|(key, _)| key == "grant_type")
+            .map(|(_, value)| value.as_str());
+        match grant_type {
+            Some(DEVICE_CODE_GRANT_TYPE) => {
+                if provider.polls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "error": "authorization_pending" })),
+                    )
+                } else {
+                    (
+                        StatusCode::OK,
+                        Json(json!({
+                            "access_token": "access-0",
+                            "refresh_token": "refresh-0",
+                            "expires_in": 3600,
+                        })),
+                    )
+                }
+            }
+            Some("refresh_token")
+                if form.contains(&("refresh_token".into(), "refresh-0".into())) =>
+            {
+                let n = provider.refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+                (
+                    StatusCode::OK,
+                    Json(json!({ "access_token": format!("access-{n}"), "expires_in": 3600 })),
+                )
+            }
+            _ => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid_grant", "error_description": "unknown token" })),
+            ),
+        }
+    }
+
+    /// Starts a mock OIDC provider and returns its issuer URL.
+    pub(crate) async fn serve_provider(provider: Arc<Provider>) -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let configuration = json!({
+            "token_endpoint": format!("http://{address}/realm/token"),
+            "device_authorization_endpoint": format!("http://{address}/realm/device"),
+        });
+
+        let router = Router::new()
+            .route(
+                "/realm/.well-known/openid-configuration",
+                get(move || async move { Json(configuration) }),
+            )
+            .route("/realm/device", post(device))
+            .route("/realm/token", post(token))
+            .with_state(provider);
+
+        tokio::spawn(axum::serve(listener, router.into_make_service()).into_future());
+        format!("http://{address}/realm").parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_and_refresh() {
+        let provider = Arc::new(Provider::default());
+        let issuer = serve_provider(provider.clone()).await;
+        let client = reqwest::Client::new();
+
+        let config = OidcConfiguration::discover(&client, &issuer).await.unwrap();
+        assert_eq!(config.token_endpoint.path(), "/realm/token");
+
+        let oauth = OAuthClient::new(client, config.token_endpoint, "rattler".to_string());
+        let device = oauth
+            .request_device_authorization(
+                &config.device_authorization_endpoint.unwrap(),
+                &["openid".to_string()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(device.user_code, "ABCD-EFGH");
+
+        assert_eq!(
+            oauth.poll_device_token(&device).await.unwrap(),
+            DeviceTokenStatus::Pending
+        );
+        let DeviceTokenStatus::Authorized(auth) = oauth.poll_device_token(&device).await.unwrap()
+        else {
+            panic!("expected the device to be authorized");
+        };
+        assert!(!needs_refresh(&auth));
+
+        // The refresh token is kept when the provider does not rotate it.
+        let refreshed = refresh(&reqwest::Client::new(), &auth).await.unwrap();
+        let Authentication::OAuth {
+            access_token,
+            refresh_token,
+            client_id,
+            ..
+        } = refreshed
+        else {
+            panic!("expected OAuth credentials");
+        };
+        assert_eq!(access_token, "access-1");
+        assert_eq!(refresh_token.as_deref(), Some("refresh-0"));
+        assert_eq!(client_id, "rattler");
+
+        // A revoked refresh token results in the error of the provider.
+        let err = oauth.refresh("revoked").await.unwrap_err();
+        assert!(
+            matches!(&err, OAuthError::Server { error, .. } if error == "invalid_grant"),
+            "{err:?}"
+        );
+    }
+}
@@ -1,19 +1,30 @@
 //! Middleware to handle `oci://` URLs to pull artifacts from an OCI registry
+//! and an uploader to push artifacts to an OCI registry
 use std::{
     collections::HashMap,
     fmt::{Display, Formatter},
 };
 
 use http::{
-    header::{ACCEPT, AUTHORIZATION},
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, LOCATION},
     Extensions,
 };
 use reqwest::{Request, Response};
 use reqwest_middleware::{Middleware, Next};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use url::{ParseError, Url};
 
-use crate::mirror_middleware::create_404_response;
+use crate::{mirror_middleware::create_404_response, Authentication};
+
+/// The media type of an OCI image manifest
+const OCI_MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// The media type of the empty config of an OCI artifact
+const OCI_EMPTY_CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+
+/// The annotation that stores the filename of a layer
+const OCI_TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
 
 #[derive(thiserror::Error, Debug)]
 enum OciMiddlewareError {
@@ -27,6 +38,26 @@ enum OciMiddlewareError {
     LayerNotFound,
 }
 
+/// An error that can occur when pushing an artifact to an OCI registry
+#[derive(thiserror::Error, Debug)]
+pub enum OciPushError {
+    /// A request to the registry failed
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+
+    /// The URL of the artifact is invalid
+    #[error("URL parse error: {0}")]
+    ParseError(#[from] ParseError),
+
+    /// The file cannot be stored as an artifact in an OCI registry
+    #[error("unsupported file type: {0}")]
+    UnsupportedFileType(String),
+
+    /// The registry did not return a location to upload a blob to
+    #[error("the registry did not return an upload location")]
+    MissingUploadLocation,
+}
+
 /// Middleware to handle `oci://` URLs
 #[derive(Default, Debug, Clone)]
 pub struct OciMiddleware;
@@ -74,6 +105,7 @@ async fn get_token(url: &OCIUrl, action: OciAction) -> Result<String, OciMiddlew
 #[derive(Debug)]
 struct OCIUrl {
     url: Url,
+    scheme: String,
     host: String,
     path: String,
     tag: String,
@@ -97,36 +129,67 @@ fn version_build_tag(tag: &str) -> String {
         .replace('=', "__eq__")
 }
 
+/// Splits the name of a package archive without its extension into the name,
+/// version and build string of the package.
+fn split_archive_name(archive_name: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = archive_name.rsplitn(3, '-');
+    let build = parts.next()?;
+    let version = parts.next()?;
+    let name = parts.next()?;
+    Some((name, version, build))
+}
+
 impl OCIUrl {
     pub fn manifest_url(&self) -> Result<Url, ParseError> {
         format!(
-            "https://{}/v2/{}/manifests/{}",
-            self.host, self.path, self.tag
+            "{}://{}/v2/{}/manifests/{}",
+            self.scheme, self.host, self.path, self.tag
         )
         .parse()
     }
 
     pub fn token_url(&self, action: OciAction) -> Result<Url, ParseError> {
         format!(
-            "https://{}/token?scope=repository:{}:{}",
-            self.host, self.path, action
+            "{}://{}/token?scope=repository:{}:{}",
+            self.scheme, self.host, self.path, action
         )
         .parse()
     }
 
     pub fn blob_url(&self, sha256: &str) -> Result<Url, ParseError> {
-        format!("https://{}/v2/{}/blobs/{}", self.host, self.path, sha256).parse()
+        format!(
+            "{}://{}/v2/{}/blobs/{}",
+            self.scheme, self.host, self.path, sha256
+        )
+        .parse()
+    }
+
+    pub fn upload_url(&self) -> Result<Url, ParseError> {
+        format!(
+            "{}://{}/v2/{}/blobs/uploads/",
+            self.scheme, self.host, self.path
+        )
+        .parse()
     }
 
+    /// Parses the `oci://` URL of a file. Files whose name is not recognized
+    /// get an empty media type.
     pub fn new(url: &Url) -> Result<Self, ParseError> {
         // get filename (last segment of path)
-        let filename = url.path_segments().unwrap().last().unwrap();
+        let filename = url
+            .path_segments()
+            .and_then(Iterator::last)
+            .unwrap_or_default();
 
         let mut res = OCIUrl {
             url: url.clone(),
             tag: "latest".to_string(),
             media_type: "".to_string(),
-            host: url.host_str().unwrap_or("").to_string(),
+            scheme: "https".to_string(),
+            host: match url.port() {
+                Some(port) => format!("{}:{port}", url.host_str().unwrap_or("")),
+                None => url.host_str().unwrap_or("").to_string(),
+            },
             path: url.path().trim_start_matches('/').to_string(),
         };
 
@@ -134,15 +197,18 @@ impl OCIUrl {
 
         // We reimplement some archive name splitting logic from rattler here
         // because we don't want to introduce cyclic dependencies
-        if let Some(archive_name) = filename.strip_suffix(".conda") {
-            let parts = archive_name.rsplitn(3, '-').collect::<Vec<&str>>();
-            computed_filename = parts[2].to_string();
-            res.tag = version_build_tag(&format!("{}-{}", parts[1], parts[0]));
+        if let Some((name, version, build)) =
+            filename.strip_suffix(".conda").and_then(split_archive_name)
+        {
+            computed_filename = name.to_string();
+            res.tag = version_build_tag(&format!("{version}-{build}"));
             res.media_type = "application/vnd.conda.package.v2".to_string();
-        } else if let Some(archive_name) = filename.strip_suffix(".tar.bz2") {
-            let parts = archive_name.rsplitn(3, '-').collect::<Vec<&str>>();
-            computed_filename = parts[2].to_string();
-            res.tag = version_build_tag(&format!("{}-{}", parts[1], parts[0]));
+        } else if let Some((name, version, build)) = filename
+            .strip_suffix(".tar.bz2")
+            .and_then(split_archive_name)
+        {
+            computed_filename = name.to_string();
+            res.tag = version_build_tag(&format!("{version}-{build}"));
             res.media_type = "application/vnd.conda.package.v1".to_string();
         } else if filename.starts_with("repodata.json") {
             computed_filename = "repodata.json".to_string();
@@ -164,7 +230,7 @@ impl OCIUrl {
             computed_filename = format!("zzz{computed_filename}");
         }
 
-        res.url = url.join(&computed_filename).unwrap();
+        res.url = url.join(&computed_filename)?;
         res.path = res.url.path().trim_start_matches('/').to_string();
         Ok(res)
     }
@@ -219,25 +285,222 @@ impl OCIUrl {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct Layer {
     digest: String,
     #[serde(rename = "mediaType")]
     media_type: String,
     size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
     annotations: Option<HashMap<String, String>>,
 }
 
+impl Layer {
+    /// Describes the given content as a blob with the given media type
+    fn new(media_type: &str, content: &[u8]) -> Self {
+        Layer {
+            digest: format!("sha256:{:x}", Sha256::digest(content)),
+            media_type: media_type.to_string(),
+            size: content.len() as u64,
+            annotations: None,
+        }
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Manifest {
     schema_version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifact_type: Option<String>,
     layers: Vec<Layer>,
     config: Layer,
+    #[serde(skip_serializing_if = "Option::is_none")]
     annotations: Option<HashMap<String, String>>,
 }
 
+/// Pushes conda packages and repodata files to an OCI registry, using the same
+/// layout that the [`OciMiddleware`] pulls them from.
+///
+/// Every file is stored as an OCI artifact with a single layer that contains
+/// the file. Packages are tagged with their version and build string in a
+/// repository named after the package, repodata files are tagged `latest`.
+#[derive(Debug, Clone, Default)]
+pub struct OciUploader {
+    client: reqwest::Client,
+    credentials: Option<Authentication>,
+    plain_http: bool,
+}
+
+impl OciUploader {
+    /// Constructs a new uploader that sends requests with the given client
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the credentials that are used to request a token for pushing to
+    /// the registry, e.g. a username and personal access token for ghcr.io.
+    pub fn with_credentials(self, credentials: Authentication) -> Self {
+        Self {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    /// Connect to the registry over plain HTTP instead of HTTPS. This is only
+    /// useful for local registries.
+    pub fn with_plain_http(self, plain_http: bool) -> Self {
+        Self { plain_http, ..self }
+    }
+
+    /// Pushes the content of a file to the registry.
+    ///
+    /// The `url` is the `oci://` URL that the file can be pulled from, e.g.
+    /// `oci://ghcr.io/channel-mirrors/conda-forge/osx-arm64/xtensor-0.25.0-h2ffa867_0.conda`.
+    /// Returns the URL of the manifest that was pushed.
+    pub async fn push(&self, url: &Url, content: Vec<u8>) -> Result<Url, OciPushError> {
+        let mut oci_url = OCIUrl::new(url)?;
+        if self.plain_http {
+            oci_url.scheme = "http".to_string();
+        }
+
+        let filename = url
+            .path_segments()
+            .and_then(Iterator::last)
+            .unwrap_or_default()
+            .to_string();
+        if oci_url.media_type.is_empty() {
+            return Err(OciPushError::UnsupportedFileType(filename));
+        }
+
+        let token = self.get_token(&oci_url).await?;
+
+        let config_content = b"{}".to_vec();
+        let config = Layer::new(OCI_EMPTY_CONFIG_MEDIA_TYPE, &config_content);
+        let layer = Layer {
+            annotations: Some(HashMap::from([(
+                OCI_TITLE_ANNOTATION.to_string(),
+                filename,
+            )])),
+            ..Layer::new(&oci_url.media_type, &content)
+        };
+
+        self.push_blob(&oci_url, &token, &config, config_content)
+            .await?;
+        self.push_blob(&oci_url, &token, &layer, content).await?;
+
+        let manifest = Manifest {
+            schema_version: 2,
+            media_type: Some(OCI_MANIFEST_MEDIA_TYPE.to_string()),
+            artifact_type: Some(oci_url.media_type.clone()),
+            layers: vec![layer],
+            config,
+            annotations: None,
+        };
+
+        let manifest_url = oci_url.manifest_url()?;
+        tracing::debug!("OCI: pushing manifest to {}", manifest_url);
+        self.client
+            .put(manifest_url.clone())
+            .bearer_auth(&token)
+            .header(CONTENT_TYPE, OCI_MANIFEST_MEDIA_TYPE)
+            .json(&manifest)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(manifest_url)
+    }
+
+    /// Requests a token that allows pushing to the repository of the URL
+    async fn get_token(&self, url: &OCIUrl) -> Result<String, OciPushError> {
+        let token_url = url.token_url(OciAction::PushPull)?;
+
+        tracing::trace!("OCI: requesting push token from {}", token_url);
+
+        let request = self.client.get(token_url);
+        let request = match &self.credentials {
+            Some(Authentication::BasicHTTP { username, password }) => {
+                request.basic_auth(username, Some(password))
+            }
+            Some(
+                Authentication::BearerToken(token)
+                | Authentication::OAuth {
+                    access_token: token,
+                    ..
+                },
+            ) => request.bearer_auth(token),
+            Some(Authentication::CondaToken(_) | Authentication::S3Credentials { .. }) | None => {
+                request
+            }
+        };
+
+        Ok(request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<OCIToken>()
+            .await?
+            .token)
+    }
+
+    /// Uploads a blob unless the registry already contains it
+    async fn push_blob(
+        &self,
+        url: &OCIUrl,
+        token: &str,
+        descriptor: &Layer,
+        content: Vec<u8>,
+    ) -> Result<(), OciPushError> {
+        let existing = self
+            .client
+            .head(url.blob_url(&descriptor.digest)?)
+            .bearer_auth(token)
+            .send()
+            .await?;
+        if existing.status().is_success() {
+            tracing::debug!("OCI: blob {} already exists", descriptor.digest);
+            return Ok(());
+        }
+
+        // Start an upload session, the registry tells us where to send the blob.
+        let session = self
+            .client
+            .post(url.upload_url()?)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?;
+        let location = session
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or(OciPushError::MissingUploadLocation)?;
+        let mut upload_url = session.url().join(location)?;
+        upload_url
+            .query_pairs_mut()
+            .append_pair("digest", &descriptor.digest);
+
+        tracing::debug!("OCI: uploading blob {}", descriptor.digest);
+        self.client
+            .put(upload_url)
+            .bearer_auth(token)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(content)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
 #[async_trait::async_trait]
 impl Middleware for OciMiddleware {
     async fn handle(
@@ -280,9 +543,225 @@ impl Middleware for OciMiddleware {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::HashMap,
+        future::IntoFuture,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, Method, StatusCode, Uri},
+        Router,
+    };
     use sha2::{Digest, Sha256};
+    use url::Url;
+
+    use super::{Manifest, OciPushError};
+    use crate::{Authentication, OciMiddleware, OciUploader};
+
+    /// The state of an in-memory stand-in for an OCI registry
+    #[derive(Default)]
+    struct TestRegistry {
+        blobs: HashMap<String, Vec<u8>>,
+        manifests: HashMap<(String, String), Vec<u8>>,
+        uploads: usize,
+    }
+
+    /// Implements the parts of the OCI distribution API that are used to push
+    /// artifacts.
+    async fn registry(
+        State(state): State<Arc<Mutex<TestRegistry>>>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut state = state.lock().unwrap();
+        let mut response_headers = HeaderMap::new();
+        let authorization = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        let path = uri.path();
+
+        if path == "/token" {
+            // The test credentials are `user:secret`
+            if authorization != Some("Basic dXNlcjpzZWNyZXQ=") {
+                return (StatusCode::UNAUTHORIZED, response_headers, Vec::new());
+            }
+            return (
+                StatusCode::OK,
+                response_headers,
+                br#"{"token": "push-token"}"#.to_vec(),
+            );
+        }
+        if authorization != Some("Bearer push-token") {
+            return (StatusCode::UNAUTHORIZED, response_headers, Vec::new());
+        }
+
+        if let Some(upload) = path.strip_prefix("/uploads/") {
+            assert_eq!(method, Method::PUT);
+            let url = Url::parse(&format!("http://localhost{uri}")).unwrap();
+            let digest = url
+                .query_pairs()
+                .find(|(key, _)| key == "digest")
+                .unwrap()
+                .1
+                .into_owned();
+            assert_eq!(digest, format!("sha256:{:x}", Sha256::digest(&body)));
+            assert!(upload.parse::<usize>().unwrap() < state.uploads);
+            state.blobs.insert(digest, body.to_vec());
+            return (StatusCode::CREATED, response_headers, Vec::new());
+        }
 
-    use crate::OciMiddleware;
+        let path = path.strip_prefix("/v2/").unwrap();
+        if path.ends_with("/blobs/uploads/") {
+            assert_eq!(method, Method::POST);
+            response_headers.insert(
+                "location",
+                format!("/uploads/{}", state.uploads).parse().unwrap(),
+            );
+            state.uploads += 1;
+            (StatusCode::ACCEPTED, response_headers, Vec::new())
+        } else if let Some((_, digest)) = path.split_once("/blobs/") {
+            let status = if state.blobs.contains_key(digest) {
+                StatusCode::OK
+            } else {
+                StatusCode::NOT_FOUND
+            };
+            (status, response_headers, Vec::new())
+        } else if let Some((repository, tag)) = path.split_once("/manifests/") {
+            assert_eq!(method, Method::PUT);
+            assert_eq!(
+                headers.get("content-type").unwrap(),
+                "application/vnd.oci.image.manifest.v1+json"
+            );
+            state
+                .manifests
+                .insert((repository.to_string(), tag.to_string()), body.to_vec());
+            (StatusCode::CREATED, response_headers, Vec::new())
+        } else {
+            (StatusCode::NOT_FOUND, response_headers, Vec::new())
+        }
+    }
+
+    async fn test_registry() -> (String, Arc<Mutex<TestRegistry>>) {
+        let state = Arc::new(Mutex::new(TestRegistry::default()));
+        let router = Router::new().fallback(registry).with_state(state.clone());
+
+        let addr = SocketAddr::new([127, 0, 0, 1].into(), 0);
+        let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(axum::serve(listener, router.into_make_service()).into_future());
+        (format!("{}:{}", addr.ip(), addr.port()), state)
+    }
+
+    #[tokio::test]
+    async fn test_oci_uploader() {
+        let (host, state) = test_registry().await;
+        let uploader = OciUploader::new(reqwest::Client::new())
+            .with_credentials(Authentication::BasicHTTP {
+                username: String::from("user"),
+                password: String::from("secret"),
+            })
+            .with_plain_http(true);
+
+        let package = b"not really a conda package".to_vec();
+        let url: Url = format!("oci://{host}/channel/osx-arm64/xtensor-0.25.0-h2ffa867_0.conda")
+            .parse()
+            .unwrap();
+        let manifest_url = uploader.push(&url, package.clone()).await.unwrap();
+        assert_eq!(
+            manifest_url.as_str(),
+            format!("http://{host}/v2/channel/osx-arm64/xtensor/manifests/0.25.0-h2ffa867_0")
+        );
+
+        {
+            let state = state.lock().unwrap();
+            let manifest: Manifest = serde_json::from_slice(
+                &state.manifests[&(
+                    String::from("channel/osx-arm64/xtensor"),
+                    String::from("0.25.0-h2ffa867_0"),
+                )],
+            )
+            .unwrap();
+            assert_eq!(manifest.schema_version, 2);
+            assert_eq!(
+                manifest.artifact_type.as_deref(),
+                Some("application/vnd.conda.package.v2")
+            );
+            assert_eq!(
+                manifest.config.media_type,
+                "application/vnd.oci.empty.v1+json"
+            );
+            assert_eq!(state.blobs[&manifest.config.digest], b"{}");
+
+            let layer = &manifest.layers[0];
+            assert_eq!(layer.media_type, "application/vnd.conda.package.v2");
+            assert_eq!(layer.size, package.len() as u64);
+            assert_eq!(
+                layer.annotations.as_ref().unwrap()["org.opencontainers.image.title"],
+                "xtensor-0.25.0-h2ffa867_0.conda"
+            );
+            assert_eq!(state.blobs[&layer.digest], package);
+            assert_eq!(state.uploads, 2);
+        }
+
+        // Blobs that already exist in the registry are not uploaded again.
+        uploader.push(&url, package.clone()).await.unwrap();
+        assert_eq!(state.lock().unwrap().uploads, 2);
+
+        // Repodata is pushed with the `latest` tag.
+        let url: Url = format!("oci://{host}/channel/osx-arm64/repodata.json")
+            .parse()
+            .unwrap();
+        uploader.push(&url, b"{}".to_vec()).await.unwrap();
+        {
+            let state = state.lock().unwrap();
+            let manifest: Manifest = serde_json::from_slice(
+                &state.manifests[&(
+                    String::from("channel/osx-arm64/repodata.json"),
+                    String::from("latest"),
+                )],
+            )
+            .unwrap();
+            assert_eq!(
+                manifest.layers[0].media_type,
+                "application/vnd.conda.repodata.v1+json"
+            );
+        }
+
+        for file in ["README.md", "xtensor.conda", "xtensor-0.25.0.tar.bz2"] {
+            let url: Url = format!("oci://{host}/channel/osx-arm64/{file}")
+                .parse()
+                .unwrap();
+            assert!(matches!(
+                uploader.push(&url, Vec::new()).await,
+                Err(OciPushError::UnsupportedFileType(_))
+            ));
+        }
+        assert!(matches!(
+            uploader
+                .push(&"oci:xtensor".parse().unwrap(), Vec::new())
+                .await,
+            Err(OciPushError::ParseError(_))
+        ));
+
+        // Pushing without credentials fails.
+        let url: Url = format!("oci://{host}/channel/osx-arm64/xtensor-0.25.0-h2ffa867_0.conda")
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            OciUploader::new(reqwest::Client::new())
+                .with_plain_http(true)
+                .push(&url, package)
+                .await,
+            Err(OciPushError::Reqwest(_))
+        ));
+    }
 
     // test pulling an image from OCI registry
     #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
@@ -300,6 +300,7 @@ impl GatewayInner {
         } else if url.scheme() == "http"
             || url.scheme() == "https"
             || url.scheme() == "gcs"
+            || url.scheme() == "s3"
             || url.scheme() == "oci"
         {
             remote_subdir::RemoteSubdirClient::new(
@@ -663,6 +664,60 @@ mod test {
         assert_matches!(gateway_error, GatewayError::MatchSpecWithoutName(_));
     }
 
+    #[tokio::test]
+    async fn test_s3_gateway() {
+        // Serve the test data as if it is a bucket called `channels` of an S3
+        // compatible storage.
+        let server =
+            SimpleChannelServer::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data"))
+                .await;
+        let middleware = rattler_networking::S3Middleware::new(
+            [(
+                String::from("channels"),
+                rattler_networking::s3_middleware::S3Config {
+                    endpoint_url: server.url(),
+                    region: String::from("us-east-1"),
+                    force_path_style: true,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            rattler_networking::AuthenticationStorage::new(),
+        );
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(middleware)
+            .build();
+
+        let gateway = Gateway::builder()
+            .with_client(client)
+            .with_channel_config(super::ChannelConfig {
+                default: SourceConfig {
+                    cache_action: CacheAction::NoCache,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .finish();
+
+        let records = gateway
+            .query(
+                vec![Channel::from_url(
+                    Url::parse("s3://channels/dummy").unwrap(),
+                )],
+                vec![Platform::Linux64],
+                vec![PackageName::from_str("cuda-version").unwrap()].into_iter(),
+            )
+            .await
+            .unwrap();
+
+        let records = records.iter().flat_map(RepoData::iter).collect::<Vec<_>>();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|record| record
+            .url
+            .as_str()
+            .starts_with("s3://channels/dummy/linux-64/")));
+    }
+
     #[rstest]
     #[case::named("non-existing-channel")]
     #[case::url("https://conda.anaconda.org/does-not-exist")]
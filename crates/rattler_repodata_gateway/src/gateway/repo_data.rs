@@ -1,4 +1,5 @@
 use rattler_conda_types::RepoDataRecord;
+use rattler_digest::{digest::Digest, Sha256, Sha256Hash};
 use std::iter::FusedIterator;
 use std::sync::Arc;
 
@@ -35,6 +36,30 @@ impl RepoData {
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    /// Computes a hash that identifies the contents of all the records in
+    /// this instance. The hash does not depend on the order of the records,
+    /// but changes if any record is added, removed or modified (e.g. by a
+    /// repodata patch).
+    ///
+    /// This can be used as the identity of the repodata when computing the
+    /// fingerprint of a solve, to cache solve results.
+    pub fn fingerprint(&self) -> Sha256Hash {
+        let mut record_hashes = self
+            .iter()
+            .map(|record| {
+                let bytes = serde_json::to_vec(record).expect("records can always be serialized");
+                rattler_digest::compute_bytes_digest::<Sha256>(bytes)
+            })
+            .collect::<Vec<_>>();
+        record_hashes.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for hash in record_hashes {
+            hasher.update(hash);
+        }
+        hasher.finalize()
+    }
 }
 
 impl<'r> IntoIterator for &'r RepoData {
@@ -87,3 +112,73 @@ impl ExactSizeIterator for RepoDataIterator<'_> {
         self.records.len - self.total
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use rattler_conda_types::{PackageRecord, RepoDataRecord};
+    use url::Url;
+
+    use super::RepoData;
+
+    fn record(name: &str, version: &str) -> RepoDataRecord {
+        RepoDataRecord {
+            package_record: PackageRecord::new(
+                name.parse().unwrap(),
+                version.parse::<rattler_conda_types::Version>().unwrap(),
+                String::from("h123_0"),
+            ),
+            file_name: format!("{name}-{version}-h123_0.conda"),
+            url: Url::parse(&format!(
+                "https://example.com/{name}-{version}-h123_0.conda"
+            ))
+            .unwrap(),
+            channel: String::from("https://example.com"),
+        }
+    }
+
+    fn repo_data(shards: Vec<Vec<RepoDataRecord>>) -> RepoData {
+        RepoData {
+            len: shards.iter().map(Vec::len).sum(),
+            shards: shards.into_iter().map(Arc::from).collect(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = repo_data(vec![
+            vec![record("foo", "1.0"), record("foo", "2.0")],
+            vec![record("bar", "1.0")],
+        ])
+        .fingerprint();
+
+        // The order of the shards and records does not matter.
+        assert_eq!(
+            fingerprint,
+            repo_data(vec![
+                vec![record("bar", "1.0")],
+                vec![record("foo", "2.0"), record("foo", "1.0")],
+            ])
+            .fingerprint()
+        );
+
+        // Modifying a record changes the fingerprint.
+        let mut patched = record("bar", "1.0");
+        patched.package_record.depends.push(String::from("foo <2"));
+        assert_ne!(
+            fingerprint,
+            repo_data(vec![
+                vec![record("foo", "1.0"), record("foo", "2.0")],
+                vec![patched],
+            ])
+            .fingerprint()
+        );
+
+        // Removing a record changes the fingerprint.
+        assert_ne!(
+            fingerprint,
+            repo_data(vec![vec![record("foo", "1.0"), record("foo", "2.0")]]).fingerprint()
+        );
+    }
+}
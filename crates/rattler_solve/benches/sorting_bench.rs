@@ -31,6 +31,7 @@ fn bench_sort(c: &mut Criterion, sparse_repo_data: &SparseRepoData, spec: &str)
                     &[],
                     &[match_spec.clone()],
                     None,
+                    None,
                     ChannelPriority::default(),
                     None,
                     rattler_solve::SolveStrategy::Highest,
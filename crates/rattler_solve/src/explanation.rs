@@ -0,0 +1,247 @@
+//! Explanations of why the packages of a solution were included.
+//!
+//! A [`SolveExplanation`] records for every package in a solution which of the
+//! requested specs, or which dependencies of other packages in the solution,
+//! caused it to be installed. This is similar to `conda why` or an inverted
+//! dependency tree.
+//!
+//! The explanation is a best-effort heuristic: it is inferred from the specs
+//! and the `depends` of the records in the solution after the solve, not from
+//! the decisions the solver made.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use rattler_conda_types::{MatchSpec, Matches, PackageName, ParseStrictness, RepoDataRecord};
+
+/// Describes why a package is part of a solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
+pub enum InclusionReason {
+    /// The package was requested by one of the specs of the task.
+    Requested {
+        /// The requested spec
+        spec: String,
+    },
+
+    /// The package is a dependency of another package in the solution.
+    Dependency {
+        /// The name of the package that depends on the package
+        parent: PackageName,
+
+        /// The dependency of the parent that the package satisfies
+        spec: String,
+    },
+}
+
+/// Describes why each of the packages of a solution was included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolveExplanation {
+    reasons: BTreeMap<PackageName, Vec<InclusionReason>>,
+}
+
+impl SolveExplanation {
+    /// Infers the explanation of a solution from the specs that were
+    /// requested and the records that make up the solution.
+    ///
+    /// This is a best-effort heuristic that re-parses the `depends` of the
+    /// records, the decisions of the solver are not taken into account. A
+    /// package is included because of a spec or dependency if the spec
+    /// matches the record of the package in the solution. Dependencies that
+    /// cannot be parsed or that refer to packages outside of the solution
+    /// (e.g. virtual packages) are ignored, and a package can be attributed
+    /// to every dependent that matches it even if the solver only needed one
+    /// of them.
+    pub fn infer_from_solution(specs: &[MatchSpec], records: &[RepoDataRecord]) -> Self {
+        let records_by_name = records
+            .iter()
+            .map(|record| (&record.package_record.name, record))
+            .collect::<HashMap<_, _>>();
+
+        let mut reasons = records
+            .iter()
+            .map(|record| (record.package_record.name.clone(), Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+
+        for spec in specs {
+            let Some(name) = &spec.name else {
+                continue;
+            };
+            if let Some(record) = records_by_name.get(name) {
+                if spec.matches(*record) {
+                    reasons
+                        .get_mut(name)
+                        .expect("every record has an entry")
+                        .push(InclusionReason::Requested {
+                            spec: spec.to_string(),
+                        });
+                }
+            }
+        }
+
+        for parent in records {
+            for depends in &parent.package_record.depends {
+                let Ok(spec) = MatchSpec::from_str(depends, ParseStrictness::Lenient) else {
+                    continue;
+                };
+                let Some(name) = &spec.name else {
+                    continue;
+                };
+                let Some(record) = records_by_name.get(name) else {
+                    continue;
+                };
+                if spec.matches(*record) {
+                    reasons
+                        .get_mut(name)
+                        .expect("every record has an entry")
+                        .push(InclusionReason::Dependency {
+                            parent: parent.package_record.name.clone(),
+                            spec: depends.clone(),
+                        });
+                }
+            }
+        }
+
+        Self { reasons }
+    }
+
+    /// Returns the reasons why the package was included, or an empty slice if
+    /// the package is not part of the solution.
+    pub fn reasons(&self, package: &PackageName) -> &[InclusionReason] {
+        self.reasons.get(package).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the names of the packages that depend on the package.
+    pub fn dependents(&self, package: &PackageName) -> impl Iterator<Item = &PackageName> + '_ {
+        self.reasons(package)
+            .iter()
+            .filter_map(|reason| match reason {
+                InclusionReason::Dependency { parent, .. } => Some(parent),
+                InclusionReason::Requested { .. } => None,
+            })
+    }
+
+    /// Returns true if the package was requested by one of the specs.
+    pub fn is_requested(&self, package: &PackageName) -> bool {
+        self.reasons(package)
+            .iter()
+            .any(|reason| matches!(reason, InclusionReason::Requested { .. }))
+    }
+
+    /// Iterates over all the packages of the solution together with the
+    /// reasons why they were included, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&PackageName, &[InclusionReason])> + '_ {
+        self.reasons
+            .iter()
+            .map(|(name, reasons)| (name, reasons.as_slice()))
+    }
+
+    /// Returns the shortest chain of reasons that explains why the package was
+    /// included. The chain starts with the [`InclusionReason::Requested`]
+    /// reason of a requested package and ends with the reason of the package
+    /// itself.
+    ///
+    /// Returns `None` if the package is not part of the solution or if it
+    /// cannot be traced back to a requested spec.
+    pub fn why(&self, package: &PackageName) -> Option<Vec<&InclusionReason>> {
+        // Search upwards from the package until a requested package is found.
+        let mut successor: HashMap<&PackageName, (&PackageName, &InclusionReason)> = HashMap::new();
+        let mut queue = VecDeque::from([package]);
+        let mut visited = HashSet::from([package]);
+        while let Some(current) = queue.pop_front() {
+            let reasons = self.reasons.get(current)?;
+            if let Some(requested) = reasons
+                .iter()
+                .find(|reason| matches!(reason, InclusionReason::Requested { .. }))
+            {
+                let mut chain = vec![requested];
+                let mut current = current;
+                while let Some((child, reason)) = successor.get(current) {
+                    chain.push(*reason);
+                    current = child;
+                }
+                return Some(chain);
+            }
+
+            for reason in reasons {
+                if let InclusionReason::Dependency { parent, .. } = reason {
+                    if visited.insert(parent) {
+                        successor.insert(parent, (current, reason));
+                        queue.push_back(parent);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use rattler_conda_types::PackageRecord;
+    use url::Url;
+
+    use super::*;
+
+    fn record(name: &str, version: &str, depends: &[&str]) -> RepoDataRecord {
+        let mut package_record = PackageRecord::new(
+            name.parse().unwrap(),
+            version.parse::<rattler_conda_types::Version>().unwrap(),
+            String::from("h123_0"),
+        );
+        package_record.depends = depends.iter().map(ToString::to_string).collect();
+        RepoDataRecord {
+            package_record,
+            file_name: format!("{name}-{version}-h123_0.conda"),
+            url: Url::from_str("https://example.com").unwrap(),
+            channel: String::from("test"),
+        }
+    }
+
+    #[test]
+    fn test_explain_solution() {
+        let records = vec![
+            record("python", "3.12", &["libzlib >=1.2", "__glibc >=2.17"]),
+            record("numpy", "2.0", &["python >=3.10", "libblas"]),
+            record("libblas", "3.9", &["libzlib <1"]),
+            record("libzlib", "1.3", &[]),
+        ];
+        let specs = vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()];
+
+        let explanation = SolveExplanation::infer_from_solution(&specs, &records);
+        let name = |name: &str| PackageName::new_unchecked(name);
+
+        assert!(explanation.is_requested(&name("numpy")));
+        assert!(!explanation.is_requested(&name("python")));
+        assert_eq!(
+            explanation.dependents(&name("python")).collect::<Vec<_>>(),
+            vec![&name("numpy")]
+        );
+
+        // The dependency of libblas does not match the libzlib in the solution.
+        assert_eq!(
+            explanation.reasons(&name("libzlib")),
+            &[InclusionReason::Dependency {
+                parent: name("python"),
+                spec: String::from("libzlib >=1.2"),
+            }]
+        );
+
+        let chain = explanation
+            .why(&name("libzlib"))
+            .unwrap()
+            .into_iter()
+            .map(|reason| match reason {
+                InclusionReason::Requested { spec } | InclusionReason::Dependency { spec, .. } => {
+                    spec.as_str()
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(chain, vec!["numpy", "python >=3.10", "libzlib >=1.2"]);
+
+        assert!(explanation.why(&name("unknown")).is_none());
+    }
+}
@@ -0,0 +1,202 @@
+//! Deterministic fingerprints of solver tasks, see [`SolverTask::fingerprint`].
+
+use std::fmt::Display;
+
+use rattler_conda_types::RepoDataRecord;
+use rattler_digest::{digest::Digest, Sha256, Sha256Hash};
+
+use crate::{ChannelPriority, SolveStrategy, SolverTask};
+
+/// Feeds the fields of a task to a hasher in an unambiguous encoding: every
+/// field is tagged and every value is prefixed with its length.
+struct FingerprintHasher(Sha256);
+
+impl FingerprintHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+    }
+
+    fn field<T: AsRef<[u8]>>(&mut self, tag: &str, values: impl IntoIterator<Item = T>) {
+        self.write(tag.as_bytes());
+        let values = values.into_iter().collect::<Vec<_>>();
+        self.0.update((values.len() as u64).to_le_bytes());
+        for value in values {
+            self.write(value.as_ref());
+        }
+    }
+
+    fn display_field<T: Display>(&mut self, tag: &str, values: impl IntoIterator<Item = T>) {
+        self.field(tag, values.into_iter().map(|value| value.to_string()));
+    }
+}
+
+/// Returns a string that identifies the record and its contents.
+fn record_identity(record: &RepoDataRecord) -> String {
+    let package_record = &record.package_record;
+    match (package_record.sha256, package_record.md5) {
+        (Some(sha256), _) => format!("{} sha256:{sha256:x}", record.url),
+        (None, Some(md5)) => format!("{} md5:{md5:x}", record.url),
+        (None, None) => record.url.to_string(),
+    }
+}
+
+impl<TAvailablePackagesIterator> SolverTask<TAvailablePackagesIterator> {
+    /// Computes a deterministic hash of everything in this task that
+    /// influences its solution. Two tasks with the same fingerprint describe
+    /// the same request, which makes it suitable as a key to cache solve
+    /// results.
+    ///
+    /// The solver backend is not part of the fingerprint. Different backends,
+    /// or different versions of the same backend, can return different
+    /// solutions for the same task, so a cache that is shared between them
+    /// should also include the backend in its key.
+    ///
+    /// The available packages are not hashed themselves, instead the caller
+    /// passes an identity for each of the repodata sources (in the same order
+    /// as the available packages), for instance
+    /// `rattler_repodata_gateway::RepoData::fingerprint` or the etag of a
+    /// `repodata.json`. The timeout is not part of the fingerprint because it
+    /// does not change the solution.
+    pub fn fingerprint<I: AsRef<[u8]>>(&self, repodata: impl IntoIterator<Item = I>) -> Sha256Hash {
+        let mut hasher = FingerprintHasher(Sha256::new());
+        hasher.field("repodata", repodata);
+        hasher.field("locked", self.locked_packages.iter().map(record_identity));
+        hasher.field("pinned", self.pinned_packages.iter().map(record_identity));
+        hasher.display_field("virtual", &self.virtual_packages);
+        hasher.display_field("specs", &self.specs);
+        hasher.display_field("constraints", &self.constraints);
+        // Selecting no features excludes records with features, unlike not
+        // selecting features at all, so the field is only hashed when present.
+        if let Some(features) = &self.features {
+            hasher.field("features", features);
+        }
+        hasher.field(
+            "preferences",
+            self.preferences
+                .iter()
+                .map(|preference| format!("{} {}", preference.weight, preference.spec)),
+        );
+        hasher.field(
+            "channel-priority",
+            [match self.channel_priority {
+                ChannelPriority::Strict => "strict",
+                ChannelPriority::Flexible => "flexible",
+                ChannelPriority::Disabled => "disabled",
+            }],
+        );
+        hasher.display_field(
+            "exclude-newer",
+            self.exclude_newer.map(|date| date.timestamp_millis()),
+        );
+        hasher.field(
+            "strategy",
+            [match self.strategy {
+                SolveStrategy::Highest => "highest",
+                SolveStrategy::LowestVersion => "lowest-version",
+                SolveStrategy::LowestVersionDirect => "lowest-version-direct",
+            }],
+        );
+        hasher.0.finalize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use rattler_conda_types::{GenericVirtualPackage, MatchSpec, ParseStrictness};
+
+    use super::*;
+    use crate::RepoDataIter;
+
+    fn task(specs: &[&str]) -> SolverTask<Vec<RepoDataIter<Vec<&'static RepoDataRecord>>>> {
+        SolverTask {
+            specs: specs
+                .iter()
+                .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Lenient).unwrap())
+                .collect(),
+            ..SolverTask::from_iter(Vec::<Vec<&RepoDataRecord>>::new())
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = task(&["python >=3.10", "numpy"]).fingerprint(["conda-forge"]);
+
+        // The fingerprint is deterministic.
+        assert_eq!(
+            fingerprint,
+            task(&["python >=3.10", "numpy"]).fingerprint(["conda-forge"])
+        );
+
+        // Everything that influences the solution changes the fingerprint.
+        assert_ne!(
+            fingerprint,
+            task(&["python >=3.10"]).fingerprint(["conda-forge"])
+        );
+        assert_ne!(
+            fingerprint,
+            task(&["python >=3.10", "numpy"]).fingerprint(["conda-forge-2"])
+        );
+        assert_ne!(
+            fingerprint,
+            task(&["python >=3.10", "numpy"]).fingerprint(["conda-", "forge"])
+        );
+        assert_ne!(
+            fingerprint,
+            SolverTask {
+                virtual_packages: vec![GenericVirtualPackage {
+                    name: "__glibc".parse().unwrap(),
+                    version: "2.17".parse().unwrap(),
+                    build_string: String::from("0"),
+                }],
+                ..task(&["python >=3.10", "numpy"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+        assert_ne!(
+            fingerprint,
+            SolverTask {
+                channel_priority: ChannelPriority::Disabled,
+                ..task(&["python >=3.10", "numpy"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+        assert_ne!(
+            fingerprint,
+            SolverTask {
+                strategy: SolveStrategy::LowestVersion,
+                ..task(&["python >=3.10", "numpy"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+        assert_ne!(
+            fingerprint,
+            SolverTask {
+                exclude_newer: Some(chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+                ..task(&["python >=3.10", "numpy"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+
+        // Moving a spec to the constraints changes the fingerprint.
+        assert_ne!(
+            fingerprint,
+            SolverTask {
+                constraints: vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()],
+                ..task(&["python >=3.10"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+
+        // The timeout does not change the solution.
+        assert_eq!(
+            fingerprint,
+            SolverTask {
+                timeout: Some(std::time::Duration::from_secs(1)),
+                ..task(&["python >=3.10", "numpy"])
+            }
+            .fingerprint(["conda-forge"])
+        );
+    }
+}
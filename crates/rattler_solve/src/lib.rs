@@ -4,15 +4,26 @@
 
 #![deny(missing_docs)]
 
+mod explanation;
+mod fingerprint;
 #[cfg(feature = "libsolv_c")]
 pub mod libsolv_c;
 #[cfg(feature = "resolvo")]
 pub mod resolvo;
+mod unsolvable;
+mod update;
 
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 use chrono::{DateTime, Utc};
-use rattler_conda_types::{GenericVirtualPackage, MatchSpec, RepoDataRecord};
+pub use explanation::{InclusionReason, SolveExplanation};
+use rattler_conda_types::{
+    GenericVirtualPackage, MatchSpec, Matches, PackageName, PackageRecord, Platform, RepoDataRecord,
+};
+pub use unsolvable::{
+    ConflictEdge, ConflictEdgeKind, ConflictNode, ConflictPackage, UnsolvableExplanation,
+};
+pub use update::{PackageChange, UpdateSolution};
 
 /// Represents a solver implementation, capable of solving [`SolverTask`]s
 pub trait SolverImpl {
@@ -29,13 +40,220 @@ pub trait SolverImpl {
         &mut self,
         task: SolverTask<TAvailablePackagesIterator>,
     ) -> Result<Vec<RepoDataRecord>, SolveError>;
+
+    /// Resolve the dependencies like [`SolverImpl::solve`] and also return a
+    /// [`SolveExplanation`] that describes why each of the returned records
+    /// was likely included.
+    ///
+    /// The explanation is not derived from the decisions of the solver, it is
+    /// inferred afterwards by matching the specs and the dependencies of the
+    /// returned records against the solution, see
+    /// [`SolveExplanation::infer_from_solution`].
+    fn solve_with_inferred_explanation<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+    ) -> Result<(Vec<RepoDataRecord>, SolveExplanation), SolveError> {
+        let specs = task.specs.clone();
+        let records = self.solve(task)?;
+        let explanation = SolveExplanation::infer_from_solution(&specs, &records);
+        Ok((records, explanation))
+    }
+
+    /// Updates the packages in `update` while changing as few of the other
+    /// packages in [`SolverTask::locked_packages`] as possible.
+    ///
+    /// The packages in `update` are first solved without any locked packages
+    /// to determine the versions they can be updated to. Then the locked
+    /// records of the other packages are kept by requiring exactly these
+    /// records in addition to [`SolverTask::specs`]. Requirements are enforced
+    /// by every backend, unlike [`SolverTask::constraints`] which some backends
+    /// only treat as preferences. If that makes the task unsolvable, the packages in `update` that
+    /// are part of the conflict may first be updated to any version newer than
+    /// their locked version. If the task is still unsolvable the constraints of
+    /// the other packages that are part of the conflict are released, until a
+    /// solution is found. The other packages that had to change are reported
+    /// in [`UpdateSolution::forced_changes`].
+    fn solve_update<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+        update: &[PackageName],
+    ) -> Result<UpdateSolution, SolveError>
+    where
+        Self::RepoData<'a>: Clone,
+    {
+        let repodata = task
+            .available_packages
+            .into_iter()
+            .map(IntoRepoData::into)
+            .collect::<Vec<Self::RepoData<'a>>>();
+        let attempt = |requirements: Vec<MatchSpec>| SolverTask {
+            available_packages: repodata.clone(),
+            locked_packages: Vec::new(),
+            pinned_packages: task.pinned_packages.clone(),
+            virtual_packages: task.virtual_packages.clone(),
+            specs: task.specs.iter().cloned().chain(requirements).collect(),
+            constraints: task.constraints.clone(),
+            features: task.features.clone(),
+            preferences: task.preferences.clone(),
+            timeout: task.timeout,
+            channel_priority: task.channel_priority,
+            exclude_newer: task.exclude_newer,
+            strategy: task.strategy,
+        };
+
+        // Determine the versions that the packages can be updated to. If the latest
+        // version conflicts with the kept records, an older version that is still
+        // newer than the locked record is tried first.
+        let mut target_constraints = self
+            .solve(attempt(Vec::new()))?
+            .iter()
+            .filter(|record| update.contains(&record.package_record.name))
+            .map(|record| {
+                let relaxed = task
+                    .locked_packages
+                    .iter()
+                    .find(|locked| locked.package_record.name == record.package_record.name)
+                    .filter(|locked| locked.package_record.version < record.package_record.version)
+                    .map(update::newer_than);
+                (
+                    record.package_record.name.clone(),
+                    update::at_least(record),
+                    relaxed,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Keep the locked records of the other packages.
+        let mut kept = task
+            .locked_packages
+            .iter()
+            .filter(|record| !update.contains(&record.package_record.name))
+            .collect::<Vec<_>>();
+
+        let records = loop {
+            let requirements = target_constraints
+                .iter()
+                .map(|(_, constraint, _)| constraint.clone())
+                .chain(kept.iter().map(|record| update::exactly(record)))
+                .collect();
+            match self.solve(attempt(requirements)) {
+                Ok(records) => break records,
+                Err(SolveError::Unsolvable(explanation)) if !kept.is_empty() => {
+                    let conflicting = update::conflicting_packages(&explanation);
+
+                    // Relax the constraints of the updated packages that are part of the
+                    // conflict before releasing any of the kept records.
+                    let mut relaxed_any = false;
+                    for (name, constraint, relaxed) in &mut target_constraints {
+                        if conflicting.contains(name.as_normalized()) {
+                            if let Some(relaxed) = relaxed.take() {
+                                *constraint = relaxed;
+                                relaxed_any = true;
+                            }
+                        }
+                    }
+                    if relaxed_any {
+                        continue;
+                    }
+
+                    // Release the kept records that are part of the conflict, or all of them
+                    // if the conflict does not involve any of them.
+                    let remaining = kept
+                        .iter()
+                        .copied()
+                        .filter(|record| {
+                            !conflicting.contains(record.package_record.name.as_normalized())
+                        })
+                        .collect::<Vec<_>>();
+                    kept = if remaining.len() == kept.len() {
+                        Vec::new()
+                    } else {
+                        remaining
+                    };
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        let forced_changes = update::forced_changes(&task.locked_packages, &records, update);
+        Ok(UpdateSolution {
+            records,
+            forced_changes,
+        })
+    }
+
+    /// Resolve the dependencies of a [`MultiPlatformSolverTask`] for each of
+    /// its platforms and return the [`RepoDataRecord`]s that should be present
+    /// in the environment of each platform.
+    ///
+    /// The available packages are only collected once and are shared between
+    /// the platforms. The default implementation solves each platform with
+    /// [`SolverImpl::solve`], backends may override this to also share their
+    /// internal state between the platforms.
+    fn solve_multi_platform<'a, R, TAvailablePackagesIterator>(
+        &mut self,
+        task: MultiPlatformSolverTask<TAvailablePackagesIterator>,
+    ) -> Result<HashMap<Platform, Vec<RepoDataRecord>>, MultiPlatformSolveError>
+    where
+        R: IntoIterator<Item = &'a RepoDataRecord>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    {
+        let repodata = task
+            .available_packages
+            .into_iter()
+            .map(|records| records.into_iter().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let mut solutions = HashMap::with_capacity(task.platforms.len());
+        for target in task.platforms {
+            let platform = target.platform;
+            let available_packages = repodata
+                .iter()
+                .map(|records| {
+                    records
+                        .iter()
+                        .copied()
+                        .filter(|record| target.is_available(record))
+                        .collect::<Self::RepoData<'a>>()
+                })
+                .collect::<Vec<_>>();
+            let records = self
+                .solve(SolverTask {
+                    available_packages,
+                    locked_packages: target.locked_packages,
+                    pinned_packages: target.pinned_packages,
+                    virtual_packages: target.virtual_packages,
+                    specs: task.specs.clone(),
+                    constraints: task.constraints.clone(),
+                    features: task.features.clone(),
+                    preferences: task.preferences.clone(),
+                    timeout: task.timeout,
+                    channel_priority: task.channel_priority,
+                    exclude_newer: task.exclude_newer,
+                    strategy: task.strategy,
+                })
+                .map_err(|error| MultiPlatformSolveError { platform, error })?;
+            solutions.insert(platform, records);
+        }
+
+        Ok(solutions)
+    }
 }
 
 /// Represents an error when solving the dependencies for a given environment
 #[derive(thiserror::Error, Debug)]
 pub enum SolveError {
-    /// There is no set of dependencies that satisfies the requirements
-    Unsolvable(Vec<String>),
+    /// There is no set of dependencies that satisfies the requirements. The
+    /// explanation describes which requirements conflict and why.
+    Unsolvable(UnsolvableExplanation),
 
     /// The solver backend returned operations that we dont know how to install.
     /// Each string is a somewhat user-friendly representation of which
@@ -56,11 +274,11 @@ pub enum SolveError {
 impl fmt::Display for SolveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SolveError::Unsolvable(operations) => {
+            SolveError::Unsolvable(explanation) => {
                 write!(
                     f,
                     "Cannot solve the request because of: {}",
-                    operations.join(", ")
+                    explanation.messages.join(", ")
                 )
             }
             SolveError::UnsupportedOperations(operations) => {
@@ -79,6 +297,19 @@ impl fmt::Display for SolveError {
     }
 }
 
+/// Represents an error when solving the dependencies of a
+/// [`MultiPlatformSolverTask`] for one of its platforms.
+#[derive(thiserror::Error, Debug)]
+#[error("failed to solve the environment for {platform}")]
+pub struct MultiPlatformSolveError {
+    /// The platform for which the solve failed
+    pub platform: Platform,
+
+    /// The reason the solve failed
+    #[source]
+    pub error: SolveError,
+}
+
 /// Represents the channel priority option to use during solves.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -89,10 +320,12 @@ pub enum ChannelPriority {
     #[default]
     Strict,
 
-    // Conda also has "Flexible" as an option, where packages present in multiple channels
-    // are only taken from lower-priority channels when this prevents unsatisfiable environment
-    // errors, but this would need implementation in the solvers.
-    // Flexible,
+    /// Packages present in multiple channels are preferably taken from the
+    /// channel with the highest priority. Packages from lower-priority
+    /// channels are only used when the higher-priority channels cannot
+    /// satisfy the requirements.
+    Flexible,
+
     /// Packages can be retrieved from any channel as package version takes
     /// precedence.
     Disabled,
@@ -135,11 +368,29 @@ pub struct SolverTask<TAvailablePackagesIterator> {
     /// installed, but they must be satisfied by the solution.
     pub constraints: Vec<MatchSpec>,
 
+    /// The legacy features that are selected (e.g. `mkl`), or `None` if no
+    /// features were selected.
+    ///
+    /// When features are selected, records that require a feature through
+    /// [`PackageRecord::features`] are only considered if all of their
+    /// features are selected, and the `resolvo` backend prefers records that
+    /// require a selected feature over records that don't. When `None`, the
+    /// features of records are ignored.
+    pub features: Option<Vec<String>>,
+
+    /// Soft preferences for candidates that match specific specs.
+    ///
+    /// Preferences never exclude candidates, the solver only tries the
+    /// candidates that match a preference before the candidates that don't.
+    /// Locked packages are still preferred over the candidates that match a
+    /// preference.
+    pub preferences: Vec<Preference>,
+
     /// The timeout after which the solver should stop
     pub timeout: Option<std::time::Duration>,
 
-    /// The channel priority to solve with, either [`ChannelPriority::Strict`]
-    /// or [`ChannelPriority::Disabled`]
+    /// The channel priority to solve with, either [`ChannelPriority::Strict`],
+    /// [`ChannelPriority::Flexible`] or [`ChannelPriority::Disabled`]
     pub channel_priority: ChannelPriority,
 
     /// Exclude any package that has a timestamp newer than the specified
@@ -161,6 +412,71 @@ impl<'r, I: IntoIterator<Item = &'r RepoDataRecord>> FromIterator<I>
             virtual_packages: Vec::new(),
             specs: Vec::new(),
             constraints: Vec::new(),
+            features: None,
+            preferences: Vec::new(),
+            timeout: None,
+            channel_priority: ChannelPriority::default(),
+            exclude_newer: None,
+            strategy: SolveStrategy::default(),
+        }
+    }
+}
+
+/// Represents a dependency resolution task that resolves the same specs for
+/// multiple platforms, to be solved by one of the backends.
+///
+/// The available packages contain the records of all the platforms (e.g. the
+/// `linux-64`, `win-64` and `noarch` subdirs of a channel). When solving for a
+/// platform only the records of its subdir and of the `noarch` subdir are
+/// considered.
+pub struct MultiPlatformSolverTask<TAvailablePackagesIterator> {
+    /// An iterator over all available packages of all platforms
+    pub available_packages: TAvailablePackagesIterator,
+
+    /// The platforms to solve for
+    pub platforms: Vec<TargetPlatform>,
+
+    /// The specs we want to solve
+    pub specs: Vec<MatchSpec>,
+
+    /// Additional constraints that should be satisfied by the solver.
+    /// Packages included in the `constraints` are not necessarily
+    /// installed, but they must be satisfied by the solution.
+    pub constraints: Vec<MatchSpec>,
+
+    /// The legacy features that are enabled (e.g. `mkl`), see
+    /// [`SolverTask::features`].
+    pub features: Option<Vec<String>>,
+
+    /// Soft preferences for candidates that match specific specs, see
+    /// [`SolverTask::preferences`].
+    pub preferences: Vec<Preference>,
+
+    /// The timeout after which the solver should stop, the timeout applies to
+    /// each platform individually
+    pub timeout: Option<std::time::Duration>,
+
+    /// The channel priority to solve with, either [`ChannelPriority::Strict`],
+    /// [`ChannelPriority::Flexible`] or [`ChannelPriority::Disabled`]
+    pub channel_priority: ChannelPriority,
+
+    /// Exclude any package that has a timestamp newer than the specified
+    /// timestamp.
+    pub exclude_newer: Option<DateTime<Utc>>,
+
+    /// The solve strategy.
+    pub strategy: SolveStrategy,
+}
+
+impl<I: IntoIterator> FromIterator<I> for MultiPlatformSolverTask<Vec<I>> {
+    fn from_iter<T: IntoIterator<Item = I>>(iter: T) -> Self {
+        Self {
+            available_packages: iter.into_iter().collect(),
+            platforms: Vec::new(),
+            specs: Vec::new(),
+            constraints: Vec::new(),
+            features: None,
+            preferences: Vec::new(),
             timeout: None,
             channel_priority: ChannelPriority::default(),
             exclude_newer: None,
@@ -169,6 +485,123 @@ impl<'r, I: IntoIterator<Item = &'r RepoDataRecord>> FromIterator<I>
     }
 }
 
+/// A soft preference for the candidates that match a spec.
+///
+/// For example, a preference for `pytorch * *cuda*` prefers the CUDA builds of
+/// pytorch and a preference for `conda-forge::numpy` prefers numpy from
+/// conda-forge, without excluding the alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preference {
+    /// The spec that the preferred candidates match
+    pub spec: MatchSpec,
+
+    /// The weight of the preference. The weights of all the preferences that a
+    /// candidate matches are summed, candidates with a higher total weight are
+    /// tried first.
+    pub weight: u32,
+}
+
+impl Preference {
+    /// Constructs a preference for the candidates that match `spec` with a
+    /// weight of 1.
+    pub fn new(spec: MatchSpec) -> Self {
+        Self { spec, weight: 1 }
+    }
+
+    /// Sets the weight of the preference.
+    #[must_use]
+    pub fn with_weight(self, weight: u32) -> Self {
+        Self { weight, ..self }
+    }
+
+    /// Returns true if the record matches the spec of the preference. Unlike
+    /// [`MatchSpec::matches`] this also takes the channel of the spec into
+    /// account.
+    pub fn matches(&self, record: &RepoDataRecord) -> bool {
+        if let Some(channel) = &self.spec.channel {
+            if record.channel != channel.canonical_name() {
+                return false;
+            }
+        }
+        self.spec.matches(record)
+    }
+}
+
+impl From<MatchSpec> for Preference {
+    fn from(spec: MatchSpec) -> Self {
+        Self::new(spec)
+    }
+}
+
+/// Returns the total weight of the preferences that the record matches.
+pub(crate) fn preference_weight(preferences: &[Preference], record: &RepoDataRecord) -> u32 {
+    preferences
+        .iter()
+        .filter(|preference| preference.matches(record))
+        .fold(0, |weight, preference| {
+            weight.saturating_add(preference.weight)
+        })
+}
+
+/// A platform to solve a [`MultiPlatformSolverTask`] for, together with the
+/// information that is specific to that platform.
+#[derive(Debug, Clone)]
+pub struct TargetPlatform {
+    /// The platform to solve for
+    pub platform: Platform,
+
+    /// Virtual packages considered active on this platform
+    pub virtual_packages: Vec<GenericVirtualPackage>,
+
+    /// Records of packages that are previously selected for this platform, see
+    /// [`SolverTask::locked_packages`].
+    pub locked_packages: Vec<RepoDataRecord>,
+
+    /// Records of packages that are previously selected for this platform and
+    /// CANNOT be changed, see [`SolverTask::pinned_packages`].
+    pub pinned_packages: Vec<RepoDataRecord>,
+}
+
+impl TargetPlatform {
+    /// Returns true if the record can be installed on this platform, i.e. if
+    /// it originates from the subdir of the platform or from `noarch`.
+    pub fn is_available(&self, record: &RepoDataRecord) -> bool {
+        let subdir = record.package_record.subdir.as_str();
+        subdir == self.platform.as_str() || subdir == Platform::NoArch.as_str()
+    }
+}
+
+impl From<Platform> for TargetPlatform {
+    fn from(platform: Platform) -> Self {
+        Self {
+            platform,
+            virtual_packages: Vec::new(),
+            locked_packages: Vec::new(),
+            pinned_packages: Vec::new(),
+        }
+    }
+}
+
+/// Returns the legacy features of a record, see [`PackageRecord::features`].
+/// Features are separated by whitespace or commas.
+pub(crate) fn record_features(record: &PackageRecord) -> impl Iterator<Item = &str> {
+    record
+        .features
+        .as_deref()
+        .unwrap_or_default()
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|feature| !feature.is_empty())
+}
+
+/// Returns the first legacy feature of a record that is not enabled, records
+/// with such a feature should not be installed.
+pub(crate) fn disabled_feature<'r>(
+    record: &'r PackageRecord,
+    enabled_features: &[String],
+) -> Option<&'r str> {
+    record_features(record).find(|feature| !enabled_features.iter().any(|f| f == feature))
+}
+
 /// Represents the strategy to use when solving dependencies
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -201,7 +634,10 @@ pub enum SolveStrategy {
 ///
 /// Some solvers may add additional functionality to their specific
 /// implementation that enables caching the repodata to disk in an efficient way
-/// (see [`crate::libsolv_c::RepoData`] for an example).
+/// (see [`crate::libsolv_c::RepoData`] for an example). The
+/// [`crate::resolvo::SolverSession`] instead caches the parsed dependencies of
+/// the records, reusing a session for multiple tasks avoids parsing them
+/// again.
 pub trait SolverRepoData<'a>: FromIterator<&'a RepoDataRecord> {}
 
 /// Defines the ability to convert a type into [`SolverRepoData`].
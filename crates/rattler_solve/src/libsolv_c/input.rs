@@ -21,7 +21,7 @@ use super::{
         solvable::SolvableId,
     },
 };
-use crate::SolveError;
+use crate::{disabled_feature, SolveError};
 
 #[cfg(not(target_family = "unix"))]
 /// Adds solvables to a repo from an in-memory .solv file
@@ -49,13 +49,18 @@ pub fn add_solv_file(pool: &Pool, repo: &Repo<'_>, solv_bytes: &LibcByteSlice) {
 
 /// Adds [`RepoDataRecord`] to `repo`
 ///
+/// Records that are newer than `exclude_newer` or that require a legacy
+/// feature which is not in `enabled_features` are skipped. Returns the
+/// solvables that were added together with their record.
+///
 /// Panics if the repo does not belong to the pool
 pub fn add_repodata_records<'a>(
     pool: &Pool,
     repo: &Repo<'_>,
     repo_data: impl IntoIterator<Item = &'a RepoDataRecord>,
     exclude_newer: Option<&DateTime<Utc>>,
-) -> Result<Vec<SolvableId>, SolveError> {
+    enabled_features: Option<&[String]>,
+) -> Result<Vec<(SolvableId, &'a RepoDataRecord)>, SolveError> {
     // Sanity check
     repo.ensure_belongs_to_pool(pool);
 
@@ -91,6 +96,13 @@ pub fn add_repodata_records<'a>(
             _ => {}
         }
 
+        // Skip packages that require a legacy feature that is not enabled
+        if let Some(enabled_features) = enabled_features {
+            if disabled_feature(&repo_data.package_record, enabled_features).is_some() {
+                continue;
+            }
+        }
+
         // Create a solvable for the package
         let solvable_id =
             match add_or_reuse_solvable(pool, repo, &data, &mut package_to_type, repo_data)? {
@@ -203,7 +215,7 @@ pub fn add_repodata_records<'a>(
             );
         }
 
-        solvable_ids.push(solvable_id);
+        solvable_ids.push((solvable_id, repo_data));
     }
 
     repo.internalize();
@@ -324,7 +336,7 @@ pub fn cache_repodata(
     // Add repodata to a new pool + repo
     let pool = Pool::default();
     let repo = Repo::new(&pool, url, channel_priority.unwrap_or(0));
-    add_repodata_records(&pool, &repo, data, None)?;
+    add_repodata_records(&pool, &repo, data, None, None)?;
 
     // Export repo to .solv in memory
     let mut stream_ptr = std::ptr::null_mut();
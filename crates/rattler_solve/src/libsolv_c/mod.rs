@@ -8,6 +8,7 @@ use std::{
 
 pub use input::cache_repodata;
 use input::{add_repodata_records, add_solv_file, add_virtual_packages};
+use itertools::Itertools;
 pub use libc_byte_slice::LibcByteSlice;
 use output::get_required_packages;
 use rattler_conda_types::{MatchSpec, NamelessMatchSpec, RepoDataRecord};
@@ -18,7 +19,10 @@ use wrapper::{
     solve_goal::SolveGoal,
 };
 
-use crate::{ChannelPriority, IntoRepoData, SolveError, SolveStrategy, SolverRepoData, SolverTask};
+use crate::{
+    preference_weight, ChannelPriority, IntoRepoData, SolveError, SolveStrategy, SolverRepoData,
+    SolverTask,
+};
 
 mod input;
 mod libc_byte_slice;
@@ -125,12 +129,14 @@ impl super::SolverImpl for Solver {
         // Determine the channel priority for each channel in the repodata in the order
         // in which the repodatas are passed, where the first channel will have
         // the highest priority value and each successive channel will descend
-        // in priority value. If not strict, the highest priority value will be
+        // in priority value. If disabled, the highest priority value will be
         // 0 and the channel priority map will not be populated as it will
         // not be used.
         let mut highest_priority: i32 = 0;
         let channel_priority: HashMap<String, i32> =
-            if task.channel_priority == ChannelPriority::Strict {
+            if task.channel_priority == ChannelPriority::Disabled {
+                HashMap::new()
+            } else {
                 let mut seen_channels = HashSet::new();
                 let mut channel_order: Vec<String> = Vec::new();
                 for channel in repodatas
@@ -152,8 +158,6 @@ impl super::SolverImpl for Solver {
                     channel_priority.insert(channel.clone(), reverse_index as i32);
                 }
                 channel_priority
-            } else {
-                HashMap::new()
             };
 
         // Add virtual packages
@@ -166,6 +170,7 @@ impl super::SolverImpl for Solver {
         // Create repos for all channel + platform combinations
         let mut repo_mapping = HashMap::new();
         let mut all_repodata_records = Vec::new();
+        let mut preference_weights = HashMap::new();
         for repodata in repodatas.iter() {
             if repodata.records.is_empty() {
                 continue;
@@ -173,22 +178,32 @@ impl super::SolverImpl for Solver {
             let channel_name = &repodata.records[0].channel;
 
             // We dont want to drop the Repo, its stored in the pool anyway.
-            let priority: i32 = if task.channel_priority == ChannelPriority::Strict {
-                *channel_priority.get(channel_name).unwrap()
-            } else {
+            let priority: i32 = if task.channel_priority == ChannelPriority::Disabled {
                 0
+            } else {
+                *channel_priority.get(channel_name).unwrap()
             };
             let repo = ManuallyDrop::new(Repo::new(&pool, channel_name, priority));
 
             if let Some(solv_file) = repodata.solv_file {
                 add_solv_file(&pool, &repo, solv_file);
             } else {
-                add_repodata_records(
+                let solvables = add_repodata_records(
                     &pool,
                     &repo,
                     repodata.records.iter().copied(),
                     task.exclude_newer.as_ref(),
+                    task.features.as_deref(),
                 )?;
+
+                // Later records replace the solvables of earlier records with a worse archive
+                // type, so the weight of the last record of a solvable is used.
+                if !task.preferences.is_empty() {
+                    for (solvable, record) in solvables {
+                        preference_weights
+                            .insert(solvable, preference_weight(&task.preferences, record));
+                    }
+                }
             }
 
             // Keep our own info about repodata_records
@@ -198,7 +213,8 @@ impl super::SolverImpl for Solver {
 
         // Create a special pool for records that are already installed or locked.
         let repo = Repo::new(&pool, "locked", highest_priority);
-        let installed_solvables = add_repodata_records(&pool, &repo, &task.locked_packages, None)?;
+        let installed_solvables =
+            add_repodata_records(&pool, &repo, &task.locked_packages, None, None)?;
 
         // Also add the installed records to the repodata
         repo_mapping.insert(repo.id(), repo_mapping.len());
@@ -206,7 +222,8 @@ impl super::SolverImpl for Solver {
 
         // Create a special pool for records that are pinned and cannot be changed.
         let repo = Repo::new(&pool, "pinned", highest_priority);
-        let pinned_solvables = add_repodata_records(&pool, &repo, &task.pinned_packages, None)?;
+        let pinned_solvables =
+            add_repodata_records(&pool, &repo, &task.pinned_packages, None, None)?;
 
         // Also add the installed records to the repodata
         repo_mapping.insert(repo.id(), repo_mapping.len());
@@ -218,13 +235,29 @@ impl super::SolverImpl for Solver {
         // Add matchspec to the queue
         let mut goal = SolveGoal::default();
 
-        // Favor the currently installed packages
-        for favor_solvable in installed_solvables {
+        // Favor the candidates that match the preferences. Solvables that are favored
+        // later take precedence, so the solvables with the same weight are favored
+        // together in the order of increasing weight.
+        let preferred_solvables = preference_weights
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0)
+            .into_group_map_by(|&(_, weight)| weight);
+        for (_, solvables) in preferred_solvables.into_iter().sorted_by_key(|&(w, _)| w) {
+            let solvables = solvables
+                .into_iter()
+                .map(|(solvable, _)| solvable)
+                .collect::<Vec<_>>();
+            goal.favor_all(&pool, &solvables);
+        }
+
+        // Favor the currently installed packages, these take precedence over the
+        // preferences.
+        for (favor_solvable, _) in installed_solvables {
             goal.favor(favor_solvable);
         }
 
         // Lock the currently pinned packages
-        for locked_solvable in pinned_solvables {
+        for (locked_solvable, _) in pinned_solvables {
             goal.lock(locked_solvable);
         }
 
@@ -242,10 +275,10 @@ impl super::SolverImpl for Solver {
         // Add virtual packages to the queue. We want to install these as part of the
         // solution as well. This ensures that if a package only has a constraint on a
         // virtual package, the virtual package is installed.
-        for virtual_package in task.virtual_packages {
+        for virtual_package in &task.virtual_packages {
             let id = pool.intern_matchspec(&MatchSpec::from_nameless(
                 NamelessMatchSpec::default(),
-                Some(virtual_package.name),
+                Some(virtual_package.name.clone()),
             ));
             goal.install(id, false);
         }
@@ -254,12 +287,26 @@ impl super::SolverImpl for Solver {
         let mut solver = pool.create_solver();
         solver.set_flag(SolverFlag::allow_uninstall(), true);
         solver.set_flag(SolverFlag::allow_downgrade(), true);
+        // With flexible channel priority the repo priorities are only used to prefer
+        // candidates, libsolv can still fall back to lower priority channels.
         solver.set_flag(
             SolverFlag::strict_channel_priority(),
             task.channel_priority == ChannelPriority::Strict,
         );
 
-        let transaction = solver.solve(&mut goal).map_err(SolveError::Unsolvable)?;
+        let transaction = match solver.solve(&mut goal) {
+            Ok(transaction) => transaction,
+            Err((messages, problems)) => {
+                return Err(SolveError::Unsolvable(output::explain_problems(
+                    &pool,
+                    &repo_mapping,
+                    problems,
+                    messages,
+                    &all_repodata_records,
+                    &task.virtual_packages,
+                )));
+            }
+        };
 
         let required_records = get_required_packages(
             &pool,
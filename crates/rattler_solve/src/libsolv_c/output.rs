@@ -5,11 +5,15 @@ use super::{
     wrapper::pool::{Pool, StringId},
     wrapper::repo::RepoId,
     wrapper::solvable::SolvableId,
+    wrapper::solve_problem::SolveProblem,
     wrapper::transaction::Transaction,
     wrapper::{ffi, solvable},
 };
-use rattler_conda_types::RepoDataRecord;
-use std::collections::HashMap;
+use crate::unsolvable::{
+    ConflictEdgeKind, ConflictGraphBuilder, ConflictNode, ConflictPackage, UnsolvableExplanation,
+};
+use rattler_conda_types::{GenericVirtualPackage, RepoDataRecord};
+use std::{collections::HashMap, ffi::CStr};
 
 /// Returns which packages should be installed in the environment
 ///
@@ -59,6 +63,134 @@ pub fn get_required_packages(
     Ok(required_packages)
 }
 
+/// Converts the problems that libsolv encountered into an
+/// [`UnsolvableExplanation`].
+pub fn explain_problems(
+    pool: &Pool,
+    repo_mapping: &HashMap<RepoId, usize>,
+    problems: Vec<SolveProblem>,
+    messages: Vec<String>,
+    repodata_records: &[Vec<&RepoDataRecord>],
+    virtual_packages: &[GenericVirtualPackage],
+) -> UnsolvableExplanation {
+    let solvable_index_id = pool
+        .find_interned_str("solvable:repodata_record_index")
+        .unwrap();
+
+    let mut builder = ConflictGraphBuilder::new();
+    let mut add_package = |builder: &mut ConflictGraphBuilder, id: SolvableId| {
+        let package = if let Some((repo_index, solvable_index)) =
+            get_solvable_indexes(pool, repo_mapping, solvable_index_id, id)
+        {
+            ConflictPackage::from_record(repodata_records[repo_index][solvable_index])
+        } else {
+            // Solvables without a record are virtual packages
+            let name = solvable_name(pool, id);
+            virtual_packages
+                .iter()
+                .find(|package| package.name.as_normalized() == name)
+                .map_or_else(
+                    || ConflictPackage {
+                        name,
+                        version: String::new(),
+                        build: String::new(),
+                        channel: None,
+                    },
+                    ConflictPackage::from_virtual_package,
+                )
+        };
+        builder.add_node(ConflictNode::Package(package))
+    };
+
+    for problem in problems {
+        match problem {
+            SolveProblem::Job { dep, candidates } | SolveProblem::Pkg { dep, candidates } => {
+                add_requirement(&mut builder, &mut add_package, 0, dep, candidates);
+            }
+            SolveProblem::JobNothingProvidesDep { dep }
+            | SolveProblem::JobUnknownPackage { dep } => {
+                add_requirement(&mut builder, &mut add_package, 0, dep, Vec::new());
+            }
+            SolveProblem::PkgRequires {
+                source,
+                dep,
+                candidates,
+            } => {
+                let source = add_package(&mut builder, source);
+                add_requirement(&mut builder, &mut add_package, source, dep, candidates);
+            }
+            SolveProblem::PkgNothingProvidesDep { source, dep } => {
+                let source = add_package(&mut builder, source);
+                add_requirement(&mut builder, &mut add_package, source, dep, Vec::new());
+            }
+            SolveProblem::PkgConflicts { source, target }
+            | SolveProblem::PkgSameName { source, target } => {
+                let source = add_package(&mut builder, source);
+                let target = add_package(&mut builder, target);
+                builder.add_edge(source, target, ConflictEdgeKind::Conflict);
+            }
+            SolveProblem::PkgConstrains {
+                source,
+                target,
+                dep,
+            } => {
+                let source = add_package(&mut builder, source);
+                let target = add_package(&mut builder, target);
+                builder.add_edge(source, target, ConflictEdgeKind::Constrains { spec: dep });
+            }
+            SolveProblem::PkgNotInstallable { source } => {
+                let source = add_package(&mut builder, source);
+                let reason = builder.add_node(ConflictNode::Excluded {
+                    reason: String::from("the package is not installable"),
+                });
+                builder.add_edge(source, reason, ConflictEdgeKind::Excluded);
+            }
+            SolveProblem::StrictRepoPriority { source } => {
+                let source = add_package(&mut builder, source);
+                let reason = builder.add_node(ConflictNode::Excluded {
+                    reason: String::from("excluded by strict repo priority"),
+                });
+                builder.add_edge(source, reason, ConflictEdgeKind::Excluded);
+            }
+            SolveProblem::Update | SolveProblem::Other => {}
+        }
+    }
+
+    builder.finish(messages)
+}
+
+/// Adds a requirement from `source` on `dep` to the graph. If there are no
+/// candidates the requirement points to [`ConflictNode::Missing`].
+fn add_requirement(
+    builder: &mut ConflictGraphBuilder,
+    add_package: &mut impl FnMut(&mut ConflictGraphBuilder, SolvableId) -> usize,
+    source: usize,
+    dep: String,
+    candidates: Vec<SolvableId>,
+) {
+    if candidates.is_empty() {
+        let missing = builder.add_node(ConflictNode::Missing);
+        builder.add_edge(source, missing, ConflictEdgeKind::Requires { spec: dep });
+    } else {
+        for candidate in candidates {
+            let target = add_package(builder, candidate);
+            builder.add_edge(
+                source,
+                target,
+                ConflictEdgeKind::Requires { spec: dep.clone() },
+            );
+        }
+    }
+}
+
+/// Returns the name of a solvable
+fn solvable_name(pool: &Pool, id: SolvableId) -> String {
+    let solvable = id.resolve_raw(pool);
+    // Safe because the name of a solvable is always a valid string id
+    let name = unsafe { CStr::from_ptr(ffi::pool_id2str(pool.as_ref(), solvable.as_ref().name)) };
+    name.to_string_lossy().into_owned()
+}
+
 fn get_solvable_indexes(
     pool: &Pool,
     repo_mapping: &HashMap<RepoId, usize>,
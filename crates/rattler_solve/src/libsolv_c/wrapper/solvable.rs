@@ -5,7 +5,7 @@ use super::{
 use std::ptr::NonNull;
 
 /// Represents a solvable in a [`Repo`] or [`Pool`]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct SolvableId(pub(super) ffi::Id);
 
 impl From<SolvableId> for ffi::Id {
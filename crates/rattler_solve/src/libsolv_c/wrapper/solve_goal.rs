@@ -2,9 +2,10 @@ use super::{
     ffi,
     ffi::{
         SOLVER_DISFAVOR, SOLVER_ERASE, SOLVER_FAVOR, SOLVER_INSTALL, SOLVER_LOCK, SOLVER_SOLVABLE,
-        SOLVER_SOLVABLE_PROVIDES, SOLVER_UPDATE, SOLVER_WEAK,
+        SOLVER_SOLVABLE_ONE_OF, SOLVER_SOLVABLE_PROVIDES, SOLVER_UPDATE, SOLVER_WEAK,
     },
-    pool::MatchSpecId,
+    pool::{MatchSpecId, Pool},
+    queue::Queue,
     solvable::SolvableId,
 };
 use std::os::raw::c_int;
@@ -78,6 +79,24 @@ impl SolveGoal {
         self.push_id_with_flags(solvable, SOLVER_SOLVABLE | SOLVER_FAVOR);
     }
 
+    /// Favor all the specified solvables equally over other variants. Solvables
+    /// that are favored by a later call are favored over the solvables of
+    /// earlier calls.
+    ///
+    /// The whatprovides index of the pool must have been created before calling
+    /// this function.
+    pub fn favor_all(&mut self, pool: &Pool, solvables: &[SolvableId]) {
+        let mut queue = Queue::<SolvableId>::default();
+        for &solvable in solvables {
+            queue.push_id(solvable);
+        }
+
+        // Safe because the pool and the queue are valid, the queue is copied into the
+        // pool.
+        let selection = unsafe { ffi::pool_queuetowhatprovides(pool.raw_ptr(), queue.raw_ptr()) };
+        self.push_id_with_flags(selection, SOLVER_SOLVABLE_ONE_OF | SOLVER_FAVOR);
+    }
+
     /// Lock the specified solvable over other variants. This implies that not other variant will
     /// ever be considered.
     pub fn lock(&mut self, solvable: SolvableId) {
@@ -9,8 +9,10 @@ use ffi::{
     SolverRuleinfo_SOLVER_RULE_PKG_CONFLICTS as SOLVER_RULE_SOLVER_RULE_PKG_CONFLICTS,
     SolverRuleinfo_SOLVER_RULE_PKG_CONSTRAINS as SOLVER_RULE_PKG_CONSTRAINS,
     SolverRuleinfo_SOLVER_RULE_PKG_NOTHING_PROVIDES_DEP as SOLVER_RULE_SOLVER_RULE_PKG_NOTHING_PROVIDES_DEP,
+    SolverRuleinfo_SOLVER_RULE_PKG_NOT_INSTALLABLE as SOLVER_RULE_PKG_NOT_INSTALLABLE,
     SolverRuleinfo_SOLVER_RULE_PKG_REQUIRES as SOLVER_RULE_PKG_REQUIRES,
     SolverRuleinfo_SOLVER_RULE_PKG_SAME_NAME as SOLVER_RULE_SOLVER_RULE_PKG_SAME_NAME,
+    SolverRuleinfo_SOLVER_RULE_STRICT_REPO_PRIORITY as SOLVER_RULE_STRICT_REPO_PRIORITY,
     SolverRuleinfo_SOLVER_RULE_UPDATE as SOLVER_RULE_SOLVER_RULE_UPDATE,
 };
 
@@ -18,7 +20,10 @@ use ffi::{
 pub enum SolveProblem {
     /// A top level requirement.
     /// The difference between JOB and PKG is unknown (possibly unused).
-    Job { dep: String },
+    Job {
+        dep: String,
+        candidates: Vec<SolvableId>,
+    },
     /// A top level dependency does not exist.
     /// Could be a wrong name or missing channel.
     JobNothingProvidesDep { dep: String },
@@ -27,7 +32,10 @@ pub enum SolveProblem {
     JobUnknownPackage { dep: String },
     /// A top level requirement.
     /// The difference between JOB and PKG is unknown (possibly unused).
-    Pkg { dep: String },
+    Pkg {
+        dep: String,
+        candidates: Vec<SolvableId>,
+    },
     /// Looking for a valid solution to the installation satisfiability expand to
     /// two solvables of same package that cannot be installed together. This is
     /// a partial explanation of why one of the solvables (could be any of the
@@ -54,32 +62,51 @@ pub enum SolveProblem {
     /// problem.
     /// Not all dependency of package will appear, only enough to explain the
     //. problem. It is not a problem in itself, only a part of the graph.
-    PkgRequires { source: SolvableId, dep: String },
+    PkgRequires {
+        source: SolvableId,
+        dep: String,
+        candidates: Vec<SolvableId>,
+    },
     /// Package conflict between two solvables of same package name (handled the same as
     /// [`SolveProblem::PkgConflicts`]).
     PkgSameName {
         source: SolvableId,
         target: SolvableId,
     },
+    /// The source package cannot be installed at all, for instance because
+    /// it is not compatible with the architecture.
+    PkgNotInstallable { source: SolvableId },
+    /// The source package was excluded because a package with the same name
+    /// is available from a channel with a higher priority.
+    StrictRepoPriority { source: SolvableId },
     /// Encountered in the problems list from libsolv but unknown.
     /// Explicitly ignored until we do something with it.
     Update,
+    /// A rule that does not contribute to the explanation of a problem.
+    Other,
 }
 
 impl SolveProblem {
     pub fn from_raw(
         problem_type: ffi::SolverRuleinfo,
         dep: Option<String>,
+        candidates: Vec<SolvableId>,
         source: Option<SolvableId>,
         target: Option<SolvableId>,
     ) -> Self {
         match problem_type {
-            SOLVER_RULE_JOB => Self::Job { dep: dep.unwrap() },
+            SOLVER_RULE_JOB => Self::Job {
+                dep: dep.unwrap(),
+                candidates,
+            },
             SOLVER_RULE_JOB_NOTHING_PROVIDES_DEP => {
                 Self::JobNothingProvidesDep { dep: dep.unwrap() }
             }
             SOLVER_RULE_JOB_UNKNOWN_PACKAGE => Self::JobUnknownPackage { dep: dep.unwrap() },
-            SOLVER_RULE_PKG => Self::Pkg { dep: dep.unwrap() },
+            SOLVER_RULE_PKG => Self::Pkg {
+                dep: dep.unwrap(),
+                candidates,
+            },
             SOLVER_RULE_SOLVER_RULE_PKG_CONFLICTS => Self::PkgConflicts {
                 source: source.unwrap(),
                 target: target.unwrap(),
@@ -96,13 +123,20 @@ impl SolveProblem {
             SOLVER_RULE_PKG_REQUIRES => Self::PkgRequires {
                 source: source.unwrap(),
                 dep: dep.unwrap(),
+                candidates,
             },
             SOLVER_RULE_SOLVER_RULE_PKG_SAME_NAME => Self::PkgSameName {
                 source: source.unwrap(),
                 target: target.unwrap(),
             },
+            SOLVER_RULE_PKG_NOT_INSTALLABLE => Self::PkgNotInstallable {
+                source: source.unwrap(),
+            },
+            SOLVER_RULE_STRICT_REPO_PRIORITY => Self::StrictRepoPriority {
+                source: source.unwrap(),
+            },
             SOLVER_RULE_SOLVER_RULE_UPDATE => Self::Update,
-            _ => panic!("Unknown problem type: {problem_type}"),
+            _ => Self::Other,
         }
     }
 }
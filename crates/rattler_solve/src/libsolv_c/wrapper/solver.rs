@@ -96,28 +96,36 @@ impl<'pool> Solver<'pool> {
 
                     let nsolvables = unsafe { (*pool).nsolvables };
 
-                    let target = if target_id < 0 || target_id >= nsolvables {
+                    let target = if target_id <= 0 || target_id >= nsolvables {
                         None
                     } else {
                         Some(SolvableId(target_id))
                     };
 
-                    let source = if source_id < 0 || source_id >= nsolvables {
+                    let source = if source_id <= 0 || source_id >= nsolvables {
                         None
                     } else {
-                        Some(SolvableId(target_id))
+                        Some(SolvableId(source_id))
                     };
 
-                    let dep = if dep_id == 0 {
-                        None
+                    let (dep, candidates) = if dep_id == 0 {
+                        (None, Vec::new())
                     } else {
                         let dep = unsafe { ffi::pool_dep2str(pool, dep_id) };
                         let dep = unsafe { CStr::from_ptr(dep) };
                         let dep = dep.to_str().expect("Invalid UTF8 value").to_string();
-                        Some(dep)
+                        // Safe because the dependency id was returned by libsolv
+                        let candidates = unsafe { whatprovides(pool, dep_id) };
+                        (Some(dep), candidates)
                     };
 
-                    problems.push(SolveProblem::from_raw(problem_type, dep, source, target));
+                    problems.push(SolveProblem::from_raw(
+                        problem_type,
+                        dep,
+                        candidates,
+                        source,
+                        target,
+                    ));
                 }
             }
         }
@@ -130,8 +138,12 @@ impl<'pool> Solver<'pool> {
     }
 
     /// Solves all the problems in the `queue` and returns a transaction from the found solution.
-    /// Returns an error if problems remain unsolved.
-    pub fn solve(&mut self, queue: &mut SolveGoal) -> Result<Transaction<'_>, Vec<String>> {
+    /// Returns an error with the description of the problems and the rules involved in them if
+    /// problems remain unsolved.
+    pub fn solve(
+        &mut self,
+        queue: &mut SolveGoal,
+    ) -> Result<Transaction<'_>, (Vec<String>, Vec<SolveProblem>)> {
         let result = unsafe {
             // Run the solve method
             ffi::solver_solve(self.raw_ptr(), queue.raw_ptr());
@@ -146,7 +158,41 @@ impl<'pool> Solver<'pool> {
             // Safe because we know the `transaction` ptr is valid
             Ok(unsafe { Transaction::new(self, transaction) })
         } else {
-            Err(self.solver_problems())
+            Err((self.solver_problems(), self.all_solver_problems()))
+        }
+    }
+}
+
+/// Returns the solvables that provide the specified dependency. This mirrors
+/// the `pool_whatprovides` function of libsolv which is only available as an
+/// inline function.
+///
+/// Safety: the caller must ensure that `dep` is a valid dependency id in the
+/// pool and that the `whatprovides` data of the pool has been created.
+unsafe fn whatprovides(pool: *mut ffi::Pool, dep: ffi::Id) -> Vec<SolvableId> {
+    // Dependency ids with the highest bit set refer to relations (e.g. conda
+    // matchspecs), other ids refer to plain names.
+    let cached = if dep < 0 {
+        *(*pool)
+            .whatprovides_rel
+            .offset((dep & 0x7fff_ffff) as isize)
+    } else {
+        *(*pool).whatprovides.offset(dep as isize)
+    };
+    let mut offset = if cached == 0 {
+        ffi::pool_addrelproviders(pool, dep)
+    } else {
+        cached as ffi::Id
+    };
+
+    let mut solvables = Vec::new();
+    loop {
+        let id = *(*pool).whatprovidesdata.offset(offset as isize);
+        if id == 0 {
+            break;
         }
+        solvables.push(SolvableId(id));
+        offset += 1;
     }
+    solvables
 }
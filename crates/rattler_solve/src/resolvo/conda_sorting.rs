@@ -21,7 +21,8 @@ pub(super) enum CompareStrategy {
 
 /// Sort the candidates based on the dependencies.
 /// This sorts in two steps:
-/// 1. Sort by tracked features, version, and build number
+/// 1. Sort by preferences, features, tracked features, channel priority,
+///    version, and build number
 /// 2. Sort by trying to sort the solvable that selects the highest versions of
 ///    the shared set of dependencies
 pub struct SolvableSorter<'a, 'repo> {
@@ -58,7 +59,8 @@ impl<'a, 'repo> SolvableSorter<'a, 'repo> {
 
     /// Sort the candidates based on the dependencies.
     /// This sorts in two steps:
-    /// 1. Sort by tracked features, version, and build number
+    /// 1. Sort by preferences, features, tracked features, channel priority,
+    ///    version, and build number
     /// 2. Sort by trying to find the candidate that selects the highest
     ///    versions of the shared set of dependencies
     pub fn sort(
@@ -76,13 +78,37 @@ impl<'a, 'repo> SolvableSorter<'a, 'repo> {
     }
 
     /// Sort the candidates based on:
-    /// 1. Whether the package has tracked features
-    /// 2. The version of the package
-    /// 3. The build number of the package
+    /// 1. The total weight of the preferences the package matches
+    /// 2. The number of legacy features the package requires (only when
+    ///    features are selected)
+    /// 3. Whether the package has tracked features
+    /// 4. The priority of the channel (only with flexible channel priority)
+    /// 5. The version of the package
+    /// 6. The build number of the package
     fn simple_compare(&self, a: SolvableId, b: SolvableId) -> Ordering {
         let a_record = &self.solvable_record(a);
         let b_record = &self.solvable_record(b);
 
+        // Prefer the variant that matches the preferences of the user the most.
+        let provider = self.solver.provider();
+        match provider
+            .preference_weight(b)
+            .cmp(&provider.preference_weight(a))
+        {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+
+        // Prefer the variant that requires the most legacy features. Candidates that
+        // require a feature that is not selected are excluded by the provider, so this
+        // prefers the variants that were built for the selected features.
+        if provider.features_selected {
+            match b_record.feature_count().cmp(&a_record.feature_count()) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+        }
+
         // First compare by "tracked_features". If one of the packages has a tracked
         // feature it is sorted below the one that doesn't have the tracked feature.
         let a_has_tracked_features = !a_record.track_features().is_empty();
@@ -93,6 +119,17 @@ impl<'a, 'repo> SolvableSorter<'a, 'repo> {
             _ => {}
         };
 
+        // Then, prefer the variant from the channel with the highest priority. This is
+        // only populated when solving with flexible channel priority.
+        let channel_priorities = &self.solver.provider().channel_priorities;
+        let a_priority = a_record.channel().and_then(|c| channel_priorities.get(c));
+        let b_priority = b_record.channel().and_then(|c| channel_priorities.get(c));
+        if let (Some(a_priority), Some(b_priority)) = (a_priority, b_priority) {
+            if a_priority != b_priority {
+                return a_priority.cmp(b_priority);
+            }
+        }
+
         // Otherwise, select the variant with the highest version
         match (self.strategy, a_record.version().cmp(b_record.version())) {
             (CompareStrategy::Default, Ordering::Greater)
@@ -0,0 +1,239 @@
+//! Converts the conflict that resolvo reports for an unsolvable problem into
+//! an [`UnsolvableExplanation`].
+//!
+//! Resolvo does not expose the structure of its conflict graph directly, it
+//! only renders it, either user-friendly or as a graphviz graph. The graphviz
+//! rendering is produced through an [`Interner`] that renders the ids of the
+//! solvables, version sets and strings instead of their values. The ids are
+//! then parsed back from the graph and resolved with the actual provider.
+//!
+//! The graphviz output is not a stable interface of resolvo, which is why the
+//! workspace pins resolvo to an exact version. The backend tests that inspect
+//! the conflict graph fail when a new version changes the format. Whenever the
+//! output cannot be interpreted the explanation falls back to the
+//! user-friendly message and a warning is logged.
+
+use std::fmt::Display;
+
+use resolvo::{
+    conflict::Conflict, Interner, NameId, SolvableId, Solver, StringId, VersionSetId,
+    VersionSetUnionId,
+};
+
+use super::{CondaDependencyProvider, SolverPackageRecord};
+use crate::unsolvable::{
+    ConflictEdgeKind, ConflictGraphBuilder, ConflictNode, ConflictPackage, UnsolvableExplanation,
+};
+
+/// Labels that resolvo uses for the root node, depending on its version.
+const ROOT_NODES: [&str; 2] = ["root", "<root>"];
+
+/// Label that resolvo uses for the node of requirements without candidates.
+const UNRESOLVED_NODE: &str = "unresolved";
+
+/// Prefix that resolvo uses for the nodes that represent an exclusion reason.
+const EXCLUDED_REASON_PREFIX: &str = "reason: ";
+
+/// Renders the ids of the items instead of their values.
+struct IdInterner<'p, 'a> {
+    provider: &'p CondaDependencyProvider<'a>,
+}
+
+impl<'p, 'a> Interner for IdInterner<'p, 'a> {
+    fn display_solvable(&self, solvable: SolvableId) -> impl Display + '_ {
+        format!("s{}", solvable.0)
+    }
+
+    fn display_merged_solvables(&self, solvables: &[SolvableId]) -> impl Display + '_ {
+        solvables
+            .iter()
+            .map(|solvable| format!("s{}", solvable.0))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn display_name(&self, _name: NameId) -> impl Display + '_ {
+        ""
+    }
+
+    fn display_version_set(&self, version_set: VersionSetId) -> impl Display + '_ {
+        format!("v{}", version_set.0)
+    }
+
+    fn display_string(&self, string_id: StringId) -> impl Display + '_ {
+        format!("r{}", string_id.0)
+    }
+
+    fn version_set_name(&self, version_set: VersionSetId) -> NameId {
+        self.provider.version_set_name(version_set)
+    }
+
+    fn solvable_name(&self, solvable: SolvableId) -> NameId {
+        self.provider.solvable_name(solvable)
+    }
+
+    fn version_sets_in_union(
+        &self,
+        version_set_union: VersionSetUnionId,
+    ) -> impl Iterator<Item = VersionSetId> {
+        self.provider.version_sets_in_union(version_set_union)
+    }
+}
+
+/// Converts a resolvo [`Conflict`] into an [`UnsolvableExplanation`]. If the
+/// graphviz output of resolvo cannot be interpreted, the explanation only
+/// contains the user-friendly message of resolvo.
+pub(super) fn explain_conflict(
+    conflict: &Conflict,
+    solver: &Solver<CondaDependencyProvider<'_>>,
+) -> UnsolvableExplanation {
+    let message = conflict.display_user_friendly(solver).to_string();
+
+    let mut graphviz = Vec::new();
+    let graph = conflict
+        .graph(solver)
+        .graphviz(
+            &mut graphviz,
+            &IdInterner {
+                provider: solver.provider(),
+            },
+            false,
+        )
+        .ok()
+        .and_then(|()| String::from_utf8(graphviz).ok())
+        .and_then(|graphviz| build_graph(solver.provider(), &graphviz));
+
+    if let Some(builder) = graph {
+        builder.finish(vec![message])
+    } else {
+        tracing::warn!(
+            "could not interpret the conflict graph of resolvo, the explanation only contains \
+             the message"
+        );
+        UnsolvableExplanation::from_messages(vec![message])
+    }
+}
+
+/// Builds the conflict graph from the graphviz output of resolvo. Returns
+/// `None` if the output is not in the expected format.
+fn build_graph(
+    provider: &CondaDependencyProvider<'_>,
+    graphviz: &str,
+) -> Option<ConflictGraphBuilder> {
+    let mut builder = ConflictGraphBuilder::new();
+    for (source, target, color, label) in parse_graphviz_edges(graphviz)? {
+        let kind = match label {
+            "excluded" => ConflictEdgeKind::Excluded,
+            "already installed" => ConflictEdgeKind::Conflict,
+            _ if color == "black" || target == UNRESOLVED_NODE => ConflictEdgeKind::Requires {
+                spec: display_requirement(provider, label)?,
+            },
+            _ => ConflictEdgeKind::Constrains {
+                spec: display_requirement(provider, label)?,
+            },
+        };
+
+        let source = builder.add_node(resolve_node(provider, source)?);
+        let target = builder.add_node(resolve_node(provider, target)?);
+        builder.add_edge(source, target, kind);
+    }
+    Some(builder)
+}
+
+/// Parses the edges from the graphviz output of resolvo. Every edge is
+/// rendered as `"source" -> "target"[color=color, label="label"];`. Returns
+/// `None` if any edge is not in this format.
+fn parse_graphviz_edges(graphviz: &str) -> Option<Vec<(&str, &str, &str, &str)>> {
+    graphviz
+        .trim()
+        .strip_prefix("digraph {")?
+        .strip_suffix('}')?
+        .split("];")
+        .filter(|edge| !edge.trim().is_empty())
+        .map(|edge| {
+            let mut parts = edge.split('"');
+            let _ = parts.next()?;
+            let source = parts.next()?;
+            let _ = parts.next()?;
+            let target = parts.next()?;
+            let color = parts.next()?.strip_prefix("[color=")?;
+            let color = color.split(',').next()?;
+            let label = parts.next()?;
+            Some((source, target, color, label))
+        })
+        .collect()
+}
+
+/// Resolves a node that was rendered through the [`IdInterner`].
+fn resolve_node(provider: &CondaDependencyProvider<'_>, node: &str) -> Option<ConflictNode> {
+    if ROOT_NODES.contains(&node) {
+        Some(ConflictNode::Root)
+    } else if node == UNRESOLVED_NODE {
+        Some(ConflictNode::Missing)
+    } else if let Some(reason) = node.strip_prefix(EXCLUDED_REASON_PREFIX) {
+        let reason = StringId(parse_id(reason, 'r')?);
+        Some(ConflictNode::Excluded {
+            reason: provider.pool.resolve_string(reason).to_string(),
+        })
+    } else {
+        let solvable = SolvableId(parse_id(node, 's')?);
+        let package = match provider.pool.resolve_solvable(solvable).record {
+            SolverPackageRecord::Record(record) => ConflictPackage::from_record(record),
+            SolverPackageRecord::VirtualPackage(package) => {
+                ConflictPackage::from_virtual_package(package)
+            }
+        };
+        Some(ConflictNode::Package(package))
+    }
+}
+
+/// Renders a requirement that was rendered through the [`IdInterner`] as one
+/// or more version sets separated by `|`.
+fn display_requirement(
+    provider: &CondaDependencyProvider<'_>,
+    requirement: &str,
+) -> Option<String> {
+    let version_sets = requirement
+        .split('|')
+        .map(|version_set| {
+            let version_set = VersionSetId(parse_id(version_set.trim(), 'v')?);
+            Some(format!(
+                "{} {}",
+                provider.display_name(provider.version_set_name(version_set)),
+                provider.display_version_set(version_set)
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(version_sets.join(" | "))
+}
+
+fn parse_id(id: &str, prefix: char) -> Option<u32> {
+    id.strip_prefix(prefix)?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_graphviz_edges;
+
+    #[test]
+    fn test_parse_graphviz_edges() {
+        let graphviz = r#"digraph {
+"<root>" -> "s1"[color=black, label="v0"];
+"s1" -> "unresolved"[color=red, label="v1 | v2"];
+}"#;
+        assert_eq!(
+            parse_graphviz_edges(graphviz),
+            Some(vec![
+                ("<root>", "s1", "black", "v0"),
+                ("s1", "unresolved", "red", "v1 | v2"),
+            ])
+        );
+
+        // Output in an unexpected format is rejected instead of skipped.
+        assert_eq!(parse_graphviz_edges("graph {}"), None);
+        assert_eq!(
+            parse_graphviz_edges("digraph {\n\"<root>\" -> \"s1\" [label=\"v0\"];\n}"),
+            None
+        );
+    }
+}
@@ -3,10 +3,12 @@
 use std::{
     cell::RefCell,
     cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Display, Formatter},
     marker::PhantomData,
     ops::Deref,
+    rc::Rc,
+    sync::{Arc, Mutex},
 };
 
 use chrono::{DateTime, Utc};
@@ -14,7 +16,7 @@ use conda_sorting::SolvableSorter;
 use itertools::Itertools;
 use rattler_conda_types::{
     package::ArchiveType, GenericVirtualPackage, MatchSpec, Matches, NamelessMatchSpec,
-    PackageName, PackageRecord, ParseMatchSpecError, ParseStrictness, RepoDataRecord,
+    PackageName, PackageRecord, ParseMatchSpecError, ParseStrictness, Platform, RepoDataRecord,
 };
 use resolvo::{
     utils::{Pool, VersionSet},
@@ -24,11 +26,13 @@ use resolvo::{
 };
 
 use crate::{
-    resolvo::conda_sorting::CompareStrategy, ChannelPriority, IntoRepoData, SolveError,
-    SolveStrategy, SolverRepoData, SolverTask,
+    disabled_feature, preference_weight, record_features, resolvo::conda_sorting::CompareStrategy,
+    ChannelPriority, IntoRepoData, MultiPlatformSolveError, MultiPlatformSolverTask, Preference,
+    SolveError, SolveStrategy, SolverRepoData, SolverTask,
 };
 
 mod conda_sorting;
+mod conflict;
 
 /// Represents the information required to load available packages into libsolv
 /// for a single channel and platform combination
@@ -124,6 +128,13 @@ impl<'a> SolverPackageRecord<'a> {
         }
     }
 
+    fn channel(&self) -> Option<&str> {
+        match self {
+            SolverPackageRecord::Record(rec) => Some(&rec.channel),
+            SolverPackageRecord::VirtualPackage(_rec) => None,
+        }
+    }
+
     fn track_features(&self) -> &[String] {
         const EMPTY: [String; 0] = [];
         match self {
@@ -132,6 +143,14 @@ impl<'a> SolverPackageRecord<'a> {
         }
     }
 
+    /// Returns the number of legacy features the record requires.
+    fn feature_count(&self) -> usize {
+        match self {
+            SolverPackageRecord::Record(rec) => record_features(&rec.package_record).count(),
+            SolverPackageRecord::VirtualPackage(_rec) => 0,
+        }
+    }
+
     fn build_number(&self) -> u64 {
         match self {
             SolverPackageRecord::Record(rec) => rec.package_record.build_number,
@@ -166,20 +185,70 @@ impl<'a> Display for SolverPackageRecord<'a> {
 #[derive(Default)]
 pub struct CondaDependencyProvider<'a> {
     /// The pool that deduplicates data used by the provider.
-    pub pool: Pool<SolverMatchSpec<'a>, String>,
+    pub pool: Rc<Pool<SolverMatchSpec<'a>, String>>,
 
     records: HashMap<NameId, Candidates>,
 
     matchspec_to_highest_version:
         RefCell<HashMap<VersionSetId, Option<(rattler_conda_types::Version, bool)>>>,
 
-    parse_match_spec_cache: RefCell<HashMap<&'a str, VersionSetId>>,
+    parse_match_spec_cache: Rc<RefCell<HashMap<&'a str, VersionSetId>>>,
+
+    spec_cache: Option<SpecCache>,
 
     stop_time: Option<std::time::SystemTime>,
 
     strategy: SolveStrategy,
 
     direct_dependencies: HashSet<NameId>,
+
+    /// Maps channels to their priority (lower is better). Only populated when
+    /// solving with [`ChannelPriority::Flexible`].
+    channel_priorities: HashMap<&'a str, usize>,
+
+    /// The total weight of the preferences that a candidate matches, only
+    /// contains the candidates that match at least one preference.
+    preference_weights: HashMap<SolvableId, u32>,
+
+    /// Whether legacy features were selected, only then candidates that
+    /// require more features are preferred.
+    features_selected: bool,
+}
+
+/// Interned data that is shared between the providers that solve the same
+/// request for multiple platforms. Records that are available on more than one
+/// platform (e.g. `noarch` packages) and the dependencies they reference are
+/// only interned once.
+#[derive(Clone, Default)]
+struct SharedPool<'a> {
+    pool: Rc<Pool<SolverMatchSpec<'a>, String>>,
+    solvables: Rc<RefCell<HashMap<*const RepoDataRecord, SolvableId>>>,
+    parse_match_spec_cache: Rc<RefCell<HashMap<&'a str, VersionSetId>>>,
+    spec_cache: Option<SpecCache>,
+}
+
+impl<'a> SharedPool<'a> {
+    /// Constructs an empty pool that parses dependencies through the given
+    /// cache, if any.
+    fn with_spec_cache(spec_cache: Option<&SpecCache>) -> Self {
+        Self {
+            spec_cache: spec_cache.cloned(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the solvable of the record, interning it if it was not
+    /// interned before.
+    fn intern_record(&self, name: NameId, record: &'a RepoDataRecord) -> SolvableId {
+        *self
+            .solvables
+            .borrow_mut()
+            .entry(std::ptr::from_ref(record))
+            .or_insert_with(|| {
+                self.pool
+                    .intern_solvable(name, SolverPackageRecord::Record(record))
+            })
+    }
 }
 
 impl<'a> CondaDependencyProvider<'a> {
@@ -191,12 +260,44 @@ impl<'a> CondaDependencyProvider<'a> {
         locked_records: &'a [RepoDataRecord],
         virtual_packages: &'a [GenericVirtualPackage],
         match_specs: &[MatchSpec],
+        features: Option<&[String]>,
         stop_time: Option<std::time::SystemTime>,
         channel_priority: ChannelPriority,
         exclude_newer: Option<DateTime<Utc>>,
         strategy: SolveStrategy,
     ) -> Result<Self, SolveError> {
-        let pool = Pool::default();
+        Self::with_shared_pool(
+            SharedPool::default(),
+            repodata,
+            favored_records,
+            locked_records,
+            virtual_packages,
+            match_specs,
+            features,
+            stop_time,
+            channel_priority,
+            exclude_newer,
+            strategy,
+        )
+    }
+
+    /// Constructs a new provider that interns its data in the given shared
+    /// pool.
+    #[allow(clippy::too_many_arguments)]
+    fn with_shared_pool(
+        shared_pool: SharedPool<'a>,
+        repodata: impl IntoIterator<Item = RepoData<'a>>,
+        favored_records: &'a [RepoDataRecord],
+        locked_records: &'a [RepoDataRecord],
+        virtual_packages: &'a [GenericVirtualPackage],
+        match_specs: &[MatchSpec],
+        features: Option<&[String]>,
+        stop_time: Option<std::time::SystemTime>,
+        channel_priority: ChannelPriority,
+        exclude_newer: Option<DateTime<Utc>>,
+        strategy: SolveStrategy,
+    ) -> Result<Self, SolveError> {
+        let pool = shared_pool.pool.clone();
         let mut records: HashMap<NameId, Candidates> = HashMap::default();
 
         // Add virtual packages to the records
@@ -223,6 +324,10 @@ impl<'a> CondaDependencyProvider<'a> {
         // Hashmap that maps the package name to the channel it was first found in.
         let mut package_name_found_in_channel = HashMap::<String, &String>::new();
 
+        // Hashmap that maps the channel to its priority based on the order in which
+        // the channels were presented to this function.
+        let mut channel_priorities = HashMap::<&'a str, usize>::new();
+
         // Add additional records
         for repo_data in repodata {
             // Iterate over all records and dedup records that refer to the same package
@@ -295,11 +400,19 @@ impl<'a> CondaDependencyProvider<'a> {
             for record in ordered_repodata {
                 let package_name =
                     pool.intern_package_name(record.package_record.name.as_normalized());
-                let solvable_id =
-                    pool.intern_solvable(package_name, SolverPackageRecord::Record(record));
+                let solvable_id = shared_pool.intern_record(package_name, record);
                 let candidates = records.entry(package_name).or_default();
                 candidates.candidates.push(solvable_id);
 
+                // Record the priority of the channel, the sorting of the candidates will prefer
+                // records from channels with a higher priority.
+                if channel_priority == ChannelPriority::Flexible {
+                    let next_priority = channel_priorities.len();
+                    channel_priorities
+                        .entry(record.channel.as_str())
+                        .or_insert(next_priority);
+                }
+
                 // Filter out any records that are newer than a specific date.
                 match (&exclude_newer, &record.package_record.timestamp) {
                     (Some(exclude_newer), Some(record_timestamp))
@@ -313,6 +426,17 @@ impl<'a> CondaDependencyProvider<'a> {
                     _ => {}
                 }
 
+                // Exclude records that require a legacy feature that is not enabled.
+                if let Some(feature) =
+                    features.and_then(|features| disabled_feature(&record.package_record, features))
+                {
+                    let reason = pool.intern_string(format!(
+                        "the package requires the feature '{feature}' which is not enabled"
+                    ));
+                    candidates.excluded.push((solvable_id, reason));
+                    continue;
+                }
+
                 // Add to excluded when package is not in the specified channel.
                 if !channel_specific_specs.is_empty() {
                     if let Some(spec) = channel_specific_specs.iter().find(|&&spec| {
@@ -399,13 +523,49 @@ impl<'a> CondaDependencyProvider<'a> {
             pool,
             records,
             matchspec_to_highest_version: RefCell::default(),
-            parse_match_spec_cache: RefCell::default(),
+            parse_match_spec_cache: shared_pool.parse_match_spec_cache,
+            spec_cache: shared_pool.spec_cache,
             stop_time,
             strategy,
             direct_dependencies,
+            channel_priorities,
+            preference_weights: HashMap::default(),
+            features_selected: features.is_some(),
         })
     }
 
+    /// Prefers the candidates that match the given preferences over the
+    /// candidates that don't.
+    #[must_use]
+    pub fn with_preferences(mut self, preferences: &[Preference]) -> Self {
+        if preferences.is_empty() {
+            return self;
+        }
+
+        for candidates in self.records.values() {
+            for &solvable in &candidates.candidates {
+                let SolverPackageRecord::Record(record) =
+                    self.pool.resolve_solvable(solvable).record
+                else {
+                    continue;
+                };
+                let weight = preference_weight(preferences, record);
+                if weight > 0 {
+                    self.preference_weights.insert(solvable, weight);
+                }
+            }
+        }
+        self
+    }
+
+    /// Returns the total weight of the preferences that the candidate matches.
+    fn preference_weight(&self, solvable: SolvableId) -> u32 {
+        self.preference_weights
+            .get(&solvable)
+            .copied()
+            .unwrap_or_default()
+    }
+
     /// Returns all package names
     pub fn package_names(&self) -> impl Iterator<Item = NameId> + '_ {
         self.records.keys().copied()
@@ -512,33 +672,41 @@ impl<'a> DependencyProvider for CondaDependencyProvider<'a> {
 
         let mut parse_match_spec_cache = self.parse_match_spec_cache.borrow_mut();
         for depends in rec.package_record.depends.iter() {
-            let version_set_id =
-                match parse_match_spec(&self.pool, depends, &mut parse_match_spec_cache) {
-                    Ok(version_set_id) => version_set_id,
-                    Err(e) => {
-                        let reason = self.pool.intern_string(format!(
-                            "the dependency '{depends}' failed to parse: {e}",
-                        ));
-
-                        return Dependencies::Unknown(reason);
-                    }
-                };
+            let version_set_id = match parse_match_spec(
+                &self.pool,
+                depends,
+                &mut parse_match_spec_cache,
+                self.spec_cache.as_ref(),
+            ) {
+                Ok(version_set_id) => version_set_id,
+                Err(e) => {
+                    let reason = self
+                        .pool
+                        .intern_string(format!("the dependency '{depends}' failed to parse: {e}",));
+
+                    return Dependencies::Unknown(reason);
+                }
+            };
 
             dependencies.requirements.push(version_set_id.into());
         }
 
         for constrains in rec.package_record.constrains.iter() {
-            let version_set_id =
-                match parse_match_spec(&self.pool, constrains, &mut parse_match_spec_cache) {
-                    Ok(version_set_id) => version_set_id,
-                    Err(e) => {
-                        let reason = self.pool.intern_string(format!(
-                            "the constrains '{constrains}' failed to parse: {e}",
-                        ));
-
-                        return Dependencies::Unknown(reason);
-                    }
-                };
+            let version_set_id = match parse_match_spec(
+                &self.pool,
+                constrains,
+                &mut parse_match_spec_cache,
+                self.spec_cache.as_ref(),
+            ) {
+                Ok(version_set_id) => version_set_id,
+                Err(e) => {
+                    let reason = self.pool.intern_string(format!(
+                        "the constrains '{constrains}' failed to parse: {e}",
+                    ));
+
+                    return Dependencies::Unknown(reason);
+                }
+            };
             dependencies.constrains.push(version_set_id);
         }
 
@@ -594,6 +762,104 @@ impl<'a> DependencyProvider for CondaDependencyProvider<'a> {
     }
 }
 
+/// The default number of dependency strings that a [`SolverSession`]
+/// remembers.
+pub const DEFAULT_SPEC_CACHE_CAPACITY: usize = 100_000;
+
+/// Dependency strings that were parsed by previous solves, shared between the
+/// providers of a [`SolverSession`]. Unlike the pool, the parsed specs do not
+/// borrow from the records so they can outlive a single [`SolverTask`].
+#[derive(Clone)]
+struct SpecCache(Arc<Mutex<SpecCacheInner>>);
+
+struct SpecCacheInner {
+    specs: HashMap<Arc<str>, CachedSpec>,
+    /// The cached dependency strings in the order in which they are
+    /// considered for eviction.
+    queue: VecDeque<Arc<str>>,
+    capacity: usize,
+    hits: u64,
+}
+
+struct CachedSpec {
+    name: PackageName,
+    spec: NamelessMatchSpec,
+    /// Whether the spec was used since it was last considered for eviction.
+    referenced: bool,
+}
+
+impl SpecCacheInner {
+    /// Evicts the spec that was added the longest time ago, giving specs that
+    /// were used since they were last considered a second chance.
+    fn evict(&mut self) {
+        while let Some(spec_str) = self.queue.pop_front() {
+            let Some(cached) = self.specs.get_mut(&spec_str) else {
+                continue;
+            };
+            if cached.referenced {
+                cached.referenced = false;
+                self.queue.push_back(spec_str);
+            } else {
+                self.specs.remove(&spec_str);
+                return;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.specs.clear();
+        self.queue.clear();
+    }
+}
+
+impl SpecCache {
+    fn with_capacity(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(SpecCacheInner {
+            specs: HashMap::default(),
+            queue: VecDeque::default(),
+            capacity,
+            hits: 0,
+        })))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SpecCacheInner> {
+        self.0.lock().expect("the spec cache is poisoned")
+    }
+
+    /// Returns the parsed spec, parsing it only if it was not parsed before.
+    /// When the cache is full a spec is evicted before the new spec is added.
+    fn parse(
+        &self,
+        spec_str: &str,
+    ) -> Result<(PackageName, NamelessMatchSpec), ParseMatchSpecError> {
+        let mut cache = self.lock();
+        if let Some(cached) = cache.specs.get_mut(spec_str) {
+            cached.referenced = true;
+            let parsed = (cached.name.clone(), cached.spec.clone());
+            cache.hits += 1;
+            return Ok(parsed);
+        }
+
+        let (name, spec) = parse_nameless_match_spec(spec_str)?;
+        if cache.capacity > 0 {
+            if cache.specs.len() >= cache.capacity {
+                cache.evict();
+            }
+            let spec_str: Arc<str> = Arc::from(spec_str);
+            cache.queue.push_back(spec_str.clone());
+            cache.specs.insert(
+                spec_str,
+                CachedSpec {
+                    name: name.clone(),
+                    spec: spec.clone(),
+                    referenced: false,
+                },
+            );
+        }
+        Ok((name, spec))
+    }
+}
+
 /// A [`Solver`] implemented using the `resolvo` library
 #[derive(Default)]
 pub struct Solver;
@@ -601,7 +867,6 @@ pub struct Solver;
 impl super::SolverImpl for Solver {
     type RepoData<'a> = RepoData<'a>;
 
-    #[allow(clippy::redundant_closure_for_method_calls)]
     fn solve<
         'a,
         R: IntoRepoData<'a, Self::RepoData<'a>>,
@@ -610,105 +875,324 @@ impl super::SolverImpl for Solver {
         &mut self,
         task: SolverTask<TAvailablePackagesIterator>,
     ) -> Result<Vec<RepoDataRecord>, SolveError> {
+        solve_task(task, None)
+    }
+
+    fn solve_multi_platform<'a, R, TAvailablePackagesIterator>(
+        &mut self,
+        task: MultiPlatformSolverTask<TAvailablePackagesIterator>,
+    ) -> Result<HashMap<Platform, Vec<RepoDataRecord>>, MultiPlatformSolveError>
+    where
+        R: IntoIterator<Item = &'a RepoDataRecord>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    {
+        solve_multi_platform_task(task, None)
+    }
+}
+
+/// A `resolvo` solver that is reused for multiple [`SolverTask`]s.
+///
+/// The session remembers the dependencies of the records it has parsed, so
+/// the same `depends` and `constrains` strings of the repodata are not parsed
+/// again for every solve. At most [`DEFAULT_SPEC_CACHE_CAPACITY`] dependencies
+/// are remembered unless a different capacity is given. When the cache is full
+/// the dependency that was added the longest time ago is evicted, unless it was
+/// used since it was last considered for eviction. Clones of a session share
+/// the same cache.
+///
+/// Only the parsed dependencies are reused. The pool that interns the package
+/// names, version sets and records borrows from the records of a task, so it
+/// is still built from scratch for every solve.
+#[derive(Clone)]
+pub struct SolverSession {
+    spec_cache: SpecCache,
+}
+
+impl Default for SolverSession {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_SPEC_CACHE_CAPACITY)
+    }
+}
+
+impl SolverSession {
+    /// Constructs a session that remembers at most `capacity` parsed
+    /// dependencies.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            spec_cache: SpecCache::with_capacity(capacity),
+        }
+    }
+
+    /// Forgets all the dependencies that were parsed by previous solves and
+    /// resets [`SolverSession::cache_hits`].
+    pub fn clear_cache(&mut self) {
+        let mut cache = self.spec_cache.lock();
+        cache.clear();
+        cache.hits = 0;
+    }
+
+    /// Returns how many times a dependency was found in the cache instead of
+    /// being parsed again.
+    pub fn cache_hits(&self) -> u64 {
+        self.spec_cache.lock().hits
+    }
+}
+
+impl super::SolverImpl for SolverSession {
+    type RepoData<'a> = RepoData<'a>;
+
+    fn solve<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+    ) -> Result<Vec<RepoDataRecord>, SolveError> {
+        solve_task(task, Some(&self.spec_cache))
+    }
+
+    fn solve_multi_platform<'a, R, TAvailablePackagesIterator>(
+        &mut self,
+        task: MultiPlatformSolverTask<TAvailablePackagesIterator>,
+    ) -> Result<HashMap<Platform, Vec<RepoDataRecord>>, MultiPlatformSolveError>
+    where
+        R: IntoIterator<Item = &'a RepoDataRecord>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    {
+        solve_multi_platform_task(task, Some(&self.spec_cache))
+    }
+}
+
+/// Solves a task, parsing the dependencies through the given cache if any.
+#[allow(clippy::redundant_closure_for_method_calls)]
+fn solve_task<
+    'a,
+    R: IntoRepoData<'a, RepoData<'a>>,
+    TAvailablePackagesIterator: IntoIterator<Item = R>,
+>(
+    task: SolverTask<TAvailablePackagesIterator>,
+    spec_cache: Option<&SpecCache>,
+) -> Result<Vec<RepoDataRecord>, SolveError> {
+    let stop_time = task
+        .timeout
+        .map(|timeout| std::time::SystemTime::now() + timeout);
+
+    // Construct a provider that can serve the data.
+    let provider = CondaDependencyProvider::with_shared_pool(
+        SharedPool::with_spec_cache(spec_cache),
+        task.available_packages.into_iter().map(|r| r.into()),
+        &task.locked_packages,
+        &task.pinned_packages,
+        &task.virtual_packages,
+        task.specs.clone().as_ref(),
+        task.features.as_deref(),
+        stop_time,
+        task.channel_priority,
+        task.exclude_newer,
+        task.strategy,
+    )?
+    .with_preferences(&task.preferences);
+
+    solve_with_provider(
+        provider,
+        &task.virtual_packages,
+        &task.specs,
+        &task.constraints,
+    )
+}
+
+/// Solves a multi platform task, parsing the dependencies through the given
+/// cache if any.
+fn solve_multi_platform_task<'a, R, TAvailablePackagesIterator>(
+    task: MultiPlatformSolverTask<TAvailablePackagesIterator>,
+    spec_cache: Option<&SpecCache>,
+) -> Result<HashMap<Platform, Vec<RepoDataRecord>>, MultiPlatformSolveError>
+where
+    R: IntoIterator<Item = &'a RepoDataRecord>,
+    TAvailablePackagesIterator: IntoIterator<Item = R>,
+{
+    let repodata = task
+        .available_packages
+        .into_iter()
+        .map(|records| records.into_iter().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // The records and dependencies are interned once and reused by the
+    // providers of all platforms.
+    let shared_pool = SharedPool::with_spec_cache(spec_cache);
+
+    let mut solutions = HashMap::with_capacity(task.platforms.len());
+    for target in &task.platforms {
         let stop_time = task
             .timeout
             .map(|timeout| std::time::SystemTime::now() + timeout);
 
-        // Construct a provider that can serve the data.
-        let provider = CondaDependencyProvider::new(
-            task.available_packages.into_iter().map(|r| r.into()),
-            &task.locked_packages,
-            &task.pinned_packages,
-            &task.virtual_packages,
-            task.specs.clone().as_ref(),
+        let available_packages = repodata.iter().map(|records| {
+            records
+                .iter()
+                .copied()
+                .filter(|record| target.is_available(record))
+                .collect::<RepoData<'_>>()
+        });
+
+        let records = CondaDependencyProvider::with_shared_pool(
+            shared_pool.clone(),
+            available_packages,
+            &target.locked_packages,
+            &target.pinned_packages,
+            &target.virtual_packages,
+            &task.specs,
+            task.features.as_deref(),
             stop_time,
             task.channel_priority,
             task.exclude_newer,
             task.strategy,
-        )?;
-
-        // Construct the requirements that the solver needs to satisfy.
-        let virtual_package_requirements = task.virtual_packages.iter().map(|spec| {
-            let name_id = provider.pool.intern_package_name(spec.name.as_normalized());
-            provider
-                .pool
-                .intern_version_set(name_id, NamelessMatchSpec::default().into())
-        });
+        )
+        .and_then(|provider| {
+            solve_with_provider(
+                provider.with_preferences(&task.preferences),
+                &target.virtual_packages,
+                &task.specs,
+                &task.constraints,
+            )
+        })
+        .map_err(|error| MultiPlatformSolveError {
+            platform: target.platform,
+            error,
+        })?;
+        solutions.insert(target.platform, records);
+    }
+
+    Ok(solutions)
+}
 
-        let root_requirements = task.specs.iter().map(|spec| {
-            let (name, nameless_spec) = spec.clone().into_nameless();
+/// Solves the requested specs with the given provider and returns the records
+/// of the solution.
+fn solve_with_provider(
+    provider: CondaDependencyProvider<'_>,
+    virtual_packages: &[GenericVirtualPackage],
+    specs: &[MatchSpec],
+    constraints: &[MatchSpec],
+) -> Result<Vec<RepoDataRecord>, SolveError> {
+    // Construct the requirements that the solver needs to satisfy.
+    let virtual_package_requirements = virtual_packages.iter().map(|spec| {
+        let name_id = provider.pool.intern_package_name(spec.name.as_normalized());
+        provider
+            .pool
+            .intern_version_set(name_id, NamelessMatchSpec::default().into())
+    });
+
+    let root_requirements = specs.iter().map(|spec| {
+        let (name, nameless_spec) = spec.clone().into_nameless();
+        let name = name.expect("cannot use matchspec without a name");
+        let name_id = provider.pool.intern_package_name(name.as_normalized());
+        provider
+            .pool
+            .intern_version_set(name_id, nameless_spec.into())
+    });
+
+    let all_requirements = virtual_package_requirements
+        .chain(root_requirements)
+        .map(Requirement::from)
+        .collect();
+
+    let root_constraints = constraints
+        .iter()
+        .map(|spec| {
+            let (name, spec) = spec.clone().into_nameless();
             let name = name.expect("cannot use matchspec without a name");
             let name_id = provider.pool.intern_package_name(name.as_normalized());
-            provider
-                .pool
-                .intern_version_set(name_id, nameless_spec.into())
-        });
-
-        let all_requirements = virtual_package_requirements
-            .chain(root_requirements)
-            .map(Requirement::from)
-            .collect();
-
-        let root_constraints = task
-            .constraints
-            .iter()
-            .map(|spec| {
-                let (name, spec) = spec.clone().into_nameless();
-                let name = name.expect("cannot use matchspec without a name");
-                let name_id = provider.pool.intern_package_name(name.as_normalized());
-                provider.pool.intern_version_set(name_id, spec.into())
-            })
-            .collect();
-
-        let problem = Problem::new()
-            .requirements(all_requirements)
-            .constraints(root_constraints);
-
-        // Construct a solver and solve the problems in the queue
-        let mut solver = LibSolvRsSolver::new(provider);
-        let solvables = solver.solve(problem).map_err(|unsolvable_or_cancelled| {
-            match unsolvable_or_cancelled {
-                UnsolvableOrCancelled::Unsolvable(problem) => {
-                    SolveError::Unsolvable(vec![problem.display_user_friendly(&solver).to_string()])
-                }
-                // We are not doing this as of yet
-                // put a generic message in here for now
-                UnsolvableOrCancelled::Cancelled(_) => SolveError::Cancelled,
+            provider.pool.intern_version_set(name_id, spec.into())
+        })
+        .collect();
+
+    let problem = Problem::new()
+        .requirements(all_requirements)
+        .constraints(root_constraints);
+
+    // Construct a solver and solve the problems in the queue
+    let mut solver = LibSolvRsSolver::new(provider);
+    let solvables = solver.solve(problem).map_err(|unsolvable_or_cancelled| {
+        match unsolvable_or_cancelled {
+            UnsolvableOrCancelled::Unsolvable(problem) => {
+                SolveError::Unsolvable(conflict::explain_conflict(&problem, &solver))
             }
-        })?;
-
-        // Get the resulting packages from the solver.
-        let required_records = solvables
-            .into_iter()
-            .filter_map(
-                |id| match solver.provider().pool.resolve_solvable(id).record {
-                    SolverPackageRecord::Record(rec) => Some(rec.clone()),
-                    SolverPackageRecord::VirtualPackage(_) => None,
-                },
-            )
-            .collect();
-
-        Ok(required_records)
-    }
+            // We are not doing this as of yet
+            // put a generic message in here for now
+            UnsolvableOrCancelled::Cancelled(_) => SolveError::Cancelled,
+        }
+    })?;
+
+    // Get the resulting packages from the solver.
+    let required_records = solvables
+        .into_iter()
+        .filter_map(
+            |id| match solver.provider().pool.resolve_solvable(id).record {
+                SolverPackageRecord::Record(rec) => Some(rec.clone()),
+                SolverPackageRecord::VirtualPackage(_) => None,
+            },
+        )
+        .collect();
+
+    Ok(required_records)
 }
 
 fn parse_match_spec<'a>(
     pool: &Pool<SolverMatchSpec<'a>>,
     spec_str: &'a str,
     parse_match_spec_cache: &mut HashMap<&'a str, VersionSetId>,
+    spec_cache: Option<&SpecCache>,
 ) -> Result<VersionSetId, ParseMatchSpecError> {
     if let Some(spec_id) = parse_match_spec_cache.get(spec_str) {
         Ok(*spec_id)
     } else {
-        let match_spec = MatchSpec::from_str(spec_str, ParseStrictness::Lenient)?;
-        let (name, spec) = match_spec.into_nameless();
-        let dependency_name = pool.intern_package_name(
-            name.as_ref()
-                .expect("match specs without names are not supported")
-                .as_normalized(),
-        );
+        let (name, spec) = match spec_cache {
+            Some(spec_cache) => spec_cache.parse(spec_str)?,
+            None => parse_nameless_match_spec(spec_str)?,
+        };
+        let dependency_name = pool.intern_package_name(name.as_normalized());
         let version_set_id = pool.intern_version_set(dependency_name, spec.into());
         parse_match_spec_cache.insert(spec_str, version_set_id);
         Ok(version_set_id)
     }
 }
+
+/// Parses a dependency string into the name and the spec of the dependency.
+fn parse_nameless_match_spec(
+    spec_str: &str,
+) -> Result<(PackageName, NamelessMatchSpec), ParseMatchSpecError> {
+    let (name, spec) = MatchSpec::from_str(spec_str, ParseStrictness::Lenient)?.into_nameless();
+    let name = name.expect("match specs without names are not supported");
+    Ok((name, spec))
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpecCache;
+
+    #[test]
+    fn test_spec_cache_eviction() {
+        let cache = SpecCache::with_capacity(2);
+        let cached = |spec_str: &str| cache.lock().specs.contains_key(spec_str);
+
+        cache.parse("foo >=1").unwrap();
+        cache.parse("bar").unwrap();
+        cache.parse("foo >=1").unwrap();
+        assert_eq!(cache.lock().hits, 1);
+
+        // foo was used since it was added, so bar is evicted instead.
+        cache.parse("baz <2").unwrap();
+        assert!(cached("foo >=1"));
+        assert!(!cached("bar"));
+        assert!(cached("baz <2"));
+
+        // foo lost its second chance, so now it is evicted.
+        cache.parse("qux").unwrap();
+        assert!(!cached("foo >=1"));
+        assert!(cached("baz <2"));
+        assert!(cached("qux"));
+        assert_eq!(cache.lock().specs.len(), 2);
+        assert_eq!(cache.lock().queue.len(), 2);
+    }
+}
@@ -0,0 +1,304 @@
+//! Structured explanations of why a [`crate::SolverTask`] could not be solved.
+//!
+//! Both solver backends describe an unsolvable problem as a conflict graph.
+//! The root of the graph represents the requested specs, other nodes are the
+//! candidates that were considered for these specs (and their dependencies)
+//! and the edges describe why the candidates could not be installed together.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rattler_conda_types::{GenericVirtualPackage, RepoDataRecord};
+
+/// A structured explanation of why the solver was unable to find a solution.
+///
+/// The explanation is a graph whose first node is always
+/// [`ConflictNode::Root`]. The edges that originate from the root are the
+/// requested specs that clash, following the [`ConflictEdgeKind::Requires`]
+/// edges from there yields the dependency chains that lead to the conflicts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnsolvableExplanation {
+    /// A user-friendly rendering of the problem as produced by the solver
+    /// backend.
+    pub messages: Vec<String>,
+
+    /// The nodes of the conflict graph.
+    pub nodes: Vec<ConflictNode>,
+
+    /// The edges of the conflict graph. The edges refer to the nodes by their
+    /// index in [`Self::nodes`].
+    pub edges: Vec<ConflictEdge>,
+}
+
+/// A node in the conflict graph of an [`UnsolvableExplanation`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
+pub enum ConflictNode {
+    /// The root of the graph, the requested specs originate from here.
+    Root,
+
+    /// A package that was a candidate for one of the requirements.
+    Package(ConflictPackage),
+
+    /// The target of a requirement for which no candidates exist.
+    Missing,
+
+    /// The reason why a package was excluded from the solve.
+    Excluded {
+        /// A description of the reason
+        reason: String,
+    },
+}
+
+/// A package that is part of a conflict graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConflictPackage {
+    /// The name of the package
+    pub name: String,
+
+    /// The version of the package
+    pub version: String,
+
+    /// The build string of the package
+    pub build: String,
+
+    /// The channel the package originates from or `None` for virtual
+    /// packages.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub channel: Option<String>,
+}
+
+/// An edge between two nodes of a conflict graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConflictEdge {
+    /// The index of the source node
+    pub source: usize,
+
+    /// The index of the target node
+    pub target: usize,
+
+    /// Describes how the source relates to the target
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub kind: ConflictEdgeKind,
+}
+
+/// Describes the relation between two nodes of a conflict graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
+pub enum ConflictEdgeKind {
+    /// The source requires `spec` and the target is one of its candidates
+    /// (or [`ConflictNode::Missing`] if there are none).
+    Requires {
+        /// The requirement
+        spec: String,
+    },
+
+    /// The source constrains the target through `spec`, and the target does
+    /// not satisfy it.
+    Constrains {
+        /// The constraint
+        spec: String,
+    },
+
+    /// The source and the target cannot be installed together, either
+    /// because they are different variants of the same package or because
+    /// the target is locked.
+    Conflict,
+
+    /// The source was excluded for the reason described by the target.
+    Excluded,
+}
+
+impl UnsolvableExplanation {
+    /// Constructs an explanation that only contains the rendered messages.
+    pub fn from_messages(messages: Vec<String>) -> Self {
+        Self {
+            messages,
+            nodes: vec![ConflictNode::Root],
+            edges: Vec::new(),
+        }
+    }
+
+    /// Returns the requested specs that are involved in the conflict.
+    pub fn requested_specs(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.edges
+            .iter()
+            .filter(|edge| edge.source == 0)
+            .filter_map(|edge| match &edge.kind {
+                ConflictEdgeKind::Requires { spec } => Some(spec.as_str()),
+                _ => None,
+            })
+            .filter(|spec| seen.insert(*spec))
+            .collect()
+    }
+
+    /// Returns the packages that were excluded from the solve together with
+    /// the reason they were excluded.
+    pub fn excluded_packages(&self) -> Vec<(&ConflictPackage, &str)> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.kind == ConflictEdgeKind::Excluded)
+            .filter_map(
+                |edge| match (&self.nodes[edge.source], &self.nodes[edge.target]) {
+                    (ConflictNode::Package(package), ConflictNode::Excluded { reason }) => {
+                        Some((package, reason.as_str()))
+                    }
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+
+    /// Returns the shortest chain of requirements that leads from the root to
+    /// the node with the given index, or `None` if the node is not reachable
+    /// from the root.
+    pub fn dependency_chain(&self, node: usize) -> Option<Vec<&ConflictEdge>> {
+        let mut predecessor: HashMap<usize, &ConflictEdge> = HashMap::new();
+        let mut queue = VecDeque::from([0]);
+        let mut visited = HashSet::from([0]);
+        while let Some(current) = queue.pop_front() {
+            if current == node {
+                let mut chain = Vec::new();
+                let mut current = node;
+                while let Some(edge) = predecessor.get(&current) {
+                    chain.push(*edge);
+                    current = edge.source;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+
+            for edge in self.edges.iter().filter(|edge| edge.source == current) {
+                if matches!(edge.kind, ConflictEdgeKind::Requires { .. })
+                    && visited.insert(edge.target)
+                {
+                    predecessor.insert(edge.target, edge);
+                    queue.push_back(edge.target);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl ConflictPackage {
+    /// Constructs a package from a record.
+    pub(crate) fn from_record(record: &RepoDataRecord) -> Self {
+        Self {
+            name: record.package_record.name.as_normalized().to_string(),
+            version: record.package_record.version.to_string(),
+            build: record.package_record.build.clone(),
+            channel: Some(record.channel.clone()),
+        }
+    }
+
+    /// Constructs a package from a virtual package.
+    pub(crate) fn from_virtual_package(package: &GenericVirtualPackage) -> Self {
+        Self {
+            name: package.name.as_normalized().to_string(),
+            version: package.version.to_string(),
+            build: package.build_string.clone(),
+            channel: None,
+        }
+    }
+}
+
+/// Helper to incrementally construct the graph of an
+/// [`UnsolvableExplanation`] without duplicate nodes or edges.
+pub(crate) struct ConflictGraphBuilder {
+    nodes: Vec<ConflictNode>,
+    node_indices: HashMap<ConflictNode, usize>,
+    edges: Vec<ConflictEdge>,
+    edge_set: HashSet<ConflictEdge>,
+}
+
+impl ConflictGraphBuilder {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![ConflictNode::Root],
+            node_indices: HashMap::from([(ConflictNode::Root, 0)]),
+            edges: Vec::new(),
+            edge_set: HashSet::new(),
+        }
+    }
+
+    /// Returns the index of the node, adding it if it does not exist yet.
+    pub fn add_node(&mut self, node: ConflictNode) -> usize {
+        if let Some(&index) = self.node_indices.get(&node) {
+            return index;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.node_indices.insert(node, index);
+        index
+    }
+
+    pub fn add_edge(&mut self, source: usize, target: usize, kind: ConflictEdgeKind) {
+        let edge = ConflictEdge {
+            source,
+            target,
+            kind,
+        };
+        if self.edge_set.insert(edge.clone()) {
+            self.edges.push(edge);
+        }
+    }
+
+    pub fn finish(self, messages: Vec<String>) -> UnsolvableExplanation {
+        UnsolvableExplanation {
+            messages,
+            nodes: self.nodes,
+            edges: self.edges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn package(name: &str) -> ConflictNode {
+        ConflictNode::Package(ConflictPackage {
+            name: name.to_string(),
+            version: String::from("1.0"),
+            build: String::from("h123_0"),
+            channel: None,
+        })
+    }
+
+    #[test]
+    fn test_dependency_chain() {
+        let mut builder = ConflictGraphBuilder::new();
+        let foo = builder.add_node(package("foo"));
+        let bar = builder.add_node(package("bar"));
+        let missing = builder.add_node(ConflictNode::Missing);
+        let requires = |spec: &str| ConflictEdgeKind::Requires {
+            spec: spec.to_string(),
+        };
+        builder.add_edge(0, foo, requires("foo"));
+        builder.add_edge(0, foo, requires("foo"));
+        builder.add_edge(foo, bar, requires("bar >=1"));
+        builder.add_edge(bar, missing, requires("baz"));
+        assert_eq!(builder.add_node(package("foo")), foo);
+
+        let explanation = builder.finish(Vec::new());
+        assert_eq!(explanation.edges.len(), 3);
+        assert_eq!(explanation.requested_specs(), vec!["foo"]);
+
+        let chain = explanation
+            .dependency_chain(missing)
+            .unwrap()
+            .into_iter()
+            .map(|edge| match &edge.kind {
+                ConflictEdgeKind::Requires { spec } => spec.as_str(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(chain, vec!["foo", "bar >=1", "baz"]);
+    }
+}
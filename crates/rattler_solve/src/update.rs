@@ -0,0 +1,131 @@
+//! Support for solving an update of specific packages of an environment while
+//! changing as few of the other packages as possible, see
+//! [`crate::SolverImpl::solve_update`].
+
+use std::collections::{HashMap, HashSet};
+
+use rattler_conda_types::{
+    version_spec::{EqualityOperator, RangeOperator},
+    MatchSpec, NamelessMatchSpec, PackageName, RepoDataRecord, StringMatcher, VersionSpec,
+};
+
+use crate::unsolvable::{ConflictNode, UnsolvableExplanation};
+
+/// The result of [`crate::SolverImpl::solve_update`].
+#[derive(Debug, Clone)]
+pub struct UpdateSolution {
+    /// The records of the updated environment
+    pub records: Vec<RepoDataRecord>,
+
+    /// The changes to packages that were not requested to be updated but that
+    /// had to change to make the update possible. Sorted by name.
+    pub forced_changes: Vec<PackageChange>,
+}
+
+/// A change to a single package of an environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageChange {
+    /// The name of the package
+    pub name: PackageName,
+
+    /// The record before the update or `None` if the package was added
+    pub previous: Option<RepoDataRecord>,
+
+    /// The record after the update or `None` if the package was removed
+    pub current: Option<RepoDataRecord>,
+}
+
+/// Returns a spec that only matches the version and build of the record.
+pub(crate) fn exactly(record: &RepoDataRecord) -> MatchSpec {
+    MatchSpec::from_nameless(
+        NamelessMatchSpec {
+            version: Some(VersionSpec::Exact(
+                EqualityOperator::Equals,
+                record.package_record.version.version().clone(),
+            )),
+            build: Some(StringMatcher::Exact(record.package_record.build.clone())),
+            ..NamelessMatchSpec::default()
+        },
+        Some(record.package_record.name.clone()),
+    )
+}
+
+/// Returns a spec that matches the version of the record or any newer version.
+pub(crate) fn at_least(record: &RepoDataRecord) -> MatchSpec {
+    MatchSpec::from_nameless(
+        NamelessMatchSpec {
+            version: Some(VersionSpec::Range(
+                RangeOperator::GreaterEquals,
+                record.package_record.version.version().clone(),
+            )),
+            ..NamelessMatchSpec::default()
+        },
+        Some(record.package_record.name.clone()),
+    )
+}
+
+/// Returns a spec that only matches versions that are newer than the record.
+pub(crate) fn newer_than(record: &RepoDataRecord) -> MatchSpec {
+    MatchSpec::from_nameless(
+        NamelessMatchSpec {
+            version: Some(VersionSpec::Range(
+                RangeOperator::Greater,
+                record.package_record.version.version().clone(),
+            )),
+            ..NamelessMatchSpec::default()
+        },
+        Some(record.package_record.name.clone()),
+    )
+}
+
+/// Returns the names of the packages that are part of the conflict.
+pub(crate) fn conflicting_packages(explanation: &UnsolvableExplanation) -> HashSet<&str> {
+    explanation
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            ConflictNode::Package(package) => Some(package.name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the changes between the previous and the updated records, ignoring
+/// the packages in `updated`.
+pub(crate) fn forced_changes(
+    previous: &[RepoDataRecord],
+    current: &[RepoDataRecord],
+    updated: &[PackageName],
+) -> Vec<PackageChange> {
+    let previous = previous
+        .iter()
+        .map(|record| (&record.package_record.name, record))
+        .collect::<HashMap<_, _>>();
+    let current = current
+        .iter()
+        .map(|record| (&record.package_record.name, record))
+        .collect::<HashMap<_, _>>();
+
+    let mut changes = previous
+        .keys()
+        .chain(current.keys())
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|name| !updated.contains(name))
+        .filter_map(|name| {
+            let previous = previous.get(name).copied();
+            let current = current.get(name).copied();
+            if previous.map(|record| &record.url) == current.map(|record| &record.url) {
+                return None;
+            }
+            Some(PackageChange {
+                name: name.clone(),
+                previous: previous.cloned(),
+                current: current.cloned(),
+            })
+        })
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
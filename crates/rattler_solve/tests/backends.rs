@@ -7,7 +7,10 @@ use rattler_conda_types::{
     ParseStrictness, RepoData, RepoDataRecord, Version,
 };
 use rattler_repodata_gateway::sparse::SparseRepoData;
-use rattler_solve::{ChannelPriority, SolveError, SolveStrategy, SolverImpl, SolverTask};
+use rattler_solve::{
+    ChannelPriority, ConflictEdgeKind, ConflictNode, SolveError, SolveStrategy, SolverImpl,
+    SolverTask,
+};
 use url::Url;
 
 fn channel_config() -> ChannelConfig {
@@ -113,6 +116,31 @@ fn installed_package(
     }
 }
 
+/// Constructs a package with a file name and URL that identify the record and
+/// that depends on the given specs.
+fn package(name: &str, version: &str, build: &str, depends: &[&str]) -> RepoDataRecord {
+    let mut record = installed_package("conda-forge", "linux-64", name, version, build, 0);
+    record.file_name = format!("{name}-{version}-{build}.conda");
+    record.url = format!("https://example.com/{}", record.file_name)
+        .parse()
+        .unwrap();
+    record.package_record.depends = depends.iter().map(ToString::to_string).collect();
+    record
+}
+
+/// Constructs a variant of a package that requires the given legacy features.
+fn feature_variant(
+    name: &str,
+    build: &str,
+    build_number: u64,
+    features: Option<&str>,
+) -> RepoDataRecord {
+    let mut record = package(name, "1.0", build, &[]);
+    record.package_record.build_number = build_number;
+    record.package_record.features = features.map(ToString::to_string);
+    record
+}
+
 fn solve_real_world<T: SolverImpl + Default>(specs: Vec<&str>) -> Vec<String> {
     let specs = specs
         .iter()
@@ -309,6 +337,42 @@ macro_rules! solver_backend_tests {
             assert!(matches!(result.err(), Some(SolveError::Unsolvable(_))));
         }
 
+        #[test]
+        fn test_solve_dummy_repo_missing_virtual_package_explanation() {
+            let result = solve::<$T>(
+                dummy_channel_json_path(),
+                SimpleSolveTask {
+                    specs: &["bar"],
+                    ..SimpleSolveTask::default()
+                },
+            );
+
+            let Some(SolveError::Unsolvable(explanation)) = result.err() else {
+                panic!("expected the solve to be unsolvable");
+            };
+            assert_eq!(explanation.nodes[0], ConflictNode::Root);
+            assert!(explanation
+                .requested_specs()
+                .iter()
+                .any(|spec| spec.starts_with("bar")));
+
+            // The missing virtual package can be traced back to the requested spec
+            let missing = explanation
+                .nodes
+                .iter()
+                .position(|node| *node == ConflictNode::Missing)
+                .expect("expected a missing requirement");
+            let chain = explanation.dependency_chain(missing).unwrap();
+            assert!(matches!(
+                &chain[0].kind,
+                ConflictEdgeKind::Requires { spec } if spec.starts_with("bar")
+            ));
+            assert!(matches!(
+                &chain.last().unwrap().kind,
+                ConflictEdgeKind::Requires { spec } if spec.starts_with("__unix")
+            ));
+        }
+
         #[test]
         fn test_solve_dummy_repo_with_virtual_package() {
             let pkgs = solve::<$T>(
@@ -542,6 +606,322 @@ macro_rules! solver_backend_tests {
             }
         }
 
+        #[test]
+        fn test_channel_priority_flexible() {
+            use rattler_solve::{ChannelPriority, SolverImpl};
+
+            let high_priority =
+                vec![installed_package("channel-a", "linux-64", "foo", "1.0", "a_0", 0)];
+            let low_priority =
+                vec![installed_package("channel-b", "linux-64", "foo", "2.0", "b_0", 0)];
+
+            let solve_with = |spec: &str, channel_priority: ChannelPriority| {
+                let task = rattler_solve::SolverTask {
+                    specs: vec![MatchSpec::from_str(spec, ParseStrictness::Lenient).unwrap()],
+                    channel_priority,
+                    ..rattler_solve::SolverTask::from_iter([&high_priority, &low_priority])
+                };
+                <$T>::default().solve(task)
+            };
+
+            // The package from the channel with the highest priority is preferred even
+            // though a newer version is available in another channel.
+            let pkgs = solve_with("foo", ChannelPriority::Flexible).unwrap();
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].channel, "channel-a");
+
+            // Strict channel priority never uses the lower priority channel, the
+            // explanation reports the package from that channel as excluded.
+            let Err(SolveError::Unsolvable(explanation)) =
+                solve_with("foo >=2", ChannelPriority::Strict)
+            else {
+                panic!("expected the solve to be unsolvable");
+            };
+            let excluded = explanation.excluded_packages();
+            assert_eq!(excluded.len(), 1);
+            assert_eq!(excluded[0].0.name, "foo");
+            assert_eq!(excluded[0].0.version, "2.0");
+            assert_eq!(excluded[0].0.channel.as_deref(), Some("channel-b"));
+
+            // Flexible channel priority falls back to the lower priority channel.
+            let pkgs = solve_with("foo >=2", ChannelPriority::Flexible).unwrap();
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].channel, "channel-b");
+        }
+
+        #[test]
+        fn test_solve_multi_platform() {
+            use rattler_conda_types::Platform;
+            use rattler_solve::{MultiPlatformSolverTask, SolverImpl, TargetPlatform};
+
+            let record = |subdir: &str, name: &str, depends: &[&str]| {
+                let mut record = package(name, "1.0", "h123_0", depends);
+                record.package_record.subdir = subdir.to_string();
+                record
+            };
+            let linux_64 = vec![record("linux-64", "foo", &["bar"])];
+            let osx_arm64 = vec![record("osx-arm64", "foo", &["bar"])];
+            let win_64 = vec![record("win-64", "foo", &["baz"])];
+            let noarch = vec![record("noarch", "bar", &[])];
+
+            let solve_for = |platforms: &[Platform]| {
+                let task = MultiPlatformSolverTask {
+                    platforms: platforms.iter().copied().map(TargetPlatform::from).collect(),
+                    specs: vec![MatchSpec::from_str("foo", ParseStrictness::Lenient).unwrap()],
+                    ..MultiPlatformSolverTask::from_iter([&linux_64, &osx_arm64, &win_64, &noarch])
+                };
+                <$T>::default().solve_multi_platform(task)
+            };
+
+            // Each platform only uses the records of its own subdir and noarch.
+            let solutions = solve_for(&[Platform::Linux64, Platform::OsxArm64]).unwrap();
+            assert_eq!(solutions.len(), 2);
+            for platform in [Platform::Linux64, Platform::OsxArm64] {
+                let mut records = solutions[&platform]
+                    .iter()
+                    .map(|record| {
+                        (
+                            record.package_record.name.as_normalized(),
+                            record.package_record.subdir.as_str(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                records.sort_unstable();
+                assert_eq!(records, vec![("bar", "noarch"), ("foo", platform.as_str())]);
+            }
+
+            // The error reports the platform that could not be solved.
+            let error = solve_for(&[Platform::Linux64, Platform::Win64]).unwrap_err();
+            assert_eq!(error.platform, Platform::Win64);
+            assert!(matches!(error.error, SolveError::Unsolvable(_)));
+        }
+
+        #[test]
+        fn test_solve_features() {
+            use rattler_solve::SolverImpl;
+
+            let records = vec![
+                feature_variant("numpy", "mkl_1", 1, Some("mkl")),
+                feature_variant("numpy", "blas_0", 0, Some("mkl blas")),
+                feature_variant("numpy", "nomkl_0", 0, None),
+            ];
+
+            let solve_with = |features: &[&str]| {
+                let task = rattler_solve::SolverTask {
+                    specs: vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()],
+                    features: Some(features.iter().map(ToString::to_string).collect()),
+                    ..rattler_solve::SolverTask::from_iter([&records])
+                };
+                <$T>::default().solve(task).unwrap()
+            };
+
+            // Variants that require a feature are only used when it is selected.
+            let pkgs = solve_with(&[]);
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].package_record.build, "nomkl_0");
+
+            let pkgs = solve_with(&["mkl"]);
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].package_record.build, "mkl_1");
+
+            // All the features of a variant have to be selected.
+            let pkgs = solve_with(&["blas"]);
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].package_record.build, "nomkl_0");
+        }
+
+        #[test]
+        fn test_solve_unselected_features() {
+            use rattler_solve::SolverImpl;
+
+            let records = vec![
+                feature_variant("numpy", "mkl_1", 1, Some("mkl")),
+                feature_variant("numpy", "nomkl_0", 0, None),
+            ];
+
+            // Without selected features the features of records are ignored, so the
+            // variant with the highest build number is used like before.
+            let task = rattler_solve::SolverTask {
+                specs: vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()],
+                ..rattler_solve::SolverTask::from_iter([&records])
+            };
+            let pkgs = <$T>::default().solve(task).unwrap();
+            assert_eq!(pkgs.len(), 1);
+            assert_eq!(pkgs[0].package_record.build, "mkl_1");
+        }
+
+        #[test]
+        fn test_solve_preferences() {
+            use rattler_conda_types::Channel;
+            use rattler_solve::{ChannelPriority, Preference, SolverImpl};
+
+            let channel = |name: &str| {
+                Channel::from_str(name, &super::channel_config())
+                    .unwrap()
+                    .canonical_name()
+            };
+            let variant = |channel: &str, version: &str, build: &str, build_number: u64| {
+                let mut record = package("pytorch", version, build, &[]);
+                record.channel = channel.to_string();
+                record.package_record.build_number = build_number;
+                record
+            };
+            let channel_a = vec![
+                variant(&channel("channel-a"), "2.0", "cpu_1", 1),
+                variant(&channel("channel-a"), "1.0", "cuda_0", 0),
+            ];
+            let channel_b = vec![variant(&channel("channel-b"), "1.0", "cpu_0", 0)];
+
+            let solve_with = |preferences: Vec<Preference>| {
+                let task = rattler_solve::SolverTask {
+                    specs: vec![MatchSpec::from_str("pytorch", ParseStrictness::Lenient).unwrap()],
+                    preferences,
+                    channel_priority: ChannelPriority::Disabled,
+                    ..rattler_solve::SolverTask::from_iter([&channel_a, &channel_b])
+                };
+                let pkgs = <$T>::default().solve(task).unwrap();
+                assert_eq!(pkgs.len(), 1);
+                pkgs.into_iter().next().unwrap()
+            };
+            let preference = |spec: &str| {
+                Preference::new(MatchSpec::from_str(spec, ParseStrictness::Lenient).unwrap())
+            };
+
+            // Without preferences the highest version is selected.
+            assert_eq!(solve_with(Vec::new()).package_record.build, "cpu_1");
+
+            // A preference is preferred over a higher version.
+            let pkg = solve_with(vec![preference("pytorch * *cuda*")]);
+            assert_eq!(pkg.package_record.build, "cuda_0");
+
+            // The candidate with the highest total weight is preferred.
+            let pkg = solve_with(vec![
+                preference("pytorch * *cuda*"),
+                preference("pytorch 1.0").with_weight(2),
+                preference("channel-b::pytorch").with_weight(2),
+            ]);
+            assert_eq!(pkg.package_record.build, "cpu_0");
+            assert_eq!(pkg.channel, channel("channel-b"));
+        }
+
+        #[test]
+        fn test_solve_with_inferred_explanation() {
+            use rattler_conda_types::PackageName;
+            use rattler_solve::{InclusionReason, SolverImpl};
+
+            let records = super::read_repodata(&dummy_channel_json_path());
+            let task = rattler_solve::SolverTask {
+                specs: vec![MatchSpec::from_str("foobar", ParseStrictness::Lenient).unwrap()],
+                ..rattler_solve::SolverTask::from_iter([&records])
+            };
+
+            let (pkgs, explanation) = <$T>::default()
+                .solve_with_inferred_explanation(task)
+                .unwrap();
+            assert_eq!(pkgs.len(), 2);
+            assert_eq!(
+                explanation.reasons(&PackageName::new_unchecked("foobar")),
+                &[InclusionReason::Requested { spec: String::from("foobar") }]
+            );
+            assert_eq!(
+                explanation.reasons(&PackageName::new_unchecked("bors")),
+                &[InclusionReason::Dependency {
+                    parent: PackageName::new_unchecked("foobar"),
+                    spec: String::from("bors <2.0"),
+                }]
+            );
+        }
+
+        #[test]
+        fn test_solve_update() {
+            use rattler_conda_types::PackageName;
+            use rattler_solve::SolverImpl;
+
+            let records = vec![
+                package("app", "1.0", "h123_0", &["lib 1.*"]),
+                package("app", "2.0", "h123_0", &["lib 2.*"]),
+                package("lib", "1.0", "h123_0", &[]),
+                package("lib", "2.0", "h123_0", &[]),
+                package("tool", "1.0", "h123_0", &["lib"]),
+                package("tool", "2.0", "h123_0", &["lib"]),
+            ];
+            let locked = vec![records[0].clone(), records[2].clone(), records[4].clone()];
+
+            let task = rattler_solve::SolverTask {
+                specs: ["app", "tool"]
+                    .iter()
+                    .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Lenient).unwrap())
+                    .collect(),
+                locked_packages: locked,
+                ..rattler_solve::SolverTask::from_iter([&records])
+            };
+            let solution = <$T>::default()
+                .solve_update(task, &[PackageName::new_unchecked("app")])
+                .unwrap();
+
+            let mut versions = solution
+                .records
+                .iter()
+                .map(|record| {
+                    format!(
+                        "{}={}",
+                        record.package_record.name.as_normalized(),
+                        record.package_record.version
+                    )
+                })
+                .collect::<Vec<_>>();
+            versions.sort();
+            assert_eq!(versions, vec!["app=2.0", "lib=2.0", "tool=1.0"]);
+
+            // Only lib had to change to make the update of app possible.
+            assert_eq!(solution.forced_changes.len(), 1);
+            let change = &solution.forced_changes[0];
+            assert_eq!(change.name.as_normalized(), "lib");
+            assert_eq!(change.previous.as_ref().unwrap().package_record.version.as_str(), "1.0");
+            assert_eq!(change.current.as_ref().unwrap().package_record.version.as_str(), "2.0");
+        }
+
+        #[test]
+        fn test_solve_update_relaxes_target() {
+            use rattler_conda_types::PackageName;
+            use rattler_solve::SolverImpl;
+
+            let records = vec![
+                package("app", "1.0", "h123_0", &["lib 1.*"]),
+                package("app", "2.0", "h123_0", &["lib 1.*"]),
+                package("app", "3.0", "h123_0", &["lib 2.*"]),
+                package("lib", "1.0", "h123_0", &[]),
+                package("lib", "2.0", "h123_0", &[]),
+            ];
+            let locked = vec![records[0].clone(), records[3].clone()];
+
+            let task = rattler_solve::SolverTask {
+                specs: vec![MatchSpec::from_str("app", ParseStrictness::Lenient).unwrap()],
+                locked_packages: locked,
+                ..rattler_solve::SolverTask::from_iter([&records])
+            };
+            let solution = <$T>::default()
+                .solve_update(task, &[PackageName::new_unchecked("app")])
+                .unwrap();
+
+            // The latest version of app requires a newer lib, so app is updated to
+            // the newest version that works with the locked lib instead.
+            let mut versions = solution
+                .records
+                .iter()
+                .map(|record| {
+                    format!(
+                        "{}={}",
+                        record.package_record.name.as_normalized(),
+                        record.package_record.version
+                    )
+                })
+                .collect::<Vec<_>>();
+            versions.sort();
+            assert_eq!(versions, vec!["app=2.0", "lib=1.0"]);
+            assert!(solution.forced_changes.is_empty());
+        }
+
         #[test]
         fn test_constraints() {
             // There following package is provided as .tar.bz and as .conda in repodata.json
@@ -604,11 +984,13 @@ macro_rules! solver_backend_tests {
 mod libsolv_c {
     #![allow(unused_imports)] // For some reason windows thinks this is an unused import.
 
+    use rattler_conda_types::{MatchSpec, ParseStrictness};
     use rattler_solve::{ChannelPriority, SolveStrategy};
 
     use super::{
-        dummy_channel_json_path, installed_package, solve, solve_real_world, FromStr,
-        GenericVirtualPackage, SimpleSolveTask, SolveError, Version,
+        dummy_channel_json_path, feature_variant, installed_package, package, solve,
+        solve_real_world, ConflictEdgeKind, ConflictNode, FromStr, GenericVirtualPackage,
+        SimpleSolveTask, SolveError, Version,
     };
 
     solver_backend_tests!(rattler_solve::libsolv_c::Solver);
@@ -650,6 +1032,8 @@ mod libsolv_c {
                 available_packages: [libsolv_repodata],
                 specs,
                 constraints: Vec::new(),
+                features: None,
+                preferences: Vec::new(),
                 pinned_packages: Vec::new(),
                 timeout: None,
                 channel_priority: ChannelPriority::default(),
@@ -704,12 +1088,132 @@ mod resolvo {
     use url::Url;
 
     use super::{
-        dummy_channel_json_path, installed_package, solve, solve_real_world, FromStr,
-        GenericVirtualPackage, SimpleSolveTask, SolveError, Version,
+        dummy_channel_json_path, feature_variant, installed_package, package, read_repodata, solve,
+        solve_real_world, ConflictEdgeKind, ConflictNode, FromStr, GenericVirtualPackage,
+        SimpleSolveTask, SolveError, Version,
     };
 
     solver_backend_tests!(rattler_solve::resolvo::Solver);
 
+    #[test]
+    fn test_solve_prefers_enabled_features() {
+        let records = vec![
+            feature_variant("numpy", "nomkl_1", 1, None),
+            feature_variant("numpy", "mkl_0", 0, Some("mkl")),
+        ];
+
+        let task = SolverTask {
+            specs: vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()],
+            features: Some(vec![String::from("mkl")]),
+            ..SolverTask::from_iter([&records])
+        };
+
+        // The variant with the enabled feature is preferred over the variant with the
+        // higher build number.
+        let pkgs = rattler_solve::resolvo::Solver.solve(task).unwrap();
+        assert_eq!(pkgs.len(), 1);
+        assert_eq!(pkgs[0].package_record.build, "mkl_0");
+    }
+
+    #[test]
+    fn test_solve_reusing_session() {
+        let repo_data = read_repodata(&dummy_channel_json_path());
+        let tasks = [
+            vec!["foobar"],
+            vec!["foo<4"],
+            vec!["foobar", "bors<2"],
+            vec!["foobar"],
+        ];
+        let task = |specs: &[&str]| SolverTask {
+            specs: specs
+                .iter()
+                .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Lenient).unwrap())
+                .collect(),
+            ..SolverTask::from_iter([&repo_data])
+        };
+
+        // Solves with a reused session give the same results as solves with a fresh
+        // solver.
+        let mut session = rattler_solve::resolvo::SolverSession::default();
+        for specs in &tasks {
+            let reused = session.solve(task(specs)).unwrap();
+            let fresh = rattler_solve::resolvo::Solver.solve(task(specs)).unwrap();
+            assert_eq!(reused, fresh);
+        }
+
+        // The dependencies of foobar were parsed by the first solve and are taken from
+        // the cache by the later solves.
+        assert!(session.cache_hits() > 0);
+
+        session.clear_cache();
+        assert_eq!(session.cache_hits(), 0);
+        assert!(!session.solve(task(&["foobar"])).unwrap().is_empty());
+        assert_eq!(session.cache_hits(), 0);
+        assert!(!session.solve(task(&["foobar"])).unwrap().is_empty());
+        assert!(session.cache_hits() > 0);
+
+        // A session without capacity never hits the cache.
+        let mut session = rattler_solve::resolvo::SolverSession::with_capacity(0);
+        for specs in &tasks {
+            session.solve(task(specs)).unwrap();
+        }
+        assert_eq!(session.cache_hits(), 0);
+    }
+
+    #[test]
+    fn test_solve_violated_constraint_explanation() {
+        let result = solve::<rattler_solve::resolvo::Solver>(
+            dummy_channel_json_path(),
+            SimpleSolveTask {
+                specs: &["bors >=2.1"],
+                constraints: vec!["bors ==1.0"],
+                ..SimpleSolveTask::default()
+            },
+        );
+
+        let Err(SolveError::Unsolvable(explanation)) = result else {
+            panic!("expected the solve to be unsolvable");
+        };
+        assert_eq!(explanation.messages.len(), 1);
+        assert!(
+            explanation.messages[0].contains("bors ==1.0"),
+            "{}",
+            explanation.messages[0]
+        );
+
+        // The violated constraint is part of the conflict graph
+        assert!(explanation.edges.iter().any(|edge| {
+            let constrains_bors = matches!(
+                &edge.kind,
+                ConflictEdgeKind::Constrains { spec } if spec == "bors ==1.0"
+            );
+            edge.source == 0 && constrains_bors
+        }));
+    }
+
+    #[test]
+    fn test_solve_disabled_feature_explanation() {
+        let records = vec![feature_variant("numpy", "mkl_0", 0, Some("mkl"))];
+
+        let task = SolverTask {
+            specs: vec![MatchSpec::from_str("numpy", ParseStrictness::Lenient).unwrap()],
+            features: Some(Vec::new()),
+            ..SolverTask::from_iter([&records])
+        };
+
+        let Err(SolveError::Unsolvable(explanation)) = rattler_solve::resolvo::Solver.solve(task)
+        else {
+            panic!("expected the solve to be unsolvable");
+        };
+        let excluded = explanation.excluded_packages();
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0].0.build, "mkl_0");
+        assert_eq!(
+            excluded[0].1,
+            "the package requires the feature 'mkl' which is not enabled"
+        );
+    }
+
     #[test]
     fn test_solve_locked() {
         let result = solve::<rattler_solve::resolvo::Solver>(
@@ -1193,10 +1697,11 @@ fn channel_priority_strict() {
 
 #[test]
 #[should_panic(
-    expected = "called `Result::unwrap()` on an `Err` value: Unsolvable([\"The following packages \
-    are incompatible\\n└─ pytorch-cpu ==0.4.1 py36_cpu_1 cannot be installed because there are no \
-    viable options:\\n   └─ pytorch-cpu 0.4.1 is excluded because due to strict channel priority \
-    not using this option from: 'https://conda.anaconda.org/pytorch/'\\n\"])"
+    expected = "called `Result::unwrap()` on an `Err` value: Unsolvable(UnsolvableExplanation { \
+    messages: [\"The following packages are incompatible\\n└─ pytorch-cpu ==0.4.1 py36_cpu_1 cannot \
+    be installed because there are no viable options:\\n   └─ pytorch-cpu 0.4.1 is excluded because \
+    due to strict channel priority not using this option from: \
+    'https://conda.anaconda.org/pytorch/'\\n\"]"
 )]
 fn channel_priority_strict_panic() {
     let repodata = vec![
@@ -1225,11 +1730,47 @@ fn channel_priority_disabled() {
     );
 }
 
+#[test]
+fn channel_priority_flexible() {
+    // Solve with conda-forge as the first channel
+    let repodata = vec![
+        read_conda_forge_sparse_repo_data(),
+        read_pytorch_sparse_repo_data(),
+    ];
+    solve_to_get_channel_of_spec::<rattler_solve::resolvo::Solver>(
+        "pytorch-cpu",
+        "https://conda.anaconda.org/conda-forge/",
+        repodata.clone(),
+        ChannelPriority::Flexible,
+    );
+
+    // A package that is only available in the lower priority channel can still be
+    // selected.
+    solve_to_get_channel_of_spec::<rattler_solve::resolvo::Solver>(
+        "pytorch-cpu=0.4.1=py36_cpu_1",
+        "https://conda.anaconda.org/pytorch/",
+        repodata,
+        ChannelPriority::Flexible,
+    );
+
+    // Solve with pytorch as the first channel
+    let repodata = vec![
+        read_pytorch_sparse_repo_data(),
+        read_conda_forge_sparse_repo_data(),
+    ];
+    solve_to_get_channel_of_spec::<rattler_solve::resolvo::Solver>(
+        "pytorch-cpu",
+        "https://conda.anaconda.org/pytorch/",
+        repodata,
+        ChannelPriority::Flexible,
+    );
+}
+
 #[cfg(feature = "libsolv_c")]
 #[test]
 #[should_panic(
-    expected = "called `Result::unwrap()` on an `Err` value: Unsolvable([\"package \
-    pytorch-cpu-0.4.1-py36_cpu_1 is excluded by strict repo priority\"])"
+    expected = "called `Result::unwrap()` on an `Err` value: Unsolvable(UnsolvableExplanation { \
+    messages: [\"package pytorch-cpu-0.4.1-py36_cpu_1 is excluded by strict repo priority\"]"
 )]
 fn channel_priority_strict_libsolv_c() {
     let repodata = vec![
@@ -1260,3 +1801,38 @@ fn channel_priority_disabled_libsolv_c() {
         ChannelPriority::Disabled,
     );
 }
+
+#[cfg(feature = "libsolv_c")]
+#[test]
+fn channel_priority_flexible_libsolv_c() {
+    let repodata = vec![
+        read_conda_forge_sparse_repo_data(),
+        read_pytorch_sparse_repo_data(),
+    ];
+    solve_to_get_channel_of_spec::<rattler_solve::libsolv_c::Solver>(
+        "pytorch-cpu",
+        "https://conda.anaconda.org/conda-forge/",
+        repodata.clone(),
+        ChannelPriority::Flexible,
+    );
+
+    // A package that is only available in the lower priority channel can still be
+    // selected.
+    solve_to_get_channel_of_spec::<rattler_solve::libsolv_c::Solver>(
+        "pytorch-cpu=0.4.1=py36_cpu_1",
+        "https://conda.anaconda.org/pytorch/",
+        repodata,
+        ChannelPriority::Flexible,
+    );
+
+    let repodata = vec![
+        read_pytorch_sparse_repo_data(),
+        read_conda_forge_sparse_repo_data(),
+    ];
+    solve_to_get_channel_of_spec::<rattler_solve::libsolv_c::Solver>(
+        "pytorch-cpu",
+        "https://conda.anaconda.org/pytorch/",
+        repodata,
+        ChannelPriority::Flexible,
+    );
+}
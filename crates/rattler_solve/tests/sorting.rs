@@ -43,6 +43,7 @@ fn create_sorting_snapshot(package_name: &str, strategy: SolveStrategy) -> Strin
         &[],
         &[match_spec.clone()],
         None,
+        None,
         ChannelPriority::default(),
         None,
         strategy,
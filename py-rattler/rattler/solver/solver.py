@@ -59,7 +59,9 @@ async def solve(
         virtual_packages: A list of virtual packages considered active.
         channel_priority: (Default = ChannelPriority.Strict) When `ChannelPriority.Strict`
                  the channel that the package is first found in will be used as
-                 the only channel for that package. When `ChannelPriority.Disabled`
+                 the only channel for that package. When `ChannelPriority.Flexible`
+                 lower-priority channels are only used when the higher-priority
+                 channels cannot satisfy the request. When `ChannelPriority.Disabled`
                  it will search for every package in every channel.
         timeout:    The maximum time the solver is allowed to run.
         exclude_newer: Exclude any record that is newer than the given datetime.
@@ -157,7 +159,9 @@ async def solve_with_sparse_repodata(
         virtual_packages: A list of virtual packages considered active.
         channel_priority: (Default = ChannelPriority.Strict) When `ChannelPriority.Strict`
                  the channel that the package is first found in will be used as
-                 the only channel for that package. When `ChannelPriority.Disabled`
+                 the only channel for that package. When `ChannelPriority.Flexible`
+                 lower-priority channels are only used when the higher-priority
+                 channels cannot satisfy the request. When `ChannelPriority.Disabled`
                  it will search for every package in every channel.
         timeout:    The maximum time the solver is allowed to run.
         exclude_newer: Exclude any record that is newer than the given datetime.
@@ -82,6 +82,8 @@ pub fn py_solve(
                 virtual_packages: virtual_packages.into_iter().map(Into::into).collect(),
                 specs: specs.into_iter().map(Into::into).collect(),
                 constraints: constraints.into_iter().map(Into::into).collect(),
+                features: None,
+                preferences: Vec::new(),
                 timeout: timeout.map(std::time::Duration::from_micros),
                 channel_priority: channel_priority.into(),
                 exclude_newer,
@@ -158,6 +160,8 @@ pub fn py_solve_with_sparse_repodata(
                 virtual_packages: virtual_packages.into_iter().map(Into::into).collect(),
                 specs: specs.into_iter().map(Into::into).collect(),
                 constraints: constraints.into_iter().map(Into::into).collect(),
+                features: None,
+                preferences: Vec::new(),
                 timeout: timeout.map(std::time::Duration::from_micros),
                 channel_priority: channel_priority.into(),
                 exclude_newer,
@@ -74,6 +74,7 @@ async fn main() {
         &[],
         &[],
         None,
+        None,
         ChannelPriority::default(),
         None,
         SolveStrategy::default(),